@@ -0,0 +1,395 @@
+//! Campaigns, either a list of level paths or a TOML file like
+//!
+//! ```toml
+//! title = "First Story"
+//! description = "..."
+//! music = "music"
+//! carry_health = true
+//! carry_weapons = true
+//! difficulty = "hard"
+//!
+//! [loadout]
+//! hp = 100
+//! armour = 5
+//! weapons = ["glock"]
+//! grenades = 1
+//! utilities = { flashbang = 2, smoke = 1 }
+//!
+//! [[levels]]
+//! path = "levels/house1.lvl"
+//! briefing = "..."
+//! next = ["house2", "mansion"]
+//! ```
+//!
+//! `next` gives the level following each exit of a level (see `Action::Exit`),
+//! otherwise the campaign just goes on to the next level in the list.
+//! `difficulty` makes the whole campaign be played on that difficulty,
+//! unless a level sets its own.
+
+use std::path::{Path, PathBuf};
+use std::fs::read_to_string;
+use std::collections::BTreeMap;
+
+use topskud::{
+    world::{Level, Difficulty, GRENADE},
+    obj::{health::Health, player::WepSlots, weapon::{WeaponInstance, WEAPONS}, grenade::Throwable},
+};
+use ggez::{GameResult, error::GameError};
+
+use super::StateSwitch;
+
+fn yes() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+/// A campaign file in the structured format
+struct CampaignDef {
+    title: Option<String>,
+    #[serde(default)]
+    description: String,
+    music: Option<String>,
+    #[serde(default = "yes")]
+    carry_health: bool,
+    #[serde(default = "yes")]
+    carry_weapons: bool,
+    difficulty: Option<Difficulty>,
+    #[serde(default)]
+    loadout: LoadoutDef,
+    levels: Vec<LevelDef>,
+}
+
+impl CampaignDef {
+    /// Reads a campaign file of either format
+    fn parse(source: &str) -> Result<Self, toml::de::Error> {
+        match toml::from_str(source) {
+            Ok(def) => Ok(def),
+            Err(e) if source.contains(['=', '[']) => Err(e),
+            // Old campaign files are just a list of level paths
+            Err(_) => Ok(CampaignDef {
+                title: None,
+                description: String::new(),
+                music: None,
+                carry_health: true,
+                carry_weapons: true,
+                difficulty: None,
+                loadout: LoadoutDef::default(),
+                levels: source.lines().map(str::trim).filter(|l| !l.is_empty()).map(|l| LevelDef {
+                    path: l.to_owned(),
+                    name: None,
+                    briefing: String::new(),
+                    next: Vec::new(),
+                }).collect(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct LoadoutDef {
+    hp: f32,
+    armour: f32,
+    weapons: Vec<String>,
+    grenades: u8,
+    /// Other throwables by id
+    utilities: BTreeMap<String, u8>,
+}
+
+impl Default for LoadoutDef {
+    fn default() -> Self {
+        let Health{hp, armour} = Health::default();
+        LoadoutDef {
+            hp,
+            armour,
+            weapons: Vec::new(),
+            grenades: 0,
+            utilities: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LevelDef {
+    path: String,
+    /// Name used by other levels to branch to this one, defaults to the file name
+    name: Option<String>,
+    #[serde(default)]
+    briefing: String,
+    /// Names of the levels following each exit, the first one being the normal exit
+    #[serde(default)]
+    next: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CampaignLevel {
+    pub name: String,
+    pub path: PathBuf,
+    pub briefing: String,
+    /// Indices of the levels following each exit
+    pub next: Vec<usize>,
+}
+
+pub struct Campaign {
+    /// Name of the campaign file, used to keep track of progress
+    pub name: String,
+    pub path: PathBuf,
+    pub title: String,
+    pub description: String,
+    /// Music played on the briefing screens
+    pub music: Option<String>,
+    pub carry_health: bool,
+    pub carry_weapons: bool,
+    /// Difficulty the campaign is played on instead of the one picked
+    pub difficulty: Option<Difficulty>,
+    /// What the player starts the campaign with
+    pub loadout: (Health, WepSlots),
+    pub levels: Vec<Level>,
+    pub entries: Vec<CampaignLevel>,
+    pub current: usize,
+    /// Points left to spend in the armoury
+    pub points: u32,
+    /// Weapons found in earlier levels that aren't being carried
+    pub stash: Vec<WeaponInstance<'static>>,
+    /// Whether cheats were used earlier in the run, their effects possibly carried along with the player's gear
+    pub cheated: bool,
+}
+
+impl Campaign {
+    pub fn load<P: AsRef<Path>>(p: P) -> GameResult<Self> {
+        let path = p.as_ref().to_owned();
+        let name = path.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let source = read_to_string(&path)?;
+
+        let def = CampaignDef::parse(&source)
+            .map_err(|e| GameError::ResourceLoadError(format!("Couldn't read campaign {}: {}", path.display(), e)))?;
+
+        let mut levels = Vec::with_capacity(def.levels.len());
+        let mut entries = Vec::with_capacity(def.levels.len());
+        for level in &def.levels {
+            levels.push(Level::load(&level.path)?);
+            entries.push(CampaignLevel {
+                name: level.name.clone().unwrap_or_else(|| {
+                    Path::new(&level.path).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+                }),
+                path: PathBuf::from(&level.path),
+                briefing: level.briefing.clone(),
+                next: Vec::new(),
+            });
+        }
+        for (i, level) in def.levels.iter().enumerate() {
+            for next in &level.next {
+                let n = entries.iter().position(|e| &e.name == next)
+                    .ok_or_else(|| GameError::ResourceLoadError(format!("No level called {} in campaign", next)))?;
+                entries[i].next.push(n);
+            }
+        }
+
+        let mut wep = WepSlots::default();
+        for id in &def.loadout.weapons {
+            let weapon = WEAPONS.get(&**id).ok_or_else(|| GameError::ResourceLoadError(format!("No weapon called {}", id)))?;
+            if let Some(old) = wep.add_weapon(weapon.make_instance()) {
+                warn!("{} replaced {} in the loadout", id, old.weapon.id);
+            }
+        }
+        wep.init_active();
+        wep.utilities.set(GRENADE, def.loadout.grenades);
+        for (id, &count) in &def.loadout.utilities {
+            let throwable = Throwable::get(id).ok_or_else(|| GameError::ResourceLoadError(format!("No throwable called {}", id)))?;
+            wep.utilities.set(&throwable.id, count);
+        }
+
+        Ok(Campaign {
+            title: def.title.unwrap_or_else(|| name.clone()),
+            name,
+            path,
+            description: def.description,
+            music: def.music,
+            carry_health: def.carry_health,
+            carry_weapons: def.carry_weapons,
+            difficulty: def.difficulty,
+            loadout: (Health{hp: def.loadout.hp, armour: def.loadout.armour}, wep),
+            levels,
+            entries,
+            current: 0,
+            points: 0,
+            stash: Vec::new(),
+            cheated: false,
+        })
+    }
+    pub fn next_level(&mut self) -> Option<Level> {
+        let ret = self.levels.get(self.current).cloned();
+        self.current += 1;
+        ret
+    }
+    /// Moves to the level with the given index
+    pub fn play_level(&mut self, i: usize) -> Option<Level> {
+        self.current = i;
+        self.next_level()
+    }
+    /// Index of the level being played
+    #[inline]
+    pub fn playing(&self) -> Option<usize> {
+        self.current.checked_sub(1)
+    }
+    /// Indices of the levels each level can lead to through any of its exits
+    pub fn followers(&self) -> Vec<Vec<usize>> {
+        self.entries.iter().enumerate().map(|(i, entry)| {
+            if !entry.next.is_empty() {
+                entry.next.clone()
+            } else if i + 1 < self.levels.len() {
+                vec![i + 1]
+            } else {
+                Vec::new()
+            }
+        }).collect()
+    }
+    /// Index of the level following the exit the current level was left through
+    pub fn after_exit(&self, exit: usize) -> usize {
+        self.playing().and_then(|i| self.entries[i].next.get(exit)).copied().unwrap_or(self.current)
+    }
+    /// Moves on to the level following the exit the current level was left through
+    #[inline]
+    pub fn take_exit(&mut self, exit: usize) {
+        self.current = self.after_exit(exit);
+    }
+    /// What the player starts the next level with
+    ///
+    /// `carried` is what the player had at the end of the last level
+    pub fn carry(&self, carried: Option<(Health, WepSlots)>) -> (Health, WepSlots) {
+        let (mut health, mut wep) = self.loadout.clone();
+        if let Some((h, w)) = carried {
+            if self.carry_health {
+                health = h;
+            }
+            if self.carry_weapons {
+                wep = w;
+            }
+        }
+        (health, wep)
+    }
+    /// Moves to the level with the given index and makes the switch to start it
+    ///
+    /// `carried` is what the player had at the end of the last level
+    #[inline]
+    pub fn start_level(&mut self, i: usize, carried: Option<(Health, WepSlots)>) -> Option<StateSwitch> {
+        if carried.is_none() {
+            self.cheated = false;
+        }
+        let (health, wep) = self.carry(carried);
+        self.start_level_with(i, health, wep)
+    }
+    /// Like `start_level`, but starting with exactly the given health and weapons
+    pub fn start_level_with(&mut self, i: usize, health: Health, wep: WepSlots) -> Option<StateSwitch> {
+        let lvl = Box::new(self.play_level(i)?);
+
+        Some(if self.entries[i].briefing.is_empty() {
+            StateSwitch::PlayWith{lvl, health, wep}
+        } else {
+            StateSwitch::Briefing{lvl, health, wep}
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use topskud::world::Palette;
+
+    #[test]
+    fn parses_structured_campaigns() {
+        let def = CampaignDef::parse(r#"
+            title = "Test"
+            carry_weapons = false
+            difficulty = "hard"
+
+            [loadout]
+            weapons = ["glock"]
+            grenades = 2
+
+            [[levels]]
+            path = "levels/a.lvl"
+            next = ["b", "c"]
+
+            [[levels]]
+            path = "levels/b.lvl"
+            briefing = "Get out"
+
+            [[levels]]
+            path = "levels/c.lvl"
+            name = "c"
+        "#).unwrap();
+
+        assert_eq!(def.title.as_deref(), Some("Test"));
+        assert!(def.carry_health);
+        assert!(!def.carry_weapons);
+        assert_eq!(def.difficulty, Some(Difficulty::Hard));
+        assert_eq!(def.loadout.weapons, ["glock"]);
+        assert_eq!(def.loadout.grenades, 2);
+        assert_eq!(def.loadout.hp, Health::default().hp);
+        assert_eq!(def.levels.len(), 3);
+        assert_eq!(def.levels[0].next, ["b", "c"]);
+        assert_eq!(def.levels[1].briefing, "Get out");
+        assert_eq!(def.levels[2].name.as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn parses_lists_of_level_paths() {
+        let def = CampaignDef::parse("levels/a.lvl\n\n  levels/b b.lvl  \n").unwrap();
+
+        assert!(def.title.is_none());
+        assert!(def.carry_health && def.carry_weapons);
+        assert!(def.difficulty.is_none());
+        let paths: Vec<_> = def.levels.iter().map(|l| &*l.path).collect();
+        assert_eq!(paths, ["levels/a.lvl", "levels/b b.lvl"]);
+    }
+
+    #[test]
+    fn reports_broken_structured_campaigns() {
+        assert!(CampaignDef::parse("title = \"Test\"\n[[levels]]\npath = ").is_err());
+        // Missing the levels altogether
+        assert!(CampaignDef::parse("title = \"Test\"").is_err());
+    }
+
+    /// A campaign of empty levels, each with the given followers
+    fn campaign(next: Vec<Vec<usize>>) -> Campaign {
+        Campaign {
+            name: "test".to_owned(),
+            path: PathBuf::from("test"),
+            title: "Test".to_owned(),
+            description: String::new(),
+            music: None,
+            carry_health: true,
+            carry_weapons: true,
+            difficulty: None,
+            loadout: (Health::default(), WepSlots::default()),
+            levels: vec![Level::new(Palette::new(Vec::new()), 1, 1); next.len()],
+            entries: next.into_iter().enumerate().map(|(i, next)| CampaignLevel {
+                name: i.to_string(),
+                path: PathBuf::from(i.to_string()),
+                briefing: String::new(),
+                next,
+            }).collect(),
+            current: 0,
+            points: 0,
+            stash: Vec::new(),
+            cheated: false,
+        }
+    }
+
+    #[test]
+    fn exits_lead_to_their_levels() {
+        let mut cam = campaign(vec![vec![2, 1], vec![], vec![]]);
+        cam.play_level(0);
+        assert_eq!(cam.after_exit(0), 2);
+        assert_eq!(cam.after_exit(1), 1);
+        // Exits without a level of their own go on to the next level in the list
+        assert_eq!(cam.after_exit(2), 1);
+
+        cam.take_exit(0);
+        assert_eq!(cam.current, 2);
+        cam.play_level(2);
+        assert_eq!(cam.after_exit(0), 3);
+        assert_eq!(cam.followers(), [vec![2, 1], vec![2], vec![]]);
+    }
+}
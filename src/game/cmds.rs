@@ -2,8 +2,10 @@ use std::collections::HashMap;
 use super::{Console, State, GameState, Command, CommandError, Content, StateSwitch};
 
 use topskud::{
-    util::dbg_strs,
-    obj::{health::Health, weapon::WEAPONS},
+    util::{dbg_strs, Point2},
+    obj::{Object, weapon::WEAPONS, grenade::THROWABLES},
+    io::{save::{SaveGame, SAVE_SLOTS, QUICKSAVE_SLOT}, achievement::ACHIEVEMENTS},
+    world::{Objective, Goal, Grid, Action, Condition},
 };
 use ggez::Context;
 
@@ -35,6 +37,7 @@ pub(super) fn commands() -> HashMap<String, Command> {
         "" => {Ok(())},
         "pi", "intels" => {
             let world = gs.get_mut_world().ok_or(NoWorld)?;
+            world.intels_collected += world.intels.len() as u16;
             world.intels.clear();
             info!("Intels got");
             Ok(())
@@ -102,16 +105,11 @@ pub(super) fn commands() -> HashMap<String, Command> {
         "cmp" => {if let Content::Campaign(ref mut cmp) = state.content {
             if let Some(i) = args.get(1) {
                 let i = i.parse().map_err(|_| InvalidArg)?;
-                cmp.current = i;
-                let lvl = cmp.next_level().ok_or(NoSuchLevel)?;
+                let carried = gs.get_world().map(|world| (world.player.health, world.player.wep.clone()));
+                cmp.cheated = true;
+                let switch = cmp.start_level(i, carried).ok_or(NoSuchLevel)?;
 
-                let (health, wep) = if let Some(world) = gs.get_world() {
-                    (world.player.health, world.player.wep.clone())
-                } else {
-                    (Health::default(), Default::default())
-                };
-
-                state.switch(StateSwitch::PlayWith{health, wep, lvl: Box::new(lvl)});
+                state.switch(switch);
             } else {
                 info!("{} levels. Current is {}", cmp.levels.len(), cmp.current);
             }
@@ -119,18 +117,219 @@ pub(super) fn commands() -> HashMap<String, Command> {
         } else {
             Err(NoCampaign)
         }},
+        "save" => {
+            let slot = args.get(1).map(|s| s.parse().map_err(|_| InvalidArg)).transpose()?.unwrap_or(QUICKSAVE_SLOT);
+            if slot >= SAVE_SLOTS {
+                return Err(InvalidArg);
+            }
+            let save = gs.save_game(state).ok_or(NoWorld)?;
+            match save.save(slot) {
+                Ok(()) => info!("Saved to {}", SaveGame::slot_name(slot)),
+                Err(e) => error!("Couldn't save game: {}", e),
+            }
+            Ok(())
+        },
+        "load" => {
+            let slot = args.get(1).map(|s| s.parse().map_err(|_| InvalidArg)).transpose()?.unwrap_or(QUICKSAVE_SLOT);
+            if !SaveGame::exists(slot) {
+                return Err(NoSuchSave);
+            }
+            match SaveGame::load(slot) {
+                Ok(save) => state.switch(StateSwitch::Load(Box::new(save))),
+                Err(e) => error!("Couldn't load game: {}", e),
+            }
+            Ok(())
+        },
+        "obj", "objective" => {
+            let level = gs.get_mut_level().ok_or(NoLevel)?;
+            let mouse = state.mouse - state.offset;
+            let index = || -> Result<usize, CommandError> {
+                args.get(2).ok_or(InvalidArg)?.parse().map_err(|_| InvalidArg)
+            };
+            let num = |i: usize| -> Result<f32, CommandError> {
+                args.get(i).ok_or(InvalidArg)?.parse().map_err(|_| InvalidArg)
+            };
+
+            let goal = match args.get(1).copied() {
+                None | Some("") | Some("list") => {
+                    for (i, objective) in level.objectives.iter().enumerate() {
+                        info!("{}: {}", i, objective);
+                    }
+                    return Ok(());
+                }
+                Some("rm") => {
+                    let i = index()?;
+                    if i < level.objectives.len() {
+                        level.objectives.remove(i);
+                    }
+                    return Ok(());
+                }
+                Some("opt") => {
+                    let objective = level.objectives.get_mut(index()?).ok_or(InvalidArg)?;
+                    objective.optional = !objective.optional;
+                    return Ok(());
+                }
+                Some("ord") => {
+                    let objective = level.objectives.get_mut(index()?).ok_or(InvalidArg)?;
+                    objective.ordered = !objective.ordered;
+                    return Ok(());
+                }
+                Some("killall") => Goal::KillAll,
+                Some("target") => Goal::KillTarget(closest(level.enemies.iter().map(|e| e.pl.obj.pos), mouse).ok_or(InvalidArg)?),
+                Some("intels") => Goal::CollectIntels(if args.len() > 2 { num(2)? as u16 } else { level.intels.len() as u16 }),
+                Some("reach") => Goal::Reach{pos: mouse, radius: if args.len() > 2 { num(2)? } else { 32. }},
+                Some("survive") => Goal::Survive(num(2)?),
+                Some("protect") => Goal::Protect(closest(level.npcs.iter().map(|npc| npc.obj.pos), mouse).ok_or(InvalidArg)?),
+                Some("defuse") => Goal::Defuse{pos: mouse, time_limit: num(2)?},
+                Some(_) => return Err(InvalidArg),
+            };
+            let objective = Objective::new(goal);
+            info!("{}: {}", level.objectives.len(), objective);
+            level.objectives.push(objective);
+            Ok(())
+        },
+        "trig", "trigger" => {
+            let level = gs.get_mut_level().ok_or(NoLevel)?;
+            let mouse = state.mouse - state.offset;
+
+            let i = match args.get(1).copied() {
+                None | Some("") | Some("list") => {
+                    for (i, trigger) in level.triggers.iter().enumerate() {
+                        info!("{}: {}", i, trigger);
+                    }
+                    return Ok(());
+                }
+                Some(i) => i.parse::<usize>().map_err(|_| InvalidArg)?,
+            };
+            if i >= level.triggers.len() {
+                return Err(InvalidArg);
+            }
+            let trigger = &mut level.triggers[i];
+            let action = match args.get(2).copied() {
+                None | Some("") => None,
+                Some("rm") => {
+                    level.triggers.remove(i);
+                    return Ok(());
+                }
+                Some("enter") => {
+                    trigger.condition = Condition::Enter;
+                    None
+                }
+                Some("obj") => {
+                    trigger.condition = Condition::ObjectiveComplete(args.get(3).ok_or(InvalidArg)?.parse().map_err(|_| InvalidArg)?);
+                    None
+                }
+                Some("once") => {
+                    trigger.once = !trigger.once;
+                    None
+                }
+                Some("undo") => {
+                    trigger.actions.pop();
+                    None
+                }
+                Some("wp") | Some("cyclic") => {
+                    let patrol = trigger.actions.iter_mut().rev().find_map(|a| match a {
+                        Action::Patrol{path, cyclical, ..} => Some((path, cyclical)),
+                        _ => None,
+                    });
+                    match (args[2], patrol) {
+                        ("wp", Some((path, _))) => path.push(mouse),
+                        (_, Some((_, cyclical))) => *cyclical = !*cyclical,
+                        (_, None) => return Err(InvalidArg),
+                    }
+                    None
+                }
+                Some("msg") => Some(Action::Message(args[3..].join(" "))),
+                Some("sound") => Some(Action::PlaySound(args.get(3).ok_or(InvalidArg)?.to_string())),
+                Some("spawn") => {
+                    let weapon = match args.get(3) {
+                        Some(&wep) => Some(WEAPONS.get(wep).ok_or(NoSuchWeapon)?.id.to_owned()),
+                        None => None,
+                    };
+                    Some(Action::SpawnEnemy{obj: Object::new(mouse), weapon})
+                }
+                Some("door") => {
+                    let (x, y) = Grid::snap(mouse);
+                    Some(Action::OpenDoor{x, y})
+                }
+                Some("patrol") => Some(Action::Patrol{
+                    enemy: closest(level.enemies.iter().map(|e| e.pl.obj.pos), mouse).ok_or(InvalidArg)?,
+                    path: Vec::new(),
+                    cyclical: false,
+                }),
+                Some("win") => Some(Action::EndLevel{won: true}),
+                Some("lose") => Some(Action::EndLevel{won: false}),
+                Some("exit") => Some(Action::Exit(args.get(3).ok_or(InvalidArg)?.parse().map_err(|_| InvalidArg)?)),
+                Some(_) => return Err(InvalidArg),
+            };
+            let trigger = &mut level.triggers[i];
+            if let Some(action) = action {
+                trigger.actions.push(action);
+            }
+            info!("{}: {}", i, trigger);
+            Ok(())
+        },
+        "script" => {
+            let level = gs.get_mut_level().ok_or(NoLevel)?;
+            match args.get(1).copied() {
+                None | Some("") => match &level.script {
+                    Some(script) => info!("Script: {}", script),
+                    None => info!("No script"),
+                },
+                Some("rm") => level.script = None,
+                Some(path) => level.script = Some(path.to_owned()),
+            }
+            Ok(())
+        },
+        "par" => {
+            let level = gs.get_mut_level().ok_or(NoLevel)?;
+            match args.get(1).copied() {
+                None | Some("") => match level.par_time {
+                    Some(par) => info!("Par time: {}s", par),
+                    None => info!("No par time"),
+                },
+                Some("rm") => level.par_time = None,
+                Some(secs) => level.par_time = Some(secs.parse().ok().filter(|&p: &f32| p > 0.).ok_or(InvalidArg)?),
+            }
+            Ok(())
+        },
         "gg" => {
             let world = gs.get_mut_world().ok_or(NoWorld)?;
-            world.player.wep.utilities.grenades += 3;
+            for throwable in THROWABLES.iter() {
+                world.player.wep.utilities.add(&throwable.id, 3);
+            }
             info!("Grenades given");
             Ok(())
         },
         "ggg" => {
             let world = gs.get_mut_world().ok_or(NoWorld)?;
-            world.player.wep.utilities.grenades += 100;
+            for throwable in THROWABLES.iter() {
+                world.player.wep.utilities.add(&throwable.id, 100);
+            }
             info!("Gggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggggg'd");
             Ok(())
         },
+        "name" => {
+            match args.get(1).copied() {
+                None | Some("") => info!("Name: {}", state.profile.name()),
+                Some(_) => {
+                    state.profile.name = args[1..].join(" ");
+                    state.profile.save().map_err(|e| {
+                        error!("Couldn't save profile: {}", e);
+                        InvalidArg
+                    })?;
+                }
+            }
+            Ok(())
+        },
+        "achievements" => {
+            let progress = &state.profile.achievements;
+            for achievement in ACHIEVEMENTS.iter() {
+                let mark = if progress.is_unlocked(&achievement.id) { "x" } else { " " };
+                info!("[{}] {}: {}", mark, achievement.name, achievement.description);
+            }
+            Ok(())
+        },
         "hello" => {
             info!("Hello!");
             Ok(())
@@ -144,4 +343,12 @@ pub(super) fn commands() -> HashMap<String, Command> {
             Ok(())
         },
     }
-}
\ No newline at end of file
+}
+/// Index of the point closest to `p`
+fn closest(points: impl Iterator<Item=Point2>, p: Point2) -> Option<usize> {
+    points
+        .map(|q| (q - p).norm())
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, _)| i)
+}
@@ -1,4 +1,4 @@
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::sync::Mutex;
 use std::fmt::{self, Display};
 use std::collections::HashMap;
@@ -7,12 +7,15 @@ use topskud::io::ctrl::{Controls, Input};
 use topskud::{
     DESIRED_FPS,
     util::{Vector2, Point2, RED, GREEN, BLUE},
-    world::{World, Level, Statistics},
+    world::{World, Level, Statistics, Checkpoint, Difficulty},
     io::{
         snd::MediaPlayer,
         tex::{Assets, PosText},
+        save::{SaveGame, ContentSave, WeaponSave},
+        profile::Profile,
+        leaderboard::level_key,
     },
-    obj::{health::Health, player::WepSlots},
+    obj::{health::Health, player::WepSlots, weapon::WeaponInstance},
 };
 use ggez::graphics::{Canvas, Drawable};
 use ggez::input::keyboard::{KeyCode, KeyInput, KeyMods};
@@ -28,6 +31,8 @@ use log::{Log, Metadata, Record, Level as LogLevel};
 use lazy_static::lazy_static;
 
 pub mod states;
+mod campaign;
+pub use self::campaign::Campaign;
 
 use self::states::menu::Menu;
 
@@ -40,6 +45,30 @@ pub enum StateSwitch {
         health: Health,
         wep: WepSlots,
     },
+    /// Continue a level from a checkpoint
+    Resume{
+        lvl: Box<Level>,
+        health: Health,
+        wep: WepSlots,
+        checkpoint: Box<Checkpoint>,
+    },
+    /// Show the briefing of a campaign level before playing it
+    Briefing{
+        lvl: Box<Level>,
+        health: Health,
+        wep: WepSlots,
+    },
+    /// Continue a saved game
+    Load(Box<SaveGame>),
+    LoadMenu,
+    LevelSelect,
+    /// Rearrange and buy gear between campaign levels
+    Intermission{
+        health: Health,
+        wep: WepSlots,
+        points: u32,
+        weapons_left: Vec<WeaponInstance<'static>>,
+    },
     Lose(Box<Statistics>),
     Win(Box<Statistics>),
 }
@@ -64,6 +93,13 @@ pub trait GameState {
     fn get_mut_world(&mut self) -> Option<&mut World> {
         None
     }
+    fn get_mut_level(&mut self) -> Option<&mut Level> {
+        None
+    }
+    /// The state of the game to be saved, if there's anything to save
+    fn save_game(&self, _: &State) -> Option<SaveGame> {
+        None
+    }
 }
 
 lazy_static! {
@@ -131,10 +167,12 @@ pub struct Console {
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum CommandError {
     NoWorld,
+    NoLevel,
     NoCampaign,
     InvalidArg,
     NoSuchLevel,
     NoSuchWeapon,
+    NoSuchSave,
 }
 
 impl Display for CommandError {
@@ -142,16 +180,21 @@ impl Display for CommandError {
         use self::CommandError::*;
         match *self {
             NoWorld => "No world".fmt(f),
+            NoLevel => "No level being edited".fmt(f),
             NoCampaign => "No campaign loaded".fmt(f),
             InvalidArg => "Invalid argument".fmt(f),
             NoSuchLevel => "No such level".fmt(f),
             NoSuchWeapon => "No such weapon".fmt(f),
+            NoSuchSave => "No such save".fmt(f),
         }
     }
 }
 
 mod cmds;
 
+/// Commands that disable achievements for the level being played
+const CHEATS: &[&str] = &["pi", "intels", "fa", "fullarmour", "god", "godarmour", "ga", "wep", "cmp", "gg", "ggg"];
+
 type Command = for<'a> fn(console: &'a mut Console, ctx: &'a mut Context, state: &'a mut State, gs: &'a mut dyn GameState, args: Vec<&'a str>) -> Result<(), CommandError>;
 
 impl Console {
@@ -161,7 +204,7 @@ impl Console {
 
         Ok(Console {
             commands: cmds::commands(),
-            history: assets.raw_text_with("Acheivements disabled.\n", 18.),
+            history: assets.raw_text_with("", 18.),
             prompt: assets.text(point!(0., PROMPT_Y)).and_text("> ").and_text(String::with_capacity(32)),
         })
     }
@@ -177,9 +220,15 @@ impl Console {
         let command_name = args[0];
 
         match self.commands.get(command_name) {
-            Some(cmd) => if let Err(e) = cmd(self, ctx, state, gs, args) {
-                error!("{}", e);
-            },
+            Some(cmd) => {
+                if CHEATS.contains(&command_name) && !state.cheated {
+                    state.cheated = true;
+                    warn!("Achievements disabled for this run");
+                }
+                if let Err(e) = cmd(self, ctx, state, gs, args) {
+                    error!("{}", e);
+                }
+            }
             None => warn!("  Unknown command `{}'!", command_name),
         }
 
@@ -227,12 +276,79 @@ pub struct Master {
     console: Console,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Playing the level as it was made
+    Normal,
+    /// Racing the ghost of the best run
+    TimeAttack,
+    /// Holding out against waves of enemies for as long as possible
+    Survival,
+}
+
+impl Mode {
+    pub fn next(self) -> Self {
+        match self {
+            Mode::Normal => Mode::TimeAttack,
+            Mode::TimeAttack => Mode::Survival,
+            Mode::Survival => Mode::Normal,
+        }
+    }
+}
+
+impl Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Normal => "Normal",
+            Mode::TimeAttack => "Time attack",
+            Mode::Survival => "Survival",
+        }.fmt(f)
+    }
+}
+
+#[allow(clippy::large_enum_variant)]
 pub enum Content {
     Campaign(Campaign),
     File(PathBuf),
     None
 }
 
+impl Content {
+    /// Name of the leaderboard of the level being played
+    fn level_key(&self) -> Option<String> {
+        match self {
+            Content::Campaign(cam) => cam.playing().map(|i| format!("{}-{}", cam.name, level_key(&cam.entries[i].path))),
+            Content::File(p) => Some(level_key(p)),
+            Content::None => None,
+        }
+    }
+    fn to_save(&self) -> ContentSave {
+        match self {
+            Content::Campaign(cam) => ContentSave::Campaign{
+                path: cam.path.clone(),
+                current: cam.current,
+                points: cam.points,
+                stash: cam.stash.iter().map(From::from).collect(),
+            },
+            Content::File(p) => ContentSave::File(p.clone()),
+            Content::None => ContentSave::None,
+        }
+    }
+    fn from_save(content: ContentSave) -> GameResult<Self> {
+        Ok(match content {
+            ContentSave::Campaign{path, current, points, stash} => {
+                let mut cam = Campaign::load(path)?;
+                cam.current = current;
+                cam.points = points;
+                cam.stash = stash.into_iter().filter_map(WeaponSave::restore).collect();
+                Content::Campaign(cam)
+            }
+            ContentSave::File(p) => Content::File(p),
+            ContentSave::None => Content::None,
+        })
+    }
+}
+
 /// The state of the game
 pub struct State {
     assets: Assets,
@@ -244,6 +360,12 @@ pub struct State {
     controls: Controls,
     switch_state: Option<StateSwitch>,
     content: Content,
+    profile: Profile,
+    /// How levels are being played
+    mode: Mode,
+    difficulty: Difficulty,
+    /// Whether cheats have been used in the level being played, disabling achievements
+    cheated: bool,
 }
 
 impl Master {
@@ -290,8 +412,12 @@ impl Master {
         controls.bind(Input::Weapon2, KeyCode::Key2);
         controls.bind(Input::Weapon3, KeyCode::Key3);
         controls.bind(Input::Weapon4, KeyCode::Key4);
+        controls.bind(Input::NextThrowable, KeyCode::Key5);
         controls.bind(Input::DropWeapon, KeyCode::G);
         controls.bind(Input::PickupWeapon, KeyCode::F);
+        controls.bind(Input::DragBody, KeyCode::E);
+        controls.bind(Input::QuickSave, KeyCode::F5);
+        controls.bind(Input::QuickLoad, KeyCode::F9);
 
         // Editor bindings
         controls.bind(Input::SaveLevel, KeyCode::Z);
@@ -306,6 +432,9 @@ impl Master {
         controls.bind(Input::RotateRight, KeyCode::E);
         controls.bind(Input::MakeWaypoints, KeyCode::H);
         controls.bind(Input::ToggleCyclicPath, KeyCode::O);
+        controls.bind(Input::ToggleFog, KeyCode::F);
+        controls.bind(Input::CycleAmbient, KeyCode::L);
+        controls.bind(Input::CycleDifficulty, KeyCode::Y);
         controls.bind(Input::DragUp, KeyCode::Up);
         controls.bind(Input::DragDown, KeyCode::Down);
         controls.bind(Input::DragLeft, KeyCode::Left);
@@ -319,8 +448,13 @@ impl Master {
         controls.bind(Input::Confirm, KeyCode::Return);
         controls.bind(Input::Restart, KeyCode::R);
 
+        let profile = Profile::load();
         let mut state = State {
             content,
+            difficulty: profile.difficulty,
+            profile,
+            mode: Mode::Normal,
+            cheated: false,
             controls,
             switch_state: None,
             assets,
@@ -348,6 +482,14 @@ impl State {
     fn switch(&mut self, ss: StateSwitch) {
         self.switch_state = Some(ss);
     }
+    /// Switches to the next difficulty, remembering it in the profile
+    fn cycle_difficulty(&mut self) {
+        self.difficulty = self.difficulty.next();
+        self.profile.difficulty = self.difficulty;
+        if let Err(e) = self.profile.save() {
+            error!("Couldn't save profile: {}", e);
+        }
+    }
 }
 
 use std::mem;
@@ -363,6 +505,12 @@ impl EventHandler for Master {
             self.gs = match gsb {
                 PlayWith{lvl, health, wep} => states::play::Play::new(ctx, &mut self.state, *lvl, Some((health, wep))),
                 Play(lvl) => states::play::Play::new(ctx, &mut self.state, lvl, None),
+                Resume{lvl, health, wep, checkpoint} => states::play::Play::resume(ctx, &mut self.state, *lvl, Some((health, wep)), *checkpoint),
+                Load(save) => states::play::Play::from_save(ctx, &mut self.state, *save),
+                LoadMenu => states::load::LoadMenu::new(ctx, &mut self.state),
+                Briefing{lvl, health, wep} => states::briefing::Briefing::new(ctx, &mut self.state, *lvl, health, wep),
+                LevelSelect => states::level_select::LevelSelect::new(ctx, &mut self.state),
+                Intermission{health, wep, points, weapons_left} => states::intermission::Intermission::new(ctx, &mut self.state, health, wep, points, weapons_left),
                 Menu => states::menu::Menu::new(ctx, &mut self.state),
                 Editor(l) => states::editor::Editor::new(&self.state, l),
                 Win(stats) => states::win::Win::new(ctx, &mut self.state, *stats),
@@ -531,35 +679,3 @@ impl EventHandler for Master {
         Ok(false)
     }
 }
-
-
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-
-pub struct Campaign {
-    pub levels: Vec<Level>,
-    pub current: usize,
-}
-
-impl Campaign {
-    pub fn load<P: AsRef<Path>>(p: P) -> GameResult<Self> {
-        let file = BufReader::new(File::open(p)?);
-
-        let mut levels = Vec::new();
-
-        for line in file.lines() {
-            let line = line?;
-            levels.push(Level::load(&line.trim())?);
-        }
-
-        Ok(Campaign {
-            levels,
-            current: 0,
-        })
-    }
-    pub fn next_level(&mut self) -> Option<Level> {
-        let ret = self.levels.get(self.current).cloned();
-        self.current += 1;
-        ret
-    }
-}
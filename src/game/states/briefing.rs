@@ -0,0 +1,86 @@
+use topskud::{
+    world::Level,
+    io::{
+        tex::PosText,
+        btn::Button,
+        ctrl::Input,
+    },
+    obj::{health::Health, player::WepSlots},
+};
+use crate::game::{State, Content, GameState, StateSwitch};
+use ggez::{
+    Context, GameResult,
+    graphics::{Rect, Canvas},
+};
+
+/// Shows the briefing of a campaign level before it's played
+pub struct Briefing {
+    title_txt: PosText,
+    briefing_txt: PosText,
+    start_btn: Button<()>,
+    music: Option<String>,
+    level: Level,
+    health: Health,
+    wep: WepSlots,
+}
+
+impl Briefing {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(ctx: &mut Context, s: &mut State, level: Level, health: Health, wep: WepSlots) -> GameResult<Box<dyn GameState>> {
+        let w = s.width;
+
+        let (title, briefing, music) = match &s.content {
+            Content::Campaign(cam) => {
+                let entry = cam.playing().and_then(|i| cam.entries.get(i));
+                (
+                    entry.map(|e| e.name.clone()).unwrap_or_default(),
+                    entry.map(|e| e.briefing.clone()).unwrap_or_default(),
+                    cam.music.clone(),
+                )
+            }
+            Content::File(_) | Content::None => Default::default(),
+        };
+
+        if let Some(music) = &music {
+            if !s.mplayer.has_music(music) {
+                s.mplayer.register_music(ctx, &**music, true)?;
+            }
+            s.mplayer.play(ctx, music)?;
+        }
+
+        let mut briefing_txt = s.assets.text(point!(w / 6., 64.)).and_text(briefing);
+        briefing_txt.text.set_bounds(vector!(2. * w / 3., f32::INFINITY));
+
+        Ok(Box::new(Briefing {
+            title_txt: s.assets.text_sized(point!(w / 2., 16.), 32.).and_text(title).centered(),
+            briefing_txt,
+            start_btn: Button::new(ctx, &s.assets, Rect{x: 3. * w / 7., y: s.height - 96., w: w / 7., h: 64.}, "Start", ())?,
+            music,
+            level,
+            health,
+            wep,
+        }))
+    }
+    fn start(&self, s: &mut State, ctx: &mut Context) {
+        if let Some(music) = &self.music {
+            s.mplayer.stop(ctx, music).unwrap();
+        }
+        s.switch(StateSwitch::PlayWith{lvl: Box::new(self.level.clone()), health: self.health, wep: self.wep.clone()});
+    }
+}
+
+impl GameState for Briefing {
+    fn draw_hud(&mut self, _s: &State, canvas: &mut Canvas, _ctx: &mut Context) -> GameResult<()> {
+        self.title_txt.draw_text(canvas);
+        self.briefing_txt.draw_text(canvas);
+        self.start_btn.draw(canvas);
+        Ok(())
+    }
+    fn event_up(&mut self, s: &mut State, ctx: &mut Context, input: Input) {
+        match input {
+            Input::Confirm => self.start(s, ctx),
+            Input::LeftClick if self.start_btn.in_bounds(s.mouse) => self.start(s, ctx),
+            _ => (),
+        }
+    }
+}
@@ -1,15 +1,15 @@
 use topskud::{
     util::{
         sstr,
-        TRANS,
+        TRANS, GREEN,
         Point2
     },
-    world::{Grid, Level, Palette},
+    world::{Grid, Level, Palette, Goal, Trigger, Area, Action, Difficulty},
     io::tex::PosText,
     io::ctrl::{Input, KeyMods, Axis},
     ext::BoolExt,
     DELTA,
-    obj::{Object, enemy::Enemy, decal::Decal, pickup::PICKUPS, weapon::WEAPONS}
+    obj::{Object, enemy::Enemy, player::Player, decal::Decal, pickup::PICKUPS, weapon::WEAPONS, security::{Camera, AlarmPanel}}
 };
 use crate::game::{
     Content, GameState, State, StateSwitch,
@@ -24,6 +24,7 @@ use std::{path::PathBuf, iter};
 use std::io::Read;
 use std::fs::File;
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, PartialEq, Clone)]
 enum Tool {
     Inserter(Insertion),
@@ -34,12 +35,20 @@ enum Tool {
 enum Insertion {
     Material(u8),
     Intel,
+    Checkpoint,
+    /// Where enemies come from in survival
+    Spawn,
+    Alarm,
+    Camera{rot: f32},
     Enemy{rot: f32},
+    Npc{rot: f32},
     Waypoint(usize),
     Pickup(u8),
     Weapon(&'static str),
     Decal{spr: &'static str, rot: f32},
     Exit,
+    /// A trigger area, holding the first corner once it's been placed
+    Trigger(Option<Point2>),
 }
 impl Insertion {
     fn get_spr(&self) -> &str {
@@ -47,9 +56,15 @@ impl Insertion {
         match *self {
             Material(_) => panic!("Get it yourself. I don't have the palette"),
             Intel => "common/intel",
+            Checkpoint => "common/checkpoint",
+            Spawn => "common/spawn",
+            Alarm => "common/alarm",
+            Camera{..} => "common/camera",
             Enemy{..} => "common/enemy",
+            Npc{..} => "common/player",
             Waypoint(..) => "common/cursor",
             Exit => "common/goal",
+            Trigger(_) => "common/crosshair_cursor",
             Pickup(i) => PICKUPS[i as usize].spr,
             Weapon(wep) => &*WEAPONS[wep].entity_sprite, 
             Decal{spr, ..} => spr,
@@ -62,11 +77,17 @@ impl ::std::cmp::PartialEq for Insertion {
         match (self, rhs) {
             (Material(m), Material(n)) if m == n => true,
             (Intel, Intel) => true,
+            (Checkpoint, Checkpoint) => true,
+            (Spawn, Spawn) => true,
+            (Alarm, Alarm) => true,
+            (Camera{..}, Camera{..}) => true,
             (Enemy{..}, Enemy{..}) => true,
+            (Npc{..}, Npc{..}) => true,
             (Pickup(i), Pickup(j)) if i == j => true,
             (Weapon(i), Weapon(j)) if i == j => true,
             (Decal{spr, ..}, Decal{spr: spr2, ..}) if spr == spr2 => true,
             (Exit, Exit) => true,
+            (Trigger(_), Trigger(_)) => true,
             _ => false
         }
     }
@@ -76,11 +97,17 @@ impl ::std::cmp::PartialEq for Insertion {
 struct Selection {
     exit: bool,
     enemies: Vec<usize>,
+    npcs: Vec<usize>,
     waypoints: Vec<(usize, usize)>,
     intels: Vec<usize>,
+    checkpoints: Vec<usize>,
+    spawns: Vec<usize>,
+    alarms: Vec<usize>,
+    cameras: Vec<usize>,
     pickups: Vec<usize>,
     weapons: Vec<usize>,
     decals: Vec<usize>,
+    triggers: Vec<usize>,
     moving: Option<Point2>,
 }
 
@@ -165,14 +192,24 @@ impl Editor {
         let mat_text = s.assets.text(point!(2., 18.0)).and_text("Materials:");
         let mut entities = vec![
             Insertion::Enemy{rot: 0.},
+            Insertion::Npc{rot: 0.},
             Insertion::Exit,
             Insertion::Intel,
+            Insertion::Checkpoint,
+            Insertion::Spawn,
+            Insertion::Alarm,
+            Insertion::Camera{rot: 0.},
+            Insertion::Trigger(None),
             Insertion::Pickup(0),
             Insertion::Pickup(1),
             Insertion::Pickup(2),
             Insertion::Pickup(3),
             Insertion::Pickup(4),
             Insertion::Pickup(5),
+            Insertion::Pickup(6),
+            Insertion::Pickup(7),
+            Insertion::Pickup(8),
+            Insertion::Pickup(9),
         ];
 
         let EditorFile{palettes: EditorPalettes{materials, weapons, decals}} = {
@@ -185,12 +222,15 @@ impl Editor {
         entities.extend(weapons.into_iter().map(|wep| Insertion::Weapon(sstr(wep))));
         entities.extend(decals.into_iter().map(|dec| Insertion::Decal{rot: 0., spr: sstr(dec)}));
 
-        let extra_entities = entities.drain(20..).collect();
+        let palette = Palette::new(materials.into_iter().map(sstr).collect());
 
-        let entities_bar = InsertionBar::new(point!(392., 18.0), s, "Entitites:", entities.into_boxed_slice());
-        let extra_bar = InsertionBar::new(point!(392., 52.0), s, "", extra_entities);
+        // Put the entities bar after the materials and fit as many entities on it as there's room for
+        let entities_x = (START_X + palette.len() as f32 * 36. + 4.).max(392.);
+        let entities_fit = ((s.width - entities_x - 82.) / 34.) as usize;
+        let extra_entities = entities.drain(entities_fit.min(entities.len())..).collect();
 
-        let palette = Palette::new(materials.into_iter().map(sstr).collect());
+        let entities_bar = InsertionBar::new(point!(entities_x, 18.0), s, "Entitites:", entities.into_boxed_slice());
+        let extra_bar = InsertionBar::new(point!(392., 52.0), s, "", extra_entities);
 
         let save;
         if let Content::File(ref f) = s.content {
@@ -232,19 +272,40 @@ impl Editor {
         self.level.enemies
             .iter_mut()
             .flat_map(|e| iter::once(&mut e.pl.obj.pos).chain(&mut e.behaviour.path))
+            .chain(self.level.npcs.iter_mut().map(|npc| &mut npc.obj.pos))
+            .chain(self.level.objectives.iter_mut().filter_map(|o| match o.goal {
+                Goal::Reach{ref mut pos, ..} | Goal::Defuse{ref mut pos, ..} => Some(pos),
+                _ => None,
+            }))
             .chain(self.level.exit.as_mut())
             .chain(self.level.start_point.as_mut())
             .chain(iter::once(&mut self.pos))
             .chain(self.level.intels.iter_mut())
+            .chain(self.level.checkpoints.iter_mut())
+            .chain(self.level.spawns.iter_mut())
+            .chain(self.level.alarms.iter_mut())
+            .chain(self.level.cameras.iter_mut().map(|c| &mut c.obj.pos))
             .chain(self.level.weapons.iter_mut().map(|w| &mut w.pos))
             .chain(self.level.pickups.iter_mut().map(|p| &mut p.0))
             .chain(self.level.decals.iter_mut().map(|d| &mut d.obj.pos))
+            .chain(self.level.triggers.iter_mut().flat_map(|t| {
+                let Trigger{area, actions, ..} = t;
+                iter::once(&mut area.min)
+                    .chain(iter::once(&mut area.max))
+                    .chain(actions.iter_mut().flat_map(|a| match a {
+                        Action::SpawnEnemy{obj, ..} => std::slice::from_mut(&mut obj.pos).iter_mut(),
+                        Action::Patrol{path, ..} => path.iter_mut(),
+                        _ => [].iter_mut(),
+                    }))
+            }))
             .for_each(f)
     }
 }
 
 const START_X: f32 = 103.;
 const YELLOW: Color = Color{r: 1., g: 1., b: 0., a: 1.};
+/// The ambient light levels cycled through, brightest first
+const AMBIENT_LEVELS: [f32; 5] = [1., 0.7, 0.45, 0.25, 0.1];
 
 impl GameState for Editor {
     fn update(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
@@ -255,6 +316,8 @@ impl GameState for Editor {
 
         match self.current {
             Tool::Inserter(Insertion::Enemy{ref mut rot}) => *rot += self.rotation_speed * DELTA,
+            Tool::Inserter(Insertion::Camera{ref mut rot}) => *rot += self.rotation_speed * DELTA,
+            Tool::Inserter(Insertion::Npc{ref mut rot}) => *rot += self.rotation_speed * DELTA,
             Tool::Inserter(Insertion::Decal{ref mut rot, ..}) => *rot += self.rotation_speed * DELTA,
             _ => (),
         }
@@ -318,6 +381,52 @@ impl GameState for Editor {
             let img = s.assets.get_img("common/intel");
             canvas.draw(&*img, drawparams);
         }
+        for (i, &checkpoint) in self.level.checkpoints.iter().enumerate() {
+            if let Tool::Selector(Selection{ref checkpoints, ..}) = self.current {
+                if checkpoints.contains(&i) {
+                    let mesh = Mesh::new_circle(ctx, DrawMode::fill(), checkpoint, 17., 0.5, YELLOW)?;
+                    canvas.draw(&mesh, DrawParam::default());
+                }
+            }
+            let drawparams = graphics::DrawParam::default()
+                .dest(checkpoint)
+                .offset(point!(0.5, 0.5));
+
+            let img = s.assets.get_img("common/checkpoint");
+            canvas.draw(&*img, drawparams);
+        }
+        for (i, &spawn) in self.level.spawns.iter().enumerate() {
+            if let Tool::Selector(Selection{ref spawns, ..}) = self.current {
+                if spawns.contains(&i) {
+                    let mesh = Mesh::new_circle(ctx, DrawMode::fill(), spawn, 17., 0.5, YELLOW)?;
+                    canvas.draw(&mesh, DrawParam::default());
+                }
+            }
+            let drawparams = graphics::DrawParam::default()
+                .dest(spawn)
+                .offset(point!(0.5, 0.5));
+
+            let img = s.assets.get_img("common/spawn");
+            canvas.draw(&*img, drawparams);
+        }
+        for (i, &alarm) in self.level.alarms.iter().enumerate() {
+            if let Tool::Selector(Selection{ref alarms, ..}) = self.current {
+                if alarms.contains(&i) {
+                    let mesh = Mesh::new_circle(ctx, DrawMode::fill(), alarm, 17., 0.5, YELLOW)?;
+                    canvas.draw(&mesh, DrawParam::default());
+                }
+            }
+            AlarmPanel::new(alarm).draw(canvas, &s.assets);
+        }
+        for (i, camera) in self.level.cameras.iter().enumerate() {
+            if let Tool::Selector(Selection{ref cameras, ..}) = self.current {
+                if cameras.contains(&i) {
+                    let mesh = Mesh::new_circle(ctx, DrawMode::fill(), camera.obj.pos, 17., 0.5, YELLOW)?;
+                    canvas.draw(&mesh, DrawParam::default());
+                }
+            }
+            camera.draw(canvas, &s.assets, Color::WHITE);
+        }
 
         for (i, enemy) in self.level.enemies.iter().enumerate() {
             if let Tool::Selector(Selection{ref enemies, ..})= self.current {
@@ -327,7 +436,7 @@ impl GameState for Editor {
                 }
             }
             if self.draw_visibility_cones {
-                enemy.draw_visibility_cone(ctx, canvas, 512.)?;
+                enemy.draw_visibility_cone(ctx, canvas, 512., &self.level.palette, &self.level.grid)?;
             }
             let mut points_lines = vec![enemy.pl.obj.pos];
             
@@ -349,6 +458,48 @@ impl GameState for Editor {
                 canvas.draw(&*img, DrawParam::default().offset(point!(0.5, 0.5)).dest(enemy.pl.obj.pos));
             }
         }
+        for (i, npc) in self.level.npcs.iter().enumerate() {
+            if let Tool::Selector(Selection{ref npcs, ..})= self.current {
+                if npcs.contains(&i) {
+                    let mesh = Mesh::new_circle(ctx, DrawMode::fill(), npc.obj.pos, 17., 0.5, YELLOW)?;
+                    canvas.draw(&mesh, DrawParam::default());
+                }
+            }
+            npc.draw(canvas, &s.assets, "common/player", GREEN);
+        }
+        for objective in &self.level.objectives {
+            match objective.goal {
+                Goal::Reach{pos, radius} => {
+                    let mesh = Mesh::new_circle(ctx, DrawMode::stroke(2.), pos, radius, 0.5, GREEN)?;
+                    canvas.draw(&mesh, DrawParam::default());
+                }
+                Goal::Defuse{pos, ..} => {
+                    let img = s.assets.get_img("weapons/pineapple");
+                    canvas.draw(&*img, DrawParam::default().offset(point!(0.5, 0.5)).dest(pos));
+                }
+                _ => (),
+            }
+        }
+        for (i, trigger) in self.level.triggers.iter().enumerate() {
+            let Area{min, ..} = trigger.area;
+            let rect = Rect{x: min.x, y: min.y, w: trigger.area.width(), h: trigger.area.height()};
+            if let Tool::Selector(Selection{ref triggers, ..}) = self.current {
+                if triggers.contains(&i) {
+                    let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, Color{a: 0.25, .. YELLOW})?;
+                    canvas.draw(&mesh, DrawParam::default());
+                }
+            }
+            let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(2.), rect, YELLOW)?;
+            canvas.draw(&mesh, DrawParam::default());
+            canvas.draw(&s.assets.raw_text_with(&format!("#{}", i), 16.), DrawParam::from(min + vector!(3., 3.)).color(YELLOW));
+
+            for action in &trigger.actions {
+                if let Action::SpawnEnemy{obj, ..} = action {
+                    let img = s.assets.get_img("common/enemy");
+                    obj.draw(canvas, &img, TRANS);
+                }
+            }
+        }
         for (i, decal) in self.level.decals.iter().enumerate() {
             if let Tool::Selector(Selection{ref decals, ..})= self.current {
                 if decals.contains(&i) {
@@ -394,6 +545,11 @@ impl GameState for Editor {
                 enem.pl.obj.pos += dist;
                 enem.draw(canvas, &s.assets, TRANS);
             }
+            for &i in &selection.npcs {
+                let mut npc = self.level.npcs[i].clone();
+                npc.obj.pos += dist;
+                npc.draw(canvas, &s.assets, "common/player", TRANS);
+            }
             for &i in &selection.intels {
                 let drawparams = graphics::DrawParam::default()
                     .dest(self.level.intels[i] + dist)
@@ -403,6 +559,38 @@ impl GameState for Editor {
                 let img = s.assets.get_img("common/intel");
                 canvas.draw(&*img, drawparams);
             }
+            for &i in &selection.checkpoints {
+                let drawparams = graphics::DrawParam::default()
+                    .dest(self.level.checkpoints[i] + dist)
+                    .offset(point!(0.5, 0.5))
+                    .color(TRANS);
+
+                let img = s.assets.get_img("common/checkpoint");
+                canvas.draw(&*img, drawparams);
+            }
+            for &i in &selection.spawns {
+                let drawparams = graphics::DrawParam::default()
+                    .dest(self.level.spawns[i] + dist)
+                    .offset(point!(0.5, 0.5))
+                    .color(TRANS);
+
+                let img = s.assets.get_img("common/spawn");
+                canvas.draw(&*img, drawparams);
+            }
+            for &i in &selection.alarms {
+                let drawparams = graphics::DrawParam::default()
+                    .dest(self.level.alarms[i] + dist)
+                    .offset(point!(0.5, 0.5))
+                    .color(TRANS);
+
+                let img = s.assets.get_img("common/alarm");
+                canvas.draw(&*img, drawparams);
+            }
+            for &i in &selection.cameras {
+                let mut camera = self.level.cameras[i].clone();
+                camera.obj.pos += dist;
+                camera.draw(canvas, &s.assets, TRANS);
+            }
             for &i in &selection.decals {
                 let mut dec = self.level.decals[i].clone();
                 dec.obj.pos += dist;
@@ -425,6 +613,12 @@ impl GameState for Editor {
                 let img = s.assets.get_img(&self.level.weapons[i].weapon.entity_sprite);
                 canvas.draw(&*img, drawparams);
             }
+            for &i in &selection.triggers {
+                let Area{min, ..} = self.level.triggers[i].area;
+                let rect = Rect{x: min.x + dist.x, y: min.y + dist.y, w: self.level.triggers[i].area.width(), h: self.level.triggers[i].area.height()};
+                let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(2.), rect, TRANS)?;
+                canvas.draw(&mesh, DrawParam::default());
+            }
             if selection.exit {
                 if let Some(exit) = self.level.exit {
                     let drawparams = graphics::DrawParam::default()
@@ -473,6 +667,10 @@ impl GameState for Editor {
                 let img = s.assets.get_img("common/enemy");
                 canvas.draw(&*img, drawparams.rotation(rot));
             }
+            Tool::Inserter(Insertion::Npc{rot}) => {
+                let img = s.assets.get_img("common/player");
+                canvas.draw(&*img, drawparams.rotation(rot));
+            }
             Tool::Inserter(Insertion::Decal{spr, rot}) => {
                 let img = s.assets.get_img(spr);
                 canvas.draw(&*img, drawparams.rotation(rot));
@@ -485,6 +683,32 @@ impl GameState for Editor {
                 let img = s.assets.get_img("common/intel");
                 canvas.draw(&*img, drawparams);
             }
+            Tool::Inserter(Insertion::Checkpoint) => {
+                let img = s.assets.get_img("common/checkpoint");
+                canvas.draw(&*img, drawparams);
+            }
+            Tool::Inserter(Insertion::Spawn) => {
+                let img = s.assets.get_img("common/spawn");
+                canvas.draw(&*img, drawparams);
+            }
+            Tool::Inserter(Insertion::Alarm) => {
+                let img = s.assets.get_img("common/alarm");
+                canvas.draw(&*img, drawparams);
+            }
+            Tool::Inserter(Insertion::Camera{rot}) => {
+                let img = s.assets.get_img("common/camera");
+                canvas.draw(&*img, drawparams.rotation(rot));
+            }
+            Tool::Inserter(Insertion::Trigger(None)) => {
+                let img = s.assets.get_img("common/crosshair_cursor");
+                canvas.draw(&*img, drawparams.color(YELLOW));
+            }
+            Tool::Inserter(Insertion::Trigger(Some(corner))) => {
+                let Area{min, max} = Area::from_corners(corner, self.mousepos(s));
+                let rect = Rect{x: min.x + s.offset.x, y: min.y + s.offset.y, w: max.x - min.x, h: max.y - min.y};
+                let mesh = Mesh::new_rectangle(ctx, DrawMode::stroke(2.), rect, YELLOW)?;
+                canvas.draw(&mesh, DrawParam::default());
+            }
         }
 
         let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect{x:0.,y:0.,h: 64., w: s.width as f32}, Color{r: 0.5, g: 0.5, b: 0.5, a: 1.})?;
@@ -506,6 +730,17 @@ impl GameState for Editor {
         self.mat_text.draw_text(canvas);
         self.entities_bar.ent_text.draw_text(canvas);
         self.extra_bar.ent_text.draw_text(canvas);
+        if self.level.fog {
+            canvas.draw(&s.assets.raw_text_with("Fog of war", 18.), DrawParam::from(point!(s.width - 100., 4.)));
+        }
+        if self.level.ambient < 1. {
+            let text = format!("Ambient {:.0}%", 100. * self.level.ambient);
+            canvas.draw(&s.assets.raw_text_with(&text, 18.), DrawParam::from(point!(s.width - 100., 24.)));
+        }
+        if let Some(difficulty) = self.level.difficulty {
+            let text = format!("{} only", difficulty);
+            canvas.draw(&s.assets.raw_text_with(&text, 18.), DrawParam::from(point!(s.width - 100., 44.)));
+        }
 
         Ok(())
     }
@@ -519,6 +754,18 @@ impl GameState for Editor {
             Input::LoadLevel => self.level = Level::load(&self.save).unwrap(),
             Input::ToggleVisibilityCones => self.draw_visibility_cones.toggle(),
             Input::ToggleGridSnap => self.snap_on_grid.toggle(),
+            Input::ToggleFog => self.level.fog.toggle(),
+            Input::CycleAmbient => {
+                let next = AMBIENT_LEVELS.iter().position(|&a| a < self.level.ambient).unwrap_or(0);
+                self.level.ambient = AMBIENT_LEVELS[next];
+            }
+            Input::CycleDifficulty => {
+                self.level.difficulty = match self.level.difficulty {
+                    None => Some(Difficulty::Easy),
+                    Some(Difficulty::Hard) => None,
+                    Some(d) => Some(d.next()),
+                };
+            }
             Input::PlayLevel => {
                 s.switch(StateSwitch::Play(self.level.clone()));
             }
@@ -527,11 +774,17 @@ impl GameState for Editor {
                 #[allow(clippy::unneeded_field_pattern)]
                 let Selection {
                     mut enemies,
+                    mut npcs,
                     mut waypoints,
                     mut intels,
+                    mut checkpoints,
+                    mut spawns,
+                    mut alarms,
+                    mut cameras,
                     mut pickups,
                     mut weapons,
                     mut decals,
+                    mut triggers,
                     exit, moving: _,
                 } = ::std::mem::replace(selection, Selection::default());
 
@@ -544,12 +797,32 @@ impl GameState for Editor {
                 }
                 enemies.sort();
                 for enemy in enemies.into_iter().rev() {
-                    self.level.enemies.remove(enemy);
+                    self.level.remove_enemy(enemy);
+                }
+                npcs.sort();
+                for npc in npcs.into_iter().rev() {
+                    self.level.remove_npc(npc);
                 }
                 intels.sort();
                 for intel in intels.into_iter().rev() {
                     self.level.intels.remove(intel);
                 }
+                checkpoints.sort();
+                for checkpoint in checkpoints.into_iter().rev() {
+                    self.level.checkpoints.remove(checkpoint);
+                }
+                spawns.sort();
+                for spawn in spawns.into_iter().rev() {
+                    self.level.spawns.remove(spawn);
+                }
+                alarms.sort();
+                for alarm in alarms.into_iter().rev() {
+                    self.level.alarms.remove(alarm);
+                }
+                cameras.sort();
+                for camera in cameras.into_iter().rev() {
+                    self.level.cameras.remove(camera);
+                }
                 decals.sort();
                 for decal in decals.into_iter().rev() {
                     self.level.decals.remove(decal);
@@ -562,12 +835,18 @@ impl GameState for Editor {
                 for weapon in weapons.into_iter().rev() {
                     self.level.weapons.remove(weapon);
                 }
+                triggers.sort();
+                for trigger in triggers.into_iter().rev() {
+                    self.level.triggers.remove(trigger);
+                }
             }
             Input::RotateLeft => {
                 self.rotation_speed = 0.;
                 if shift {
                     match self.current {
                         Tool::Inserter(Insertion::Enemy{ref mut rot}) => *rot -= std::f32::consts::FRAC_PI_4,
+                        Tool::Inserter(Insertion::Camera{ref mut rot}) => *rot -= std::f32::consts::FRAC_PI_4,
+                        Tool::Inserter(Insertion::Npc{ref mut rot}) => *rot -= std::f32::consts::FRAC_PI_4,
                         Tool::Inserter(Insertion::Decal{ref mut rot, ..}) => *rot -= std::f32::consts::FRAC_PI_4,
                         _ => (),
                     }
@@ -578,6 +857,8 @@ impl GameState for Editor {
                 if shift {
                     match self.current {
                         Tool::Inserter(Insertion::Enemy{ref mut rot}) => *rot += std::f32::consts::FRAC_PI_4,
+                        Tool::Inserter(Insertion::Camera{ref mut rot}) => *rot += std::f32::consts::FRAC_PI_4,
+                        Tool::Inserter(Insertion::Npc{ref mut rot}) => *rot += std::f32::consts::FRAC_PI_4,
                         Tool::Inserter(Insertion::Decal{ref mut rot, ..}) => *rot += std::f32::consts::FRAC_PI_4,
                         _ => (),
                     }
@@ -618,6 +899,9 @@ impl GameState for Editor {
             _ => ()
         }
     }
+    fn get_mut_level(&mut self) -> Option<&mut Level> {
+        Some(&mut self.level)
+    }
     fn event_down(&mut self, s: &mut State, ctx: &mut Context, input: Input) {
         let shift = s.controls.ctx(ctx).is_mod_active(KeyMods::SHIFT);
         let mousepos = self.mousepos(&s);
@@ -629,11 +913,36 @@ impl GameState for Editor {
                         return selection.moving = Some(mousepos);
                     }
                 }
+                for &i in &selection.npcs {
+                    if (self.level.npcs[i].obj.pos - mousepos).norm() <= 16. {
+                        return selection.moving = Some(mousepos);
+                    }
+                }
                 for &i in &selection.intels {
                     if (self.level.intels[i] - mousepos).norm() <= 16. {
                         return selection.moving = Some(mousepos);
                     }
                 }
+                for &i in &selection.checkpoints {
+                    if (self.level.checkpoints[i] - mousepos).norm() <= 16. {
+                        return selection.moving = Some(mousepos);
+                    }
+                }
+                for &i in &selection.spawns {
+                    if (self.level.spawns[i] - mousepos).norm() <= 16. {
+                        return selection.moving = Some(mousepos);
+                    }
+                }
+                for &i in &selection.alarms {
+                    if (self.level.alarms[i] - mousepos).norm() <= 16. {
+                        return selection.moving = Some(mousepos);
+                    }
+                }
+                for &i in &selection.cameras {
+                    if (self.level.cameras[i].obj.pos - mousepos).norm() <= 16. {
+                        return selection.moving = Some(mousepos);
+                    }
+                }
                 for &i in &selection.decals {
                     if (self.level.decals[i].obj.pos - mousepos).norm() <= 16. {
                         return selection.moving = Some(mousepos);
@@ -656,6 +965,11 @@ impl GameState for Editor {
                         }
                     }
                 }
+                for &i in &selection.triggers {
+                    if self.level.triggers[i].area.contains(mousepos) {
+                        return selection.moving = Some(mousepos);
+                    }
+                }
             }
             Input::RotateLeft if !shift => self.rotation_speed -= 6.,
             Input::RotateRight if !shift => self.rotation_speed += 6.,
@@ -695,9 +1009,24 @@ impl Editor {
                         for i in selection.enemies.iter().rev() {
                             self.level.enemies[*i].pl.obj.pos += dist;
                         }
+                        for i in selection.npcs.iter().rev() {
+                            self.level.npcs[*i].obj.pos += dist;
+                        }
                         for i in selection.intels.iter().rev() {
                             self.level.intels[*i] += dist;
                         }
+                        for i in selection.checkpoints.iter().rev() {
+                            self.level.checkpoints[*i] += dist;
+                        }
+                        for i in selection.spawns.iter().rev() {
+                            self.level.spawns[*i] += dist;
+                        }
+                        for i in selection.alarms.iter().rev() {
+                            self.level.alarms[*i] += dist;
+                        }
+                        for i in selection.cameras.iter().rev() {
+                            self.level.cameras[*i].obj.pos += dist;
+                        }
                         for i in selection.decals.iter().rev() {
                             self.level.decals[*i].obj.pos += dist;
                         }
@@ -707,6 +1036,10 @@ impl Editor {
                         for i in selection.weapons.iter().rev() {
                             self.level.weapons[*i].pos += dist;
                         }
+                        for i in selection.triggers.iter().rev() {
+                            self.level.triggers[*i].area.min += dist;
+                            self.level.triggers[*i].area.max += dist;
+                        }
                         selection.moving = None;
                     } else {
                         if !s.controls.ctx(ctx).is_mod_active(KeyMods::CTRL) {
@@ -718,6 +1051,12 @@ impl Editor {
                                 return
                             }
                         }
+                        for (i, npc) in self.level.npcs.iter().enumerate() {
+                            if (npc.obj.pos - mousepos).norm() <= 16. && !selection.npcs.contains(&i) {
+                                selection.npcs.push(i);
+                                return
+                            }
+                        }
                         if let Some(exit) = self.level.exit {
                             if (exit - mousepos).norm() <= 16. && !selection.exit {
                                 selection.exit = true;
@@ -730,6 +1069,30 @@ impl Editor {
                                 return
                             }
                         }
+                        for (i, &checkpoint) in self.level.checkpoints.iter().enumerate() {
+                            if (checkpoint - mousepos).norm() <= 16. && !selection.checkpoints.contains(&i) {
+                                selection.checkpoints.push(i);
+                                return
+                            }
+                        }
+                        for (i, &spawn) in self.level.spawns.iter().enumerate() {
+                            if (spawn - mousepos).norm() <= 16. && !selection.spawns.contains(&i) {
+                                selection.spawns.push(i);
+                                return
+                            }
+                        }
+                        for (i, &alarm) in self.level.alarms.iter().enumerate() {
+                            if (alarm - mousepos).norm() <= 16. && !selection.alarms.contains(&i) {
+                                selection.alarms.push(i);
+                                return
+                            }
+                        }
+                        for (i, camera) in self.level.cameras.iter().enumerate() {
+                            if (camera.obj.pos - mousepos).norm() <= 16. && !selection.cameras.contains(&i) {
+                                selection.cameras.push(i);
+                                return
+                            }
+                        }
                         for (i, decal) in self.level.decals.iter().enumerate() {
                             if (decal.obj.pos - mousepos).norm() <= 16. && !selection.decals.contains(&i) {
                                 selection.decals.push(i);
@@ -748,6 +1111,13 @@ impl Editor {
                                 return
                             }
                         }
+                        // Triggers are checked last since they cover the other objects
+                        for (i, trigger) in self.level.triggers.iter().enumerate() {
+                            if trigger.area.contains(mousepos) && !selection.triggers.contains(&i) {
+                                selection.triggers.push(i);
+                                return
+                            }
+                        }
                     }
                 }
                 Tool::Inserter(Insertion::Exit) => {
@@ -758,6 +1128,9 @@ impl Editor {
                     s.mplayer.play(ctx, "reload").unwrap();
                     self.level.enemies.push(Enemy::new(Object::with_rot(mousepos, rot)));
                 },
+                Tool::Inserter(Insertion::Npc{rot}) => {
+                    self.level.npcs.push(Player::new(Object::with_rot(mousepos, rot)));
+                },
                 Tool::Inserter(Insertion::Waypoint(e)) => {
                     self.level.enemies[e].behaviour.path.push(mousepos);
                 }
@@ -771,6 +1144,15 @@ impl Editor {
                     self.level.weapons.push(WEAPONS[id].make_drop(mousepos));
                 },
                 Tool::Inserter(Insertion::Intel) => self.level.intels.push(mousepos),
+                Tool::Inserter(Insertion::Checkpoint) => self.level.checkpoints.push(mousepos),
+                Tool::Inserter(Insertion::Spawn) => self.level.spawns.push(mousepos),
+                Tool::Inserter(Insertion::Alarm) => self.level.alarms.push(mousepos),
+                Tool::Inserter(Insertion::Camera{rot}) => self.level.cameras.push(Camera::new(Object::with_rot(mousepos, rot))),
+                Tool::Inserter(Insertion::Trigger(None)) => self.current = Tool::Inserter(Insertion::Trigger(Some(mousepos))),
+                Tool::Inserter(Insertion::Trigger(Some(corner))) => {
+                    self.level.triggers.push(Trigger::new(Area::from_corners(corner, mousepos)));
+                    self.current = Tool::Selector(Selection{triggers: vec![self.level.triggers.len() - 1], .. Default::default()});
+                }
             }
         }
     }
@@ -0,0 +1,280 @@
+use topskud::{
+    io::{
+        tex::PosText,
+        btn::Button,
+        ctrl::Input,
+    },
+    obj::{
+        health::Health,
+        player::WepSlots,
+        weapon::{WeaponInstance, WeaponSlot},
+    },
+    world::GRENADE,
+};
+use crate::game::{Campaign, State, Content, GameState, StateSwitch};
+use ggez::{
+    Context, GameResult,
+    graphics::{Rect, Canvas, Mesh, DrawMode, DrawParam, Color},
+};
+
+const AMMO_PRICE: u32 = 50;
+const ARMOUR_PRICE: u32 = 100;
+const ARMOUR_AMOUNT: f32 = 25.;
+const MAX_ARMOUR: f32 = 100.;
+const GRENADE_PRICE: u32 = 150;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Holster,
+    Holster2,
+    Sling,
+}
+
+impl Slot {
+    const ALL: [Slot; 3] = [Slot::Holster, Slot::Holster2, Slot::Sling];
+
+    fn get(self, wep: &mut WepSlots) -> &mut Option<WeaponInstance<'static>> {
+        match self {
+            Slot::Holster => &mut wep.holster,
+            Slot::Holster2 => &mut wep.holster2,
+            Slot::Sling => &mut wep.sling,
+        }
+    }
+    fn fits(self, slot: WeaponSlot) -> bool {
+        matches!((self, slot), (Slot::Holster, WeaponSlot::Holster) | (Slot::Holster2, WeaponSlot::Holster) | (Slot::Sling, WeaponSlot::Sling))
+    }
+    fn name(self) -> &'static str {
+        match self {
+            Slot::Holster => "Holster",
+            Slot::Holster2 => "Holster 2",
+            Slot::Sling => "Sling",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Callback {
+    Slot(Slot),
+    Stash(usize),
+    PutInStash,
+    BuyAmmo,
+    BuyArmour,
+    BuyGrenade,
+    Continue,
+}
+
+/// Between campaign levels, where weapons can be rearranged and gear bought
+pub struct Intermission {
+    title_txt: PosText,
+    points_txt: PosText,
+    health_txt: PosText,
+    status_txt: PosText,
+    buttons: Vec<Button<Callback>>,
+    selected: Option<Slot>,
+    health: Health,
+    wep: WepSlots,
+}
+
+/// The rectangle of a button in the given column and row
+fn button_rect(w: f32, col: f32, row: f32) -> Rect {
+    Rect{x: w / 16. + col * 5. * w / 16., y: 96. + row * 68., w: w / 4., h: 64.}
+}
+
+impl Intermission {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(ctx: &mut Context, s: &mut State, health: Health, wep: WepSlots, points: u32, weapons_left: Vec<WeaponInstance<'static>>) -> GameResult<Box<dyn GameState>> {
+        let w = s.width;
+
+        let (health, wep) = match &mut s.content {
+            Content::Campaign(cam) => {
+                cam.points += points;
+                let (health, wep) = cam.carry(Some((health, wep)));
+                // Weapons found and left behind go into the stash
+                for found in weapons_left {
+                    let carried = wep.clone().into_iter().chain(cam.stash.iter().copied()).any(|w| w.weapon.id == found.weapon.id);
+                    if !carried {
+                        cam.stash.push(found);
+                    }
+                }
+                (health, wep)
+            }
+            Content::File(_) | Content::None => (health, wep),
+        };
+
+        let mut intermission = Intermission {
+            title_txt: s.assets.text_sized(point!(w / 2., 16.), 32.).and_text("Armoury").centered(),
+            points_txt: s.assets.text(point!(w / 16., 64.)).and_text(""),
+            health_txt: s.assets.text(point!(6. * w / 16., 64.)).and_text(""),
+            status_txt: s.assets.text(point!(w / 2., s.height - 124.)).and_text("").centered(),
+            buttons: Vec::new(),
+            selected: None,
+            health,
+            wep,
+        };
+        intermission.layout(ctx, s)?;
+
+        Ok(Box::new(intermission))
+    }
+    /// Remakes the buttons and texts after something has changed
+    fn layout(&mut self, ctx: &mut Context, s: &State) -> GameResult<()> {
+        let (w, h) = (s.width, s.height);
+        let (points, stash) = match &s.content {
+            Content::Campaign(cam) => (cam.points, &*cam.stash),
+            Content::File(_) | Content::None => (0, &[][..]),
+        };
+
+        self.points_txt.update(0, format!("Points: {}", points))?;
+        self.health_txt.update(0, format!("Health: {:.0}  Armour: {:.0}  Grenades: {}", self.health.hp, self.health.armour, self.wep.utilities.count(GRENADE)))?;
+
+        let mut buttons = Vec::with_capacity(8 + stash.len());
+        for (i, &slot) in Slot::ALL.iter().enumerate() {
+            let text = match slot.get(&mut self.wep) {
+                Some(wep) => format!("{}: {}", slot.name(), wep),
+                None => format!("{}: empty", slot.name()),
+            };
+            buttons.push(Button::new(ctx, &s.assets, button_rect(w, 0., i as f32), &text, Callback::Slot(slot))?);
+        }
+        buttons.push(Button::new(ctx, &s.assets, button_rect(w, 0., 3.), "Put in stash", Callback::PutInStash)?);
+
+        buttons.push(Button::new(ctx, &s.assets, button_rect(w, 1., 0.), &format!("Ammo ({})", AMMO_PRICE), Callback::BuyAmmo)?);
+        buttons.push(Button::new(ctx, &s.assets, button_rect(w, 1., 1.), &format!("Armour +{} ({})", ARMOUR_AMOUNT, ARMOUR_PRICE), Callback::BuyArmour)?);
+        buttons.push(Button::new(ctx, &s.assets, button_rect(w, 1., 2.), &format!("Grenade ({})", GRENADE_PRICE), Callback::BuyGrenade)?);
+
+        for (i, wep) in stash.iter().enumerate() {
+            buttons.push(Button::new(ctx, &s.assets, button_rect(w, 2., i as f32), &format!("{}", wep), Callback::Stash(i))?);
+        }
+
+        buttons.push(Button::new(ctx, &s.assets, Rect{x: 3. * w / 7., y: h - 96., w: w / 7., h: 64.}, "Continue", Callback::Continue)?);
+
+        self.buttons = buttons;
+        Ok(())
+    }
+    /// Carries out what a button does
+    ///
+    /// ### Returns
+    /// Where to go next if the player is done, or a message to show the player if it couldn't be done
+    fn press(&mut self, callback: Callback, cam: &mut Campaign) -> Result<Option<StateSwitch>, &'static str> {
+        match callback {
+            Callback::Slot(slot) => match self.selected {
+                Some(selected) if selected == slot => self.selected = None,
+                Some(selected) => {
+                    let (a, b) = (selected.get(&mut self.wep).take(), slot.get(&mut self.wep).take());
+                    let fits = a.map(|w| slot.fits(w.weapon.slot)).unwrap_or(true) && b.map(|w| selected.fits(w.weapon.slot)).unwrap_or(true);
+                    if fits {
+                        *selected.get(&mut self.wep) = b;
+                        *slot.get(&mut self.wep) = a;
+                    } else {
+                        *selected.get(&mut self.wep) = a;
+                        *slot.get(&mut self.wep) = b;
+                    }
+                    self.selected = None;
+                    if !fits {
+                        return Err("That weapon doesn't fit there");
+                    }
+                }
+                None => self.selected = Some(slot),
+            },
+            Callback::Stash(i) => {
+                let weapon_slot = cam.stash[i].weapon.slot;
+                let slot = match self.selected {
+                    Some(slot) if slot.fits(weapon_slot) => slot,
+                    Some(_) => return Err("That weapon doesn't fit there"),
+                    None => *Slot::ALL.iter()
+                        .filter(|s| s.fits(weapon_slot))
+                        .find(|s| s.get(&mut self.wep).is_none())
+                        .unwrap_or(if let WeaponSlot::Sling = weapon_slot { &Slot::Sling } else { &Slot::Holster }),
+                };
+                let wep = cam.stash.remove(i);
+                if let Some(old) = slot.get(&mut self.wep).replace(wep) {
+                    cam.stash.push(old);
+                }
+            }
+            Callback::PutInStash => {
+                let slot = self.selected.ok_or("Select a weapon first")?;
+                let wep = slot.get(&mut self.wep).take().ok_or("No weapon there")?;
+                cam.stash.push(wep);
+                self.selected = None;
+            }
+            Callback::BuyAmmo => {
+                let slot = self.selected.ok_or("Select a weapon first")?;
+                let wep = slot.get(&mut self.wep).as_mut().ok_or("No weapon there")?;
+                let max = wep.weapon.clip_size.get() * wep.weapon.clips.get();
+                if wep.ammo >= max {
+                    return Err("Can't carry more ammo for that");
+                }
+                spend(cam, AMMO_PRICE)?;
+                wep.ammo = (wep.ammo + wep.weapon.clip_size.get()).min(max);
+            }
+            Callback::BuyArmour => {
+                if self.health.armour >= MAX_ARMOUR {
+                    return Err("Armour is full");
+                }
+                spend(cam, ARMOUR_PRICE)?;
+                self.health.armour = (self.health.armour + ARMOUR_AMOUNT).min(MAX_ARMOUR);
+            }
+            Callback::BuyGrenade => {
+                if self.wep.utilities.count(GRENADE) == u8::MAX {
+                    return Err("Can't carry more grenades");
+                }
+                spend(cam, GRENADE_PRICE)?;
+                self.wep.utilities.add(GRENADE, 1);
+            }
+            Callback::Continue => {
+                if !self.wep.slot_has_weapon(self.wep.active) {
+                    self.wep.init_active();
+                }
+                let next = cam.current;
+                return Ok(Some(cam.start_level_with(next, self.health, self.wep.clone()).unwrap_or(StateSwitch::Menu)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn spend(cam: &mut Campaign, price: u32) -> Result<(), &'static str> {
+    if cam.points < price {
+        return Err("Not enough points");
+    }
+    cam.points -= price;
+    Ok(())
+}
+
+impl GameState for Intermission {
+    fn draw_hud(&mut self, s: &State, canvas: &mut Canvas, ctx: &mut Context) -> GameResult<()> {
+        self.title_txt.draw_text(canvas);
+        self.points_txt.draw_text(canvas);
+        self.health_txt.draw_text(canvas);
+        self.status_txt.draw_text(canvas);
+        for button in &self.buttons {
+            button.draw(canvas);
+        }
+        if let Some(selected) = self.selected {
+            let row = Slot::ALL.iter().position(|&s| s == selected).unwrap();
+            let outline = Mesh::new_rectangle(ctx, DrawMode::stroke(3.), button_rect(s.width, 0., row as f32), Color::WHITE)?;
+            canvas.draw(&outline, DrawParam::new());
+        }
+        Ok(())
+    }
+    fn event_up(&mut self, s: &mut State, ctx: &mut Context, input: Input) {
+        let callback = match input {
+            Input::Confirm => Callback::Continue,
+            Input::LeftClick => match self.buttons.iter().find(|b| b.in_bounds(s.mouse)) {
+                Some(button) => button.callback,
+                None => return,
+            },
+            _ => return,
+        };
+
+        let status = match &mut s.content {
+            Content::Campaign(cam) => match self.press(callback, cam) {
+                Ok(Some(switch)) => return s.switch(switch),
+                Ok(None) => "",
+                Err(status) => status,
+            },
+            // There's no gear to sort out outside of a campaign
+            Content::File(_) | Content::None => return s.switch(StateSwitch::Menu),
+        };
+        self.status_txt.update(0, status).unwrap();
+        self.layout(ctx, s).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use topskud::io::{
+    tex::PosText,
+    btn::Button,
+    ctrl::Input,
+    profile::CampaignProgress,
+};
+use crate::game::{State, Content, GameState, StateSwitch};
+use ggez::{
+    Context, GameResult,
+    graphics::{Rect, Canvas},
+};
+
+/// Lets the player pick which unlocked level of the campaign to play
+pub struct LevelSelect {
+    title_txt: PosText,
+    buttons: Vec<Button<Callback>>,
+}
+
+enum Callback {
+    Play(usize),
+    /// Switch to the next mode to play the levels in
+    CycleMode,
+    CycleDifficulty,
+    Back,
+}
+
+/// Lays the buttons out in columns going down the screen
+fn button_rect(w: f32, h: f32, i: usize) -> Rect {
+    let rows = (((h - 64.) / 68.) as usize).max(1);
+    let (col, row) = (i / rows, i % rows);
+    Rect{x: w / 14. + col as f32 * 3. * w / 14., y: 64. + row as f32 * 68., w: 2. * w / 7., h: 64.}
+}
+
+impl LevelSelect {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(ctx: &mut Context, s: &mut State) -> GameResult<Box<dyn GameState>> {
+        let (w, h) = (s.width, s.height);
+
+        let mut buttons = Vec::new();
+        if let Content::Campaign(cam) = &s.content {
+            let default = CampaignProgress::default();
+            let progress = s.profile.progress(&cam.name).unwrap_or(&default);
+            let followers = cam.followers();
+
+            for i in (0..cam.levels.len()).filter(|&i| progress.unlocked(i, &followers)) {
+                let text = match progress.levels.get(i) {
+                    Some(record) if record.completed => format!("Level {} ({:.1}s, {} kills)", i + 1, record.best_time, record.kills),
+                    _ => format!("Level {}", i + 1),
+                };
+                buttons.push(Button::new(ctx, &s.assets, button_rect(w, h, buttons.len()), &text, Callback::Play(i))?);
+            }
+        }
+        buttons.push(Button::new(ctx, &s.assets, button_rect(w, h, buttons.len()), &format!("Mode: {}", s.mode), Callback::CycleMode)?);
+        buttons.push(Button::new(ctx, &s.assets, button_rect(w, h, buttons.len()), &format!("Difficulty: {}", s.difficulty), Callback::CycleDifficulty)?);
+        buttons.push(Button::new(ctx, &s.assets, button_rect(w, h, buttons.len()), "Back", Callback::Back)?);
+
+        Ok(Box::new(LevelSelect {
+            title_txt: s.assets.text_sized(point!(w / 2., 16.), 32.).and_text("Select Level").centered(),
+            buttons,
+        }))
+    }
+}
+
+impl GameState for LevelSelect {
+    fn draw_hud(&mut self, _s: &State, canvas: &mut Canvas, _ctx: &mut Context) -> GameResult<()> {
+        self.title_txt.draw_text(canvas);
+        for button in &self.buttons {
+            button.draw(canvas);
+        }
+        Ok(())
+    }
+    fn event_up(&mut self, s: &mut State, _ctx: &mut Context, input: Input) {
+        if let Input::LeftClick = input {
+            for button in &self.buttons {
+                if button.in_bounds(s.mouse) {
+                    match button.callback {
+                        Callback::Play(i) => if let Content::Campaign(cam) = &mut s.content {
+                            if let Some(switch) = cam.start_level(i, None) {
+                                s.switch(switch);
+                            }
+                        },
+                        Callback::CycleMode => {
+                            s.mode = s.mode.next();
+                            s.switch(StateSwitch::LevelSelect);
+                        }
+                        Callback::CycleDifficulty => {
+                            s.cycle_difficulty();
+                            s.switch(StateSwitch::LevelSelect);
+                        }
+                        Callback::Back => s.switch(StateSwitch::Menu),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use topskud::io::{
+    tex::PosText,
+    btn::Button,
+    ctrl::Input,
+    save::{SaveGame, SAVE_SLOTS},
+};
+use crate::game::{State, GameState, StateSwitch};
+use ggez::{
+    Context, GameResult,
+    graphics::{Rect, Canvas},
+};
+
+/// Lists the save slots to continue from
+pub struct LoadMenu {
+    title_txt: PosText,
+    buttons: Vec<Button<Callback>>,
+}
+
+enum Callback {
+    Load(u8),
+    Back,
+}
+
+fn button_rect(w: f32, i: f32) -> Rect {
+    Rect{x:3. * w / 7., y: 64. + i * 68., w:w / 7., h:64.}
+}
+
+impl LoadMenu {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(ctx: &mut Context, s: &mut State) -> GameResult<Box<dyn GameState>> {
+        let w = s.width;
+
+        let mut buttons = Vec::new();
+        for slot in (0..SAVE_SLOTS).filter(|&slot| SaveGame::exists(slot)) {
+            buttons.push(Button::new(ctx, &s.assets, button_rect(w, buttons.len() as f32), &SaveGame::slot_name(slot), Callback::Load(slot))?);
+        }
+        buttons.push(Button::new(ctx, &s.assets, button_rect(w, buttons.len() as f32), "Back", Callback::Back)?);
+
+        Ok(Box::new(LoadMenu {
+            title_txt: s.assets.text_sized(point!(w / 2., 16.), 32.).and_text("Load Game").centered(),
+            buttons,
+        }))
+    }
+}
+
+impl GameState for LoadMenu {
+    fn draw_hud(&mut self, _s: &State, canvas: &mut Canvas, _ctx: &mut Context) -> GameResult<()> {
+        self.title_txt.draw_text(canvas);
+        for button in &self.buttons {
+            button.draw(canvas);
+        }
+        Ok(())
+    }
+    fn event_up(&mut self, s: &mut State, _ctx: &mut Context, input: Input) {
+        if let Input::LeftClick = input {
+            for button in &self.buttons {
+                if button.in_bounds(s.mouse) {
+                    match button.callback {
+                        Callback::Load(slot) => match SaveGame::load(slot) {
+                            Ok(save) => s.switch(StateSwitch::Load(Box::new(save))),
+                            Err(e) => error!("Couldn't load {}: {}", SaveGame::slot_name(slot), e),
+                        },
+                        Callback::Back => s.switch(StateSwitch::Menu),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -6,7 +6,7 @@ use topskud::{
         btn::Button,
         ctrl::Input,
     },
-    world::{Statistics, Level},
+    world::{Statistics, Level, Checkpoint},
     obj::{health::Health, player::WepSlots},
 };
 use crate::game::{
@@ -22,11 +22,14 @@ pub struct Lose {
     you_died: PosText,
     time_text: PosText,
     enemies_text: PosText,
+    waves_text: Option<PosText>,
     restart_btn: Button<()>,
+    restart_level_btn: Option<Button<()>>,
     edit_btn: Option<Button<()>>,
     level: Level,
     health: Health,
-    weapon: WepSlots
+    weapon: WepSlots,
+    checkpoint: Option<Box<Checkpoint>>,
 }
 
 impl Lose {
@@ -36,11 +39,41 @@ impl Lose {
         let you_died = s.assets.text(point!(s.width as f32/ 2., 10.)).and_text(TextFragment::from("You died!").color(RED)).centered();
         let time_text = s.assets.text(point!(4., 20.)).and_text(format!("Time: {:.0}s", stats.time as f32 * DELTA));
         let enemy_total = stats.level.enemies.len();
-        let enemies_text = s.assets.text(point!(4., 36.)).and_text(format!("Enemies killed: {} / {}", enemy_total - stats.enemies_left, enemy_total));
+        let (enemies_text, waves_text) = match stats.waves_survived {
+            Some(waves) => {
+                let best = match s.content.level_key() {
+                    Some(key) => {
+                        let best = s.profile.record_survival(&key, waves);
+                        if let Err(e) = s.profile.save() {
+                            error!("Couldn't save profile: {}", e);
+                        }
+                        best
+                    }
+                    None => waves,
+                };
+                (
+                    s.assets.text(point!(4., 36.)).and_text(format!("Enemies killed: {}", stats.score.total_kills())),
+                    Some(s.assets.text(point!(4., 52.)).and_text(format!("Waves survived: {} (best {})", waves, best))),
+                )
+            }
+            None => (
+                s.assets.text(point!(4., 36.)).and_text(format!("Enemies killed: {} / {}", enemy_total - stats.enemies_left, enemy_total)),
+                None,
+            ),
+        };
         let restart_btn = Button::new(ctx, &s.assets, Rect{x: 3. * w / 7., y: 64., w: w / 7., h: 64.}, "Restart", ())?;
+        let mut y = 132.;
+        let restart_level_btn = if stats.checkpoint.is_some() {
+            y += 68.;
+            Some(
+                Button::new(ctx, &s.assets, Rect{x: 3. * w / 7., y: 132., w: w / 7., h: 64.}, "Restart level", ())?
+            )
+        } else {
+            None
+        };
         let edit_btn = if let Content::File(_) = s.content {
             Some(
-                Button::new(ctx, &s.assets, Rect{x: 3. * w / 7., y: 132., w: w / 7., h: 64.}, "Edit", ())?
+                Button::new(ctx, &s.assets, Rect{x: 3. * w / 7., y, w: w / 7., h: 64.}, "Edit", ())?
             )
         } else {
             None
@@ -50,17 +83,32 @@ impl Lose {
             you_died,
             time_text,
             enemies_text,
+            waves_text,
             restart_btn,
+            restart_level_btn,
             edit_btn,
             level: stats.level,
             health: stats.health_left,
             weapon: stats.weapon,
+            checkpoint: stats.checkpoint,
         }))
     }
     fn edit(&self, s: &mut State) {
         s.switch(StateSwitch::Editor(Some(self.level.clone())));
     }
+    /// Restarts from the last checkpoint if one was reached
     fn restart(&self, s: &mut State) {
+        match &self.checkpoint {
+            Some(checkpoint) => s.switch(StateSwitch::Resume{
+                lvl: Box::new(self.level.clone()),
+                health: self.health,
+                wep: self.weapon.clone(),
+                checkpoint: checkpoint.clone(),
+            }),
+            None => self.restart_level(s),
+        }
+    }
+    fn restart_level(&self, s: &mut State) {
         s.switch(StateSwitch::PlayWith{lvl: Box::new(self.level.clone()), health: self.health, wep: self.weapon.clone()})
     }
 }
@@ -68,6 +116,9 @@ impl Lose {
 impl GameState for Lose {
     fn draw_hud(&mut self, _s: &State, canvas: &mut Canvas, _ctx: &mut Context) -> GameResult<()> {
         self.restart_btn.draw(canvas);
+        if let Some(btn) = &self.restart_level_btn {
+            btn.draw(canvas);
+        }
         if let Some(btn) = &self.edit_btn {
             btn.draw(canvas);
         }
@@ -75,6 +126,9 @@ impl GameState for Lose {
         self.you_died.draw_text(canvas);
         self.time_text.draw_text(canvas);
         self.enemies_text.draw_text(canvas);
+        if let Some(text) = &self.waves_text {
+            text.draw_text(canvas);
+        }
 
         Ok(())
     }
@@ -85,6 +139,11 @@ impl GameState for Lose {
                 if self.restart_btn.in_bounds(s.mouse) {
                     self.restart(s);
                 }
+                if let Some(btn) = &self.restart_level_btn {
+                    if btn.in_bounds(s.mouse) {
+                        self.restart_level(s);
+                    }
+                }
                 if let Some(btn) = &self.edit_btn {
                     if btn.in_bounds(s.mouse) {
                         self.edit(s);
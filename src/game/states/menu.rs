@@ -3,12 +3,13 @@ use topskud::{
     io::{
         tex::PosText,
         btn::Button,
-        ctrl::Input
+        ctrl::Input,
+        save::{SaveGame, SAVE_SLOTS},
     },
     world::Level,
 };
 use crate::game::{
-    Campaign, Content, State, GameState, StateSwitch,
+    Campaign, Content, State, GameState, StateSwitch, Mode,
 };
 use ggez::{
     Context, GameResult,
@@ -23,9 +24,15 @@ pub struct Menu {
 }
 
 enum Callback {
-    SwitchPlay(PathBuf),
+    SwitchPlay(PathBuf, Mode),
     SwitchEditor,
     Campaign(PathBuf),
+    LoadMenu,
+    /// Play the level of the campaign with the given index
+    PlayLevel(usize),
+    LevelSelect,
+    CycleDifficulty,
+    Back,
 }
 
 // ↓
@@ -38,16 +45,26 @@ impl Menu {
     pub fn new(ctx: &mut Context, s: &mut State) -> GameResult<Box<dyn GameState>> {
         let w = s.width as f32;
 
-        let corner_text = if let Content::File(ref f) = s.content {
-            Some(s.assets.text(point!(2., 2.)).and_text("File: ").and_text(format!("File: {}", f.display())))
-        } else {
-            None
+        let corner_text = match s.content {
+            Content::File(ref f) => Some(s.assets.text(point!(2., 2.)).and_text("File: ").and_text(format!("File: {}", f.display()))),
+            Content::Campaign(ref cam) => Some(s.assets.text(point!(2., 2.)).and_text(format!("Campaign: {}\n{}", cam.title, cam.description))),
+            Content::None => None,
         };
         s.mplayer.play(ctx, "music")?;
 
-        let buttons = match &mut s.content {
-            Content::Campaign(_cam) => {
-                unreachable!()
+        let mut buttons = match &mut s.content {
+            Content::Campaign(cam) => {
+                let next = s.profile.progress(&cam.name).map(|p| p.next_level(&cam.followers())).unwrap_or(Some(0));
+                let mut buttons = Vec::with_capacity(3);
+                match next {
+                    Some(0) => buttons.push(Button::new(ctx, &s.assets, button_rect(w, 0.), "New game", Callback::PlayLevel(0))?),
+                    Some(next) => buttons.push(Button::new(ctx, &s.assets, button_rect(w, 0.), "Continue", Callback::PlayLevel(next))?),
+                    None => (),
+                }
+                buttons.push(Button::new(ctx, &s.assets, button_rect(w, buttons.len() as f32), "Select level", Callback::LevelSelect)?);
+                buttons.push(Button::new(ctx, &s.assets, button_rect(w, buttons.len() as f32), &format!("Difficulty: {}", s.difficulty), Callback::CycleDifficulty)?);
+                buttons.push(Button::new(ctx, &s.assets, button_rect(w, buttons.len() as f32), "Back", Callback::Back)?);
+                buttons
             }
             Content::File(p) if p.extension().and_then(|s| s.to_str()) == Some("cmp") => {
                 vec![
@@ -56,8 +73,11 @@ impl Menu {
             }
             Content::File(p) => {
                 vec![
-                    Button::new(ctx, &s.assets, button_rect(w, 0.), "Play", Callback::SwitchPlay(p.clone()))?,
-                    Button::new(ctx, &s.assets, button_rect(w, 1.), "Editor", Callback::SwitchEditor)?,
+                    Button::new(ctx, &s.assets, button_rect(w, 0.), "Play", Callback::SwitchPlay(p.clone(), Mode::Normal))?,
+                    Button::new(ctx, &s.assets, button_rect(w, 1.), "Time attack", Callback::SwitchPlay(p.clone(), Mode::TimeAttack))?,
+                    Button::new(ctx, &s.assets, button_rect(w, 2.), "Survival", Callback::SwitchPlay(p.clone(), Mode::Survival))?,
+                    Button::new(ctx, &s.assets, button_rect(w, 3.), &format!("Difficulty: {}", s.difficulty), Callback::CycleDifficulty)?,
+                    Button::new(ctx, &s.assets, button_rect(w, 4.), "Editor", Callback::SwitchEditor)?,
                 ]
             }
             Content::None => {
@@ -71,6 +91,9 @@ impl Menu {
                     .collect()
             },
         };
+        if (0..SAVE_SLOTS).any(SaveGame::exists) {
+            buttons.push(Button::new(ctx, &s.assets, button_rect(w, buttons.len() as f32), "Load game", Callback::LoadMenu)?);
+        }
 
         Ok(Box::new(Menu {
             title_txt: s.assets.text_sized(point!(w / 2., 16.), 32.).and_text("Main Menu").centered(),
@@ -106,16 +129,34 @@ impl GameState for Menu {
                     s.mplayer.stop(ctx, "music").unwrap();
                     match &button.callback {
                         Callback::Campaign(cam) => {
-                            let mut cam = Campaign::load(cam).unwrap();
-                            let lvl = cam.next_level().unwrap();
-                            s.content = Content::Campaign(cam);
-                            s.switch(StateSwitch::Play(lvl));
+                            s.content = Content::Campaign(Campaign::load(cam).unwrap());
+                            s.switch(StateSwitch::Menu);
+                        },
+                        &Callback::PlayLevel(i) => if let Content::Campaign(cam) = &mut s.content {
+                            s.mode = Mode::Normal;
+                            if let Some(switch) = cam.start_level(i, None) {
+                                s.switch(switch);
+                            }
                         },
-                        Callback::SwitchPlay(p) => {
-                            let lvl = Level::load(&p).unwrap();
+                        Callback::LevelSelect => {
+                            s.mode = Mode::Normal;
+                            s.switch(StateSwitch::LevelSelect);
+                        }
+                        Callback::CycleDifficulty => {
+                            s.cycle_difficulty();
+                            s.switch(StateSwitch::Menu);
+                        }
+                        Callback::Back => {
+                            s.content = Content::None;
+                            s.switch(StateSwitch::Menu);
+                        }
+                        &Callback::SwitchPlay(ref p, mode) => {
+                            let lvl = Level::load(p).unwrap();
+                            s.mode = mode;
                             s.switch(StateSwitch::Play(lvl));
                         },
                         Callback::SwitchEditor => s.switch(StateSwitch::Editor(None)),
+                        Callback::LoadMenu => s.switch(StateSwitch::LoadMenu),
                     }
                 }
             }
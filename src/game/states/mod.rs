@@ -1,5 +1,9 @@
 pub mod editor;
 pub mod play;
 pub mod menu;
+pub mod load;
+pub mod level_select;
+pub mod briefing;
+pub mod intermission;
 pub mod lose;
 pub mod win;
\ No newline at end of file
@@ -1,13 +1,18 @@
-use std::f32::consts::FRAC_1_SQRT_2 as COS_45_D;
 use topskud::{
     DELTA,
-    ext::FloatExt,
+    ext::{FloatExt, BoolExt},
     util::{
-        BLUE, GREEN, RED,
-        angle_to_vec, angle_from_vec,
-        iterate_and_kill_afterwards, iterate_and_kill_afterwards_mut, iterate_and_kill_one_mut,
+        BLUE, GREEN, RED, TRANS,
+        angle_to_vec, angle_from_vec, Point2,
+        iterate_and_kill_afterwards, iterate_and_kill_afterwards_mut,
+    },
+    io::{
+        tex::PosText,
+        ctrl::{Input, Axis, KeyMods},
+        save::{SaveGame, QUICKSAVE_SLOT},
+        leaderboard::{Ghost, GhostReplay},
+        achievement::Event,
     },
-    io::{tex::PosText, ctrl::{Input, Axis, KeyMods}},
     obj::{
         Object,
         bullet::Bullet,
@@ -16,19 +21,22 @@ use topskud::{
         player::{Player, WepSlots, ActiveSlot},
         enemy::Enemy,
         health::Health,
-        weapon::{self, WeaponInstance},
-        grenade::GrenadeUpdate,
+        weapon::{self, WeaponInstance, MeleeWeapon, WEAPONS},
+        grenade::{GrenadeUpdate, GrenadeMaker},
+        security::AlarmPanel,
+        corpse::{Corpse, Blood},
+        light::Light,
     },
-    world::{Level, Statistics, World},
+    world::{Level, Statistics, World, DRAG_SPEED, Objective, ObjectiveState, Goal, Status, Action, Script, ScriptEvent, Checkpoint, Score, GRENADE, GUNSHOT_NOISE, MELEE_NOISE, FOOTSTEP_NOISE, SNEAK_FOOTSTEP_NOISE, STRIDE, Survival, WaveUpdate, Difficulty},
 };
-use crate::game::{State, GameState, StateSwitch};
+use crate::game::{State, GameState, StateSwitch, Content, Mode};
 use ggez::{
     Context, GameResult,
     graphics::{
         self, Drawable, DrawMode, Rect,
         Color, DrawParam,
         MeshBuilder, Mesh,
-        Canvas,
+        Canvas, TextFragment,
     },
     input::mouse,
 };
@@ -49,6 +57,11 @@ pub fn new_blood(mut obj: Object) -> Decal {
     }
 }
 
+/// Seconds a message from a trigger stays on the screen
+const MESSAGE_TIME: f32 = 4.;
+/// How close the player has to get to a checkpoint to reach it
+const CHECKPOINT_RANGE: f32 = 32.;
+
 /// The state of the game
 pub struct Play {
     hp_text: PosText,
@@ -56,6 +69,10 @@ pub struct Play {
     reload_text: PosText,
     wep_text: PosText,
     status_text: PosText,
+    objectives_text: PosText,
+    message_text: PosText,
+    /// Seconds left to show the trigger message
+    message_time: f32,
     hud: Hud,
     world: World,
     cur_pickup: Option<usize>,
@@ -63,19 +80,130 @@ pub struct Play {
     time: usize,
     initial: (Health, WepSlots),
     level: Level,
+    script: Option<Script>,
+    /// Events for the script since last update
+    script_events: Vec<ScriptEvent>,
+    /// Events towards achievements since last update
+    achievement_events: Vec<Event>,
+    /// The last checkpoint reached
+    checkpoint: Option<Box<Checkpoint>>,
+    /// The exit the level is being won through
+    exit: usize,
+    weapons_found: Vec<&'static str>,
+    score: Score,
+    /// The player's input so far, if the level was played from the start
+    recording: Option<Ghost>,
+    /// The best run, shown in time attack
+    ghost: Option<GhostReplay>,
+    survival: Option<Survival>,
+    /// Distance walked since the player's last footstep
+    stride: f32,
+    /// Whether the fields of vision of enemies are drawn
+    vision_cones: bool,
+    /// Seconds left of the player being blinded by a flashbang
+    flashed: f32,
+    util_text: PosText,
+    /// The throwable with its pin pulled, held until the throw button is let go
+    cooking: Option<GrenadeMaker>,
 }
 
 impl Play {
     #[allow(clippy::new_ret_no_self)]
     pub fn new(ctx: &mut Context, s: &mut State, level: Level, pl: Option<(Health, WepSlots)>) -> GameResult<Box<dyn GameState>> {
+        // Whatever was carried over from a cheated level of the campaign still counts as cheating
+        s.cheated = match &s.content {
+            Content::Campaign(cam) if pl.is_some() => cam.cheated,
+            _ => false,
+        };
+        Ok(Box::new(Self::build(ctx, s, level, pl)?))
+    }
+    /// Continues the level from a checkpoint
+    ///
+    /// `pl` is what the player started the level with in case it's restarted later
+    pub fn resume(ctx: &mut Context, s: &mut State, level: Level, pl: Option<(Health, WepSlots)>, checkpoint: Checkpoint) -> GameResult<Box<dyn GameState>> {
+        let mut play = Self::build(ctx, s, level, pl)?;
+        let Checkpoint{world, time, script, score} = checkpoint.clone();
+
+        play.world = world;
+        play.time = time;
+        play.score = score;
+        play.recording = None;
+        play.ghost = None;
+        if let (Some(playing), Some(state)) = (&mut play.script, script) {
+            playing.restore_state(state);
+        }
+        play.checkpoint = Some(Box::new(checkpoint));
+
+        Ok(Box::new(play))
+    }
+    /// Continues a saved game
+    pub fn from_save(ctx: &mut Context, s: &mut State, save: SaveGame) -> GameResult<Box<dyn GameState>> {
+        let SaveGame{content, level, initial_health, initial_weapons, world, time, script, checkpoint, weapons_found, score, cheated} = save;
+        s.content = Content::from_save(content)?;
+        s.cheated = cheated;
+
+        let mut play = Self::build(ctx, s, level, Some((initial_health, initial_weapons.restore())))?;
+        play.world = world.restore();
+        if Self::forced_difficulty(s, &play.level).is_none() {
+            s.difficulty = play.world.difficulty;
+        }
+        play.time = time;
+        play.score = score;
+        play.recording = None;
+        play.ghost = None;
+        if let (Some(playing), Some(state)) = (&mut play.script, script) {
+            playing.restore_state(state);
+        }
+        play.checkpoint = checkpoint.map(|c| Box::new(c.restore()));
+        play.weapons_found = weapons_found.iter().filter_map(|id| WEAPONS.get(&**id)).map(|w| w.id).collect();
+
+        Ok(Box::new(play))
+    }
+    /// The difficulty the level or its campaign has to be played on, if any
+    fn forced_difficulty(s: &State, level: &Level) -> Option<Difficulty> {
+        level.difficulty.or(match &s.content {
+            Content::Campaign(cam) => cam.difficulty,
+            _ => None,
+        })
+    }
+    fn build(ctx: &mut Context, s: &mut State, level: Level, pl: Option<(Health, WepSlots)>) -> GameResult<Self> {
         mouse::set_cursor_hidden(ctx, true);
 
-        let mut player = Player::from_point(level.start_point.unwrap_or_else(|| point!(500., 500.)));
+        let difficulty = Self::forced_difficulty(s, &level).unwrap_or(s.difficulty);
+
+        let start = level.start_point.unwrap_or_else(|| point!(500., 500.));
+        let mut player = Player::from_point(start);
         if let Some((h, w)) = pl {
             player = player.with_health(h).with_weapon(w);
         };
+        let script = level.script.as_ref().and_then(|path| {
+            Script::load(path).map_err(|e| error!("Couldn't load script {}: {}", path, e)).ok()
+        });
+        let ghost = if s.mode == Mode::TimeAttack {
+            s.content.level_key().and_then(|key| Ghost::load(&key).ok()).map(|ghost| GhostReplay::new(ghost, start))
+        } else {
+            None
+        };
+        let survival = if s.mode == Mode::Survival {
+            let survival = Survival::new(&level);
+            if survival.is_none() {
+                error!("Can't play survival on a level without spawn points or enemies");
+                s.mode = Mode::Normal;
+            }
+            survival
+        } else {
+            None
+        };
+        // Survival goes on until the player dies
+        let objectives = if survival.is_some() {
+            Vec::new()
+        } else if level.objectives.is_empty() {
+            Objective::defaults(&level)
+        } else {
+            level.objectives.clone()
+        };
 
-        Ok(Box::new(
+        Ok(
             Play {
                 level: level.clone(),
                 initial: (player.health, player.wep.clone()),
@@ -84,16 +212,30 @@ impl Play {
                 reload_text: s.assets.text(point!(4., 62.)).and_text("0.0").and_text("s"),
                 wep_text: WeaponInstance::weapon_text(point!(2., 87.), &s.assets),
                 status_text: s.assets.text(point!(s.width as f32 / 2., s.height as f32 / 2. + 32.)).and_text("").centered(),
+                objectives_text: s.assets.text(point!(4., 110.)),
+                message_text: s.assets.text(point!(s.width / 2., 48.)).and_text("").centered(),
+                message_time: 0.,
                 hud: Hud::new(ctx)?,
                 time: 0,
                 victory_time: 0.,
                 cur_pickup: None,
+                script,
+                script_events: Vec::new(),
+                achievement_events: Vec::new(),
+                checkpoint: None,
+                exit: 0,
+                weapons_found: Vec::new(),
+                score: Score::default(),
+                recording: Some(Ghost::default()),
+                ghost,
                 world: {
                     let mut world = World {
+                        next_enemy_id: level.enemies.len(),
                         enemies: level.enemies,
                         bullets: Vec::new(),
                         grenades: Vec::new(),
                         decal_queue: level.decals.clone(),
+                        decals: Vec::new(),
                         weapons: level.weapons,
                         player,
                         canvas: None,
@@ -101,8 +243,34 @@ impl Play {
                         grid: level.grid,
                         exit: level.exit,
                         intels: level.intels,
+                        intels_collected: 0,
+                        checkpoints: level.checkpoints,
+                        npcs: level.npcs,
+                        objectives: Vec::new(),
+                        triggers: level.triggers,
                         pickups: level.pickups.into_iter().map(|(p, i)| Pickup::new(p, i)).collect(),
+                        difficulty,
+                        alarm: false,
+                        alarm_panels: level.alarms.into_iter().map(AlarmPanel::new).collect(),
+                        cameras: level.cameras,
+                        corpses: Vec::new(),
+                        blood: Vec::new(),
+                        dragging: None,
+                        fog: level.fog,
+                        lights: Vec::new(),
+                        ambient: level.ambient,
+                        light_map: None,
                     };
+                    world.lights = level.decals.iter().filter_map(|d| Light::from_decal(d, &world.palette, &world.grid)).collect();
+                    for (i, enemy) in world.enemies.iter_mut().enumerate() {
+                        enemy.id = i;
+                        enemy.pl.wep.utilities.set(GRENADE, difficulty.enemy_grenades());
+                    }
+                    if survival.is_some() {
+                        world.enemies.clear();
+                        world.checkpoints.clear();
+                    }
+                    world.objectives = objectives.into_iter().map(|o| ObjectiveState::new(o, &world)).collect();
                     world.enemy_pickup();
                     world.player_pickup();
 
@@ -116,8 +284,131 @@ impl Play {
 
                     world
                 },
+                survival,
+                stride: 0.,
+                vision_cones: false,
+                flashed: 0.,
+                cooking: None,
+                util_text: s.assets.text(point!(172., 36.)).and_text("0"),
             }
-        ))
+        )
+    }
+    /// The weapons picked up during the level that the player isn't carrying anymore, as they were dropped
+    ///
+    /// Weapons dropped by the player are added to the end, so the last one of a kind is taken
+    /// over any untouched one lying around the level.
+    fn weapons_left(&self) -> Vec<WeaponInstance<'static>> {
+        let mut left: Vec<WeaponInstance<'static>> = Vec::new();
+        for &id in &self.weapons_found {
+            let carried = self.world.player.wep.clone().into_iter().chain(left.iter().copied()).any(|w| w.weapon.id == id);
+            if carried {
+                continue
+            }
+            if let Some(drop) = self.world.weapons.iter().rev().find(|d| d.weapon.id == id) {
+                left.push(WeaponInstance::from_drop(drop.clone()));
+            }
+        }
+        left
+    }
+    /// A snapshot of the world with anything being cooked back unthrown with the rest of the player's throwables
+    fn uncooked_world(&self) -> World {
+        let mut world = self.world.snapshot();
+        if let Some(gm) = &self.cooking {
+            world.player.wep.utilities.add(&gm.throwable.id, 1);
+        }
+        world
+    }
+    /// Aims a throw from the player's hand so it comes to rest at `target`, as strong as the arm allows
+    fn aim_throw(&self, gm: GrenadeMaker, target: Point2) -> (GrenadeMaker, Object) {
+        let player = &self.world.player.obj;
+        let mut gren = Object::new(player.pos + 20. * angle_to_vec(player.rot));
+        gren.rot = player.rot;
+
+        (gm.with_range((target - gren.pos).norm()), gren)
+    }
+    /// The path a grenade thrown at `target` would take
+    fn throw_path(&self, gm: GrenadeMaker, target: Point2) -> Vec<Point2> {
+        let (gm, gren) = self.aim_throw(gm, target);
+        gm.predict_path(gren.pos, gren.rot, &self.world.palette, &self.world.grid)
+    }
+    /// Hits the closest enemy in reach of the player's melee weapon once a swing lands
+    ///
+    /// Enemies that have no idea the player is around are taken down silently
+    fn land_melee(&mut self, s: &mut State, ctx: &mut Context) -> GameResult<()> {
+        let melee = self.world.player.wep.melee.weapon;
+        let player = self.world.player.obj.clone();
+
+        let hit = self.world.enemies.iter()
+            .enumerate()
+            .filter(|(_, e)| melee.in_reach(&player, e.pl.obj.pos))
+            .min_by(|(_, a), (_, b)| (a.pl.obj.pos - player.pos).norm().total_cmp(&(b.pl.obj.pos - player.pos).norm()))
+            .map(|(i, _)| i);
+        let i = match hit {
+            Some(i) => i,
+            None => return Ok(()),
+        };
+
+        let enemy = &mut self.world.enemies[i];
+        let takedown = !self.world.alarm && enemy.unaware();
+        let backstab = MeleeWeapon::behind(player.pos, &enemy.pl.obj);
+        let pos = enemy.pl.obj.pos;
+
+        self.world.decal_queue.push(new_blood(enemy.pl.obj.clone()));
+        if takedown {
+            enemy.pl.health.hp = 0.;
+        } else {
+            enemy.pl.health.weapon_damage(melee.damage(backstab), melee.penetration);
+        }
+        if takedown || backstab {
+            s.mplayer.play(ctx, melee.backstab_snd)?;
+        }
+        // Only stabs in the back count as backstabs, however unaware the enemy was
+        if backstab {
+            self.score.backstabs += 1;
+            self.achievement_events.push(Event::Backstab);
+        }
+
+        if enemy.pl.health.is_dead() {
+            let Enemy{pl: Player{wep, obj, ..}, id, ..} = self.world.enemies.remove(i);
+            if !takedown {
+                s.mplayer.play(ctx, "death")?;
+            }
+            self.script_events.push(ScriptEvent::EnemyKilled{id, pos});
+            self.world.blood.push(Blood::new(pos));
+            self.world.corpses.push(Corpse::new(obj));
+            self.score.kill(melee.id);
+            self.achievement_events.push(Event::Kill);
+
+            for wep in wep {
+                self.world.weapons.push(wep.into_drop(pos));
+            }
+        } else {
+            if !enemy.behaviour.chasing() {
+                enemy.behaviour.go_to_then_go_back(pos, player.pos);
+            }
+            s.mplayer.play(ctx, "hurt")?;
+        }
+        // Taking someone down quietly is the point
+        if !takedown {
+            self.world.noise(pos, MELEE_NOISE);
+        }
+        Ok(())
+    }
+    /// Statistics for the lose screen, letting the level be restarted as it was started
+    fn lose_statistics(&self) -> Box<Statistics> {
+        Box::new(Statistics{
+            time: self.time,
+            enemies_left: self.world.enemies.len(),
+            health_left: self.initial.0,
+            level: self.level.clone(),
+            weapon: self.initial.1.clone(),
+            checkpoint: self.checkpoint.clone(),
+            exit: 0,
+            weapons_left: Vec::new(),
+            score: self.score.clone(),
+            recording: None,
+            waves_survived: self.survival.as_ref().map(|s| s.cleared),
+        })
     }
     pub fn add_decal(&mut self, decal: Decal) {
         self.world.decal_queue.push(decal);
@@ -133,18 +424,52 @@ impl GameState for Play {
             self.reload_text.update(0, format!("{:.1}", wep.loading_time))?;
             wep.update_text(&mut self.wep_text)?;
         }
+        {
+            let utilities = &self.world.player.wep.utilities;
+            let text = match &self.cooking {
+                Some(gm) => format!("{:.1}s", gm.fuse()),
+                None => format!("{}", utilities.count(&utilities.selected().id)),
+            };
+            self.util_text.update(0, text)?;
+        }
         if let Some(i) = self.cur_pickup {
             // TODO change text to say what's being swapped out
             self.status_text.text.fragments_mut()[0]= format!("Press F to pick up {}", self.world.weapons[i]).into();
+        } else if self.world.panel_in_reach().is_some() {
+            self.status_text.update(0, "Press F to disable alarm panel")?;
+        } else if self.world.dragging.is_none() && self.world.corpse_in_reach().is_some() {
+            self.status_text.update(0, "Press E to drag body")?;
         } else {
             self.status_text.update(0, "")?;
         }
+        if let Some(survival) = &self.survival {
+            let text = match survival.countdown {
+                Some(countdown) => format!("Wave {} in {:.0}s", survival.wave + 1, countdown.ceil()),
+                None => format!("Wave {}: {} enemies left", survival.wave, self.world.enemies.len()),
+            };
+            self.objectives_text.text = s.assets.raw_text_with(&text, 18.);
+        } else {
+            let time = self.time as f32 * DELTA;
+            self.objectives_text.text = self.world.objectives.iter().fold(s.assets.raw_text(18.), |mut text, objective| {
+                let frag = TextFragment::from(objective.progress_text(time));
+                text.add(match objective.status {
+                    Status::Active => frag,
+                    Status::Locked => frag.color(TRANS),
+                    Status::Complete => frag.color(GREEN),
+                    Status::Failed => frag.color(RED),
+                });
+                text
+            });
+        }
+
+        let health_before = self.world.player.health;
+        let mut burnt_to_death = false;
 
         iterate_and_kill_afterwards_mut(&mut self.world.grenades, |grenade| {
-            let g_update = grenade.update(ctx, &self.world.palette, &self.world.grid, &mut self.world.player, &mut *self.world.enemies)?;
+            let g_update = grenade.update(ctx, &self.world.palette, &self.world.grid, &mut self.world.player, self.world.difficulty.damage_taken(), &mut *self.world.enemies, &mut self.world.npcs)?;
 
             Ok(match g_update {
-                GrenadeUpdate::Explosion{player_hit, enemy_hits} => {
+                GrenadeUpdate::Explosion{player_hit, enemy_hits, npc_hits} => {
                     s.mplayer.play(ctx, "boom")?;
 
                     self.world.decal_queue.push(Decal {
@@ -162,12 +487,19 @@ impl GameState for Play {
                                 health_left: self.initial.0,
                                 level: self.level.clone(),
                                 weapon: self.initial.1.clone(),
+                                checkpoint: self.checkpoint.clone(),
+                                exit: 0,
+                                weapons_left: Vec::new(),
+                                score: self.score.clone(),
+                                recording: None,
+                                waves_survived: self.survival.as_ref().map(|s| s.cleared),
                             })));
                             s.mplayer.play(ctx, "death")?;
                         } else {
                             s.mplayer.play(ctx, "hurt")?;
                         }
                     }
+                    let mut kills = 0;
                     for i in enemy_hits {
                         let enemy = &self.world.enemies[i];
                         s.mplayer.play(ctx, "hit")?;
@@ -175,9 +507,18 @@ impl GameState for Play {
                         self.world.decal_queue.push(new_blood(enemy.pl.obj.clone()));
                         if enemy.pl.health.is_dead() {
                             s.mplayer.play(ctx, "death")?;
+                            if grenade.from_player {
+                                self.score.kill(&grenade.throwable.id);
+                                self.achievement_events.push(Event::Kill);
+                                kills += 1;
+                            }
 
-                            let Enemy{pl: Player{wep, obj: Object{pos, ..}, ..}, ..}
+                            let Enemy{pl: Player{wep, obj, ..}, id, ..}
                                 = self.world.enemies.remove(i);
+                            let pos = obj.pos;
+                            self.world.blood.push(Blood::new(pos));
+                            self.world.corpses.push(Corpse::new(obj));
+                            self.script_events.push(ScriptEvent::EnemyKilled{id, pos});
                             for wep in wep {
                                 self.world.weapons.push(wep.into_drop(pos));
                             }
@@ -189,15 +530,73 @@ impl GameState for Play {
                             s.mplayer.play(ctx, "hurt")?;
                         }
                     }
+                    if kills > 0 {
+                        self.achievement_events.push(Event::GrenadeKills(kills));
+                    }
+                    for i in npc_hits {
+                        let npc = &self.world.npcs[i];
+                        s.mplayer.play(ctx, "hit")?;
+
+                        self.world.decal_queue.push(new_blood(npc.obj.clone()));
+                        s.mplayer.play(ctx, if npc.health.is_dead() { "death" } else { "hurt" })?;
+                    }
+                    false
+                }
+                // Unlike an explosion, a fire burning away doesn't spill blood or have anyone come looking every time it hurts
+                GrenadeUpdate::Burn{player_hit, enemy_hits, npc_hits} => {
+                    if player_hit {
+                        if self.world.player.health.is_dead() {
+                            burnt_to_death = true;
+                        } else {
+                            s.mplayer.play(ctx, "hurt")?;
+                        }
+                    }
+                    for i in enemy_hits {
+                        if self.world.enemies[i].pl.health.is_dead() {
+                            s.mplayer.play(ctx, "death")?;
+                            if grenade.from_player {
+                                self.score.kill(&grenade.throwable.id);
+                                self.achievement_events.push(Event::Kill);
+                            }
+
+                            let Enemy{pl: Player{wep, obj, ..}, id, ..}
+                                = self.world.enemies.remove(i);
+                            let pos = obj.pos;
+                            self.world.blood.push(Blood::new(pos));
+                            self.world.corpses.push(Corpse::new(obj));
+                            self.script_events.push(ScriptEvent::EnemyKilled{id, pos});
+                            for wep in wep {
+                                self.world.weapons.push(wep.into_drop(pos));
+                            }
+                        } else {
+                            s.mplayer.play(ctx, "hurt")?;
+                        }
+                    }
+                    for i in npc_hits {
+                        s.mplayer.play(ctx, if self.world.npcs[i].health.is_dead() { "death" } else { "hurt" })?;
+                    }
+                    false
+                }
+                GrenadeUpdate::Flash{player_blinded} => {
+                    s.mplayer.play(ctx, "boom")?;
+                    self.flashed = self.flashed.max(player_blinded);
+                    false
+                }
+                GrenadeUpdate::Spread => {
+                    s.mplayer.play(ctx, "impact")?;
                     false
                 }
                 GrenadeUpdate::Dead => true,
                 GrenadeUpdate::None => false
             })
         })?;
+        if burnt_to_death {
+            s.switch(StateSwitch::Lose(self.lose_statistics()));
+            s.mplayer.play(ctx, "death")?;
+        }
 
         iterate_and_kill_afterwards_mut(&mut self.world.bullets, |bullet| {
-            let hit = bullet.update(&self.world.palette, &self.world.grid, &mut self.world.player, &mut *self.world.enemies);
+            let hit = bullet.update(&self.world.palette, &self.world.grid, &mut self.world.player, self.world.difficulty.damage_taken(), &mut *self.world.enemies, &mut self.world.npcs);
             let mut dead = false;
 
             use topskud::obj::bullet::Hit;
@@ -226,6 +625,12 @@ impl GameState for Play {
                             health_left: self.initial.0,
                             level: self.level.clone(),
                             weapon: self.initial.1.clone(),
+                            checkpoint: self.checkpoint.clone(),
+                            exit: 0,
+                            weapons_left: Vec::new(),
+                            score: self.score.clone(),
+                            recording: None,
+                            waves_survived: self.survival.as_ref().map(|s| s.cleared),
                         })));
                         s.mplayer.play(ctx, "death")?;
                     } else {
@@ -236,13 +641,24 @@ impl GameState for Play {
                     dead = true;
                     let enemy = &self.world.enemies[e];
                     s.mplayer.play(ctx, "hit")?;
+                    if bullet.from_player {
+                        self.score.shots_hit += 1;
+                    }
 
                     self.world.decal_queue.push(new_blood(bullet.obj.clone()));
                     if enemy.pl.health.is_dead() {
                         s.mplayer.play(ctx, "death")?;
+                        if bullet.from_player {
+                            self.score.kill(bullet.weapon.id);
+                            self.achievement_events.push(Event::Kill);
+                        }
 
-                        let Enemy{pl: Player{wep, obj: Object{pos, ..}, ..}, ..}
+                        let Enemy{pl: Player{wep, obj, ..}, id, ..}
                             = self.world.enemies.remove(e);
+                        let pos = obj.pos;
+                        self.world.blood.push(Blood::new(pos));
+                        self.world.corpses.push(Corpse::new(obj));
+                        self.script_events.push(ScriptEvent::EnemyKilled{id, pos});
                         for wep in wep {
                             self.world.weapons.push(wep.into_drop(pos));
                         }
@@ -254,22 +670,34 @@ impl GameState for Play {
                         s.mplayer.play(ctx, "hurt")?;
                     }
                 }
+                Hit::Npc(i) => {
+                    dead = true;
+                    s.mplayer.play(ctx, "hit")?;
+
+                    self.world.decal_queue.push(new_blood(bullet.obj.clone()));
+                    s.mplayer.play(ctx, if self.world.npcs[i].health.is_dead() { "death" } else { "hurt" })?;
+                }
             }
             Ok(dead)
         })?;
+        self.score.record_damage(health_before, self.world.player.health);
 
         iterate_and_kill_afterwards(&mut self.world.intels, |&intel| {
             Ok(if (intel-self.world.player.obj.pos).norm() <= 15. {
                 s.mplayer.play(ctx, "hit")?;
+                self.world.intels_collected += 1;
+                self.script_events.push(ScriptEvent::Pickup("intel".to_owned()));
                 true
             } else { false })
         })?;
         iterate_and_kill_afterwards(&mut self.world.pickups, |pickup| {
-            Ok(if (pickup.pos-self.world.player.obj.pos).norm() <= 15. && pickup.apply(&mut self.world.player.health) {
+            Ok(if (pickup.pos-self.world.player.obj.pos).norm() <= 15. && pickup.apply(&mut self.world.player, self.world.difficulty.pickup_amount()) {
                 s.mplayer.play(ctx, "hit")?;
+                self.script_events.push(ScriptEvent::Pickup(pickup.pickup_type.spr.trim_start_matches("pickups/").to_owned()));
                 true
             } else { false })
         })?;
+        self.world.enemy_scavenge();
         self.cur_pickup = None;
         for (i, weapon) in self.world.weapons.iter().enumerate().rev() {
             if (weapon.pos-self.world.player.obj.pos).norm() <= 29. {
@@ -281,31 +709,69 @@ impl GameState for Play {
         let ctrls = s.controls.ctx(ctx);
         // Define player velocity here already because enemies need it
         let player_vel = vector!(ctrls.axis(Axis::RightLeft), ctrls.axis(Axis::DownUp));
+        let sneaking = ctrls.is_mod_active(KeyMods::SHIFT);
+        let light = self.world.light_at(self.world.player.obj.pos);
 
-        let &mut World {ref grid, ref palette, ref mut enemies, ref player, ref mut bullets, ..} = &mut self.world;
+        let &mut World {ref grid, ref palette, ref mut enemies, ref player, ref npcs, ref mut bullets, ref mut grenades, difficulty, ..} = &mut self.world;
+        let sight_range = difficulty.sight_range();
+        let mut stabbed = None;
 
         for enemy in enemies.iter_mut() {
-            if enemy.can_see(player.obj.pos, palette, grid) {
-                // If an enemy can see the player, they will chase them and shoot
+            let obscured = grenades.iter().any(|g| g.blocks_sight(enemy.pl.obj.pos, player.obj.pos));
+            let sees_player = enemy.detect(player.obj.pos, sneaking, obscured, light, sight_range, palette, grid);
+            if let Some(at) = enemy.plan(sees_player.then_some(player.obj.pos), palette, grid) {
+                // Flush the player out from behind cover
+                if let Some(gm) = enemy.pl.wep.utilities.throw_grenade(ctx, &mut s.mplayer)? {
+                    let dist = at - enemy.pl.obj.pos;
+                    let mut gren = Object::new(enemy.pl.obj.pos + 20. * dist.normalize());
+                    gren.rot = angle_from_vec(dist);
+
+                    grenades.push(gm.with_range(dist.norm()).make(gren));
+                }
+            }
+            let target = if sees_player {
+                self.score.detected = true;
+                Some((player.obj.pos, player_vel))
+            } else {
+                npcs.iter()
+                    .filter(|npc| !npc.health.is_dead())
+                    .find(|npc| enemy.can_see(npc.obj.pos, sight_range, palette, grid))
+                    .map(|npc| (npc.obj.pos, vector!(0., 0.)))
+            };
+            if let Some((target_pos, target_vel)) = target {
+                // If an enemy can see the player or an NPC, they will chase them and shoot
 
                 // enemy.behaviour.chase_then_wander(self.world.player.obj.pos);
-                enemy.behaviour.path_then_wander(vec![player.obj.pos, player.obj.pos+16.*player_vel]);
+                if !enemy.busy() {
+                    enemy.behaviour.path_then_wander(vec![target_pos, target_pos+16.*target_vel]);
+                }
 
-                if let Some(wep) = enemy.pl.wep.get_active_mut() {
-                    if let Some(bm) = wep.shoot(ctx, &mut s.mplayer)? {
-                        let pos = enemy.pl.obj.pos + 20. * angle_to_vec(enemy.pl.obj.rot);
-                        let mut bul = Object::new(pos);
-                        bul.rot = enemy.pl.obj.rot;
+                if enemy.aim.update(difficulty) {
+                    if let Some(wep) = enemy.pl.wep.get_active_mut() {
+                        if let Some(bm) = wep.shoot(ctx, &mut s.mplayer)? {
+                            let pos = enemy.pl.obj.pos + 20. * angle_to_vec(enemy.pl.obj.rot);
+                            let mut bul = Object::new(pos);
+                            bul.rot = enemy.pl.obj.rot + enemy.aim.offset;
 
-                        for bullet in bm.make(bul) {
-                            bullets.push(bullet);
+                            for bullet in bm.make(bul) {
+                                bullets.push(bullet);
+                            }
+                            enemy.aim.fired(wep.weapon.fire_mode.is_auto());
                         }
+                    } else if sees_player && enemy.pl.wep.melee.weapon.in_reach(&enemy.pl.obj, player.obj.pos) && enemy.pl.wep.melee.start_swing() {
+                        s.mplayer.play(ctx, enemy.pl.wep.melee.weapon.swing_snd)?;
                     }
                 }
+            } else {
+                enemy.aim.lose_sight();
+            }
+            let melee = enemy.pl.wep.melee.weapon;
+            if enemy.pl.wep.melee.update() && melee.in_reach(&enemy.pl.obj, player.obj.pos) {
+                stabbed = Some((melee, MeleeWeapon::behind(enemy.pl.obj.pos, &player.obj)));
             }
             let from = enemy.pl.obj.pos;
 
-            enemy.update(ctx, &mut s.mplayer, || {
+            enemy.update(ctx, &mut s.mplayer, difficulty, || {
                 let dir = thread_rng().gen_range(0. .. 2. * std::f32::consts::PI);
                 let length = thread_rng().gen_range(0. ..= 1.);
 
@@ -318,7 +784,32 @@ impl GameState for Play {
             })?;
         }
 
-        let speed = if !s.controls.ctx(ctx).is_mod_active(KeyMods::SHIFT) {
+        if let Some((melee, backstab)) = stabbed {
+            let before = self.world.player.health;
+            self.world.player.health.weapon_damage(melee.damage(backstab) * difficulty.damage_taken(), melee.penetration);
+            self.score.record_damage(before, self.world.player.health);
+            self.world.decal_queue.push(new_blood(self.world.player.obj.clone()));
+            if backstab {
+                s.mplayer.play(ctx, melee.backstab_snd)?;
+            }
+
+            if self.world.player.health.is_dead() {
+                s.switch(StateSwitch::Lose(self.lose_statistics()));
+                s.mplayer.play(ctx, "death")?;
+            } else {
+                s.mplayer.play(ctx, "hurt")?;
+            }
+        }
+        if self.world.update_alarm() {
+            self.message_text.update(0, "Alarm raised!")?;
+            self.message_time = MESSAGE_TIME;
+            s.mplayer.play(ctx, "ding")?;
+        }
+        self.world.notice_bodies();
+
+        let speed = if self.world.dragging.is_some() {
+            DRAG_SPEED
+        } else if !sneaking {
             200.
         } else {
             100.
@@ -331,18 +822,133 @@ impl GameState for Play {
                     let mut bul = Object::new(pos);
                     bul.rot = self.world.player.obj.rot;
 
-                    for bullet in bm.make(bul) {
+                    for mut bullet in bm.make(bul) {
+                        bullet.from_player = true;
+                        self.score.shots_fired += 1;
                         self.world.bullets.push(bullet);
                     }
+                    self.world.noise(pos, GUNSHOT_NOISE);
                 }
             }
         }
+        if self.world.player.wep.melee.update() {
+            self.land_melee(s, ctx)?;
+        }
+        // Held too long, it goes off in the player's hand
+        if self.cooking.as_mut().map(GrenadeMaker::cook).unwrap_or(false) {
+            let gm = self.cooking.take().unwrap();
+            let mut grenade = gm.with_range(0.).make(Object::new(self.world.player.obj.pos));
+            grenade.from_player = true;
+            self.world.grenades.push(grenade);
+        }
+        let before = self.world.player.obj.pos;
         self.world.player.obj.move_on_grid(player_vel, speed, &self.world.palette, &self.world.grid);
+        self.stride += (self.world.player.obj.pos - before).norm();
+        if self.stride >= STRIDE {
+            self.stride = 0.;
+            self.world.noise(self.world.player.obj.pos, if sneaking { SNEAK_FOOTSTEP_NOISE } else { FOOTSTEP_NOISE });
+        }
+        if let Some(smear) = self.world.drag_corpse() {
+            self.world.decal_queue.push(new_blood(smear));
+        }
+        if self.victory_time <= 0. {
+            if let Some(recording) = &mut self.recording {
+                recording.record(player_vel, speed, self.world.player.obj.rot);
+            }
+        }
+        if let Some(ghost) = &mut self.ghost {
+            ghost.update(&self.world.palette, &self.world.grid);
+        }
 
-        let game_won = match self.world.exit {
-            Some(p) => self.world.intels.is_empty() && (p - self.world.player.obj.pos).norm() < 32.,
-            None => self.world.enemies.is_empty(),
-        };
+        let player_pos = self.world.player.obj.pos;
+        if let Some(i) = self.world.checkpoints.iter().position(|&c| (c - player_pos).norm() <= CHECKPOINT_RANGE) {
+            self.world.checkpoints.remove(i);
+            self.checkpoint = Some(Box::new(Checkpoint {
+                world: self.uncooked_world(),
+                time: self.time,
+                script: self.script.as_ref().map(Script::save_state),
+                score: self.score.clone(),
+            }));
+            self.message_text.update(0, "Checkpoint reached")?;
+            self.message_time = MESSAGE_TIME;
+            s.mplayer.play(ctx, "reload")?;
+        }
+
+        if let Some(survival) = &mut self.survival {
+            match survival.update(&mut self.world, &self.level) {
+                WaveUpdate::Started(wave) => {
+                    self.message_text.update(0, format!("Wave {}", wave))?;
+                    self.message_time = MESSAGE_TIME;
+                }
+                WaveUpdate::Cleared(wave) => {
+                    self.message_text.update(0, format!("Wave {} cleared", wave))?;
+                    self.message_time = MESSAGE_TIME;
+                    s.mplayer.play(ctx, "reload")?;
+                }
+                WaveUpdate::None => (),
+            }
+        }
+
+        self.world.update_objectives(self.time as f32 * DELTA);
+        let mut game_won = self.world.objectives_won();
+        let mut game_lost = self.world.objectives_failed();
+
+        if self.message_time > 0. {
+            self.message_time -= DELTA;
+            if self.message_time <= 0. {
+                self.message_text.update(0, "")?;
+            }
+        }
+        if self.flashed > 0. {
+            self.flashed -= DELTA;
+        }
+        let mut actions = self.world.fire_triggers();
+        if let Some(script) = &mut self.script {
+            script.update(&self.world, self.time as f32 * DELTA, self.script_events.drain(..));
+            for cmd in script.take_commands() {
+                actions.extend(self.world.apply_script_command(cmd));
+            }
+        } else {
+            // Nothing is listening for them
+            self.script_events.clear();
+        }
+        for event in self.achievement_events.drain(..) {
+            if s.cheated {
+                continue;
+            }
+            if let Some(achievement) = s.profile.achieve(event).last() {
+                self.message_text.update(0, format!("Achievement unlocked: {}", achievement.name))?;
+                self.message_time = MESSAGE_TIME;
+            }
+        }
+        for action in actions {
+            match &action {
+                Action::Message(msg) => {
+                    self.message_text.update(0, &**msg)?;
+                    self.message_time = MESSAGE_TIME;
+                }
+                Action::PlaySound(snd) => s.mplayer.play(ctx, snd)?,
+                &Action::EndLevel{won} => if won {
+                    game_won = true;
+                } else {
+                    game_lost = true;
+                },
+                &Action::Exit(exit) => {
+                    self.exit = exit;
+                    game_won = true;
+                }
+                _ => self.world.apply_action(&action),
+            }
+        }
+
+        // Survival can only be lost
+        if self.survival.is_some() {
+            game_won = false;
+        }
+        if game_lost && self.victory_time <= 0. {
+            s.switch(StateSwitch::Lose(self.lose_statistics()));
+            return Ok(());
+        }
 
         if game_won && self.victory_time <= 0. {
             s.mplayer.play(ctx, "victory")?;
@@ -359,6 +965,12 @@ impl GameState for Play {
                 enemies_left: self.world.enemies.len(),
                 health_left: self.world.player.health,
                 weapon: self.world.player.wep.clone(),
+                checkpoint: None,
+                exit: self.exit,
+                weapons_left: self.weapons_left(),
+                score: self.score.clone(),
+                recording: self.recording.clone(),
+                waves_survived: None,
             })));
         }
         Ok(())
@@ -386,15 +998,38 @@ impl GameState for Play {
             let img = s.assets.get_img("common/intel");
             canvas.draw(&*img, drawparams);
         }
+        for &checkpoint in &self.world.checkpoints {
+            let drawparams = graphics::DrawParam::default()
+                .dest(checkpoint)
+                .offset(point!(0.5, 0.5));
+            let img = s.assets.get_img("common/checkpoint");
+            canvas.draw(&*img, drawparams);
+        }
+
+        for objective in &self.world.objectives {
+            if let (Goal::Defuse{pos, ..}, Status::Active) = (objective.objective.goal, objective.status) {
+                let drawparams = graphics::DrawParam::default()
+                    .dest(pos)
+                    .offset(point!(0.5, 0.5));
+                let img = s.assets.get_img("weapons/pineapple");
+                canvas.draw(&*img, drawparams);
+            }
+        }
 
-        for pickup in &self.world.pickups {
+        for corpse in self.world.corpses.iter().filter(|c| self.world.player_sees(c.obj.pos)) {
+            corpse.draw(canvas, &s.assets);
+        }
+        for panel in &self.world.alarm_panels {
+            panel.draw(canvas, &s.assets);
+        }
+        for pickup in self.world.pickups.iter().filter(|p| self.world.player_sees(p.pos)) {
             let drawparams = graphics::DrawParam::default()
                 .dest(pickup.pos)
                 .offset(point!(0.5, 0.5));
             let img = s.assets.get_img(pickup.pickup_type.spr);
             canvas.draw(&*img, drawparams);
         }
-        for wep in &self.world.weapons {
+        for wep in self.world.weapons.iter().filter(|w| self.world.player_sees(w.pos)) {
             let drawparams = graphics::DrawParam::default()
                 .dest(wep.pos)
                 .offset(point!(0.5, 0.5));
@@ -402,10 +1037,29 @@ impl GameState for Play {
             canvas.draw(&*img, drawparams);
         }
 
+        if let Some(ghost) = &self.ghost {
+            if !ghost.finished() {
+                ghost.draw(canvas, &s.assets.get_img("common/player"));
+            }
+        }
         self.world.player.draw_player(canvas, &s.assets);
 
-        for enemy in &self.world.enemies {
+        for npc in self.world.npcs.iter().filter(|npc| !npc.health.is_dead()) {
+            npc.draw(canvas, &s.assets, "common/player", GREEN);
+        }
+
+        if self.vision_cones {
+            let sight_range = self.world.difficulty.sight_range();
+            for enemy in self.world.enemies.iter().filter(|e| self.world.player_sees(e.pl.obj.pos)) {
+                enemy.draw_visibility_cone(ctx, canvas, sight_range, &self.world.palette, &self.world.grid)?;
+            }
+        }
+        for enemy in self.world.enemies.iter().filter(|e| self.world.player_sees(e.pl.obj.pos)) {
             enemy.draw(canvas, &s.assets, Color::WHITE);
+            enemy.draw_detection(ctx, canvas)?;
+        }
+        for camera in &self.world.cameras {
+            camera.draw(canvas, &s.assets, Color::WHITE);
         }
         for bullet in &self.world.bullets {
             bullet.draw(canvas, &s.assets);
@@ -413,10 +1067,28 @@ impl GameState for Play {
         for grenade in &self.world.grenades {
             grenade.draw(canvas, &s.assets);
         }
+        self.world.draw_lighting(ctx, canvas)?;
+        self.world.draw_fog(ctx, canvas)?;
+
+        if let Some(gm) = &self.cooking {
+            let path = self.throw_path(gm.clone(), s.mouse - s.offset);
+            if let Some(&end) = path.last() {
+                if path.len() > 1 {
+                    let arc = Mesh::new_line(ctx, &path, 2., Color{a: 0.5, ..Color::WHITE})?;
+                    canvas.draw(&arc, DrawParam::default());
+                }
+                let landing = Mesh::new_circle(ctx, DrawMode::stroke(2.), end, 8., 0.5, Color{a: 0.5, ..Color::WHITE})?;
+                canvas.draw(&landing, DrawParam::default());
+            }
+        }
 
         Ok(())
     }
     fn draw_hud(&mut self, s: &State, canvas: &mut Canvas, ctx: &mut Context) -> GameResult<()> {
+        if self.flashed > 0. {
+            let whiteout = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect{x: 0., y: 0., w: s.width, h: s.height}, Color{a: self.flashed.min(1.), ..Color::WHITE})?;
+            canvas.draw(&whiteout, DrawParam::default());
+        }
         self.hud.draw(canvas)?;
 
         self.hp_text.draw_text(canvas);
@@ -424,10 +1096,13 @@ impl GameState for Play {
         self.reload_text.draw_text(canvas);
         self.wep_text.draw_text(canvas);
         self.status_text.draw_text(canvas);
+        self.objectives_text.draw_text(canvas);
+        self.message_text.draw_text(canvas);
+        self.util_text.draw_text(canvas);
 
         {
             let drawparams = DrawParam::from(point![104., 2.]);
-            let img = s.assets.get_img("weapons/knife");
+            let img = s.assets.get_img(self.world.player.wep.melee.weapon.entity_sprite);
             canvas.draw(&*img, drawparams);
         }
         if let Some(holster_wep) = &self.world.player.wep.holster {
@@ -445,6 +1120,12 @@ impl GameState for Play {
             let img = s.assets.get_img(&sling_wep.weapon.entity_sprite);
             canvas.draw(&*img, drawparams);
         }
+        {
+            let throwable = self.world.player.wep.utilities.selected();
+            let drawparams = DrawParam::from(point![186., 18.]).offset(point!(0.5, 0.5));
+            let img = s.assets.get_img(&throwable.sprite);
+            canvas.draw(&*img, drawparams);
+        }
         let selection = Mesh::new_rectangle(ctx, DrawMode::stroke(2.), RECTS[self.world.player.wep.active as u8 as usize], Color{r: 1., g: 1., b: 0., a: 1.})?;
         canvas.draw(&selection, DrawParam::default());
 
@@ -456,8 +1137,27 @@ impl GameState for Play {
         canvas.draw(&*img, drawparams);
         Ok(())
     }
+    fn event_down(&mut self, s: &mut State, ctx: &mut Context, input: Input) {
+        if let Input::ThrowGrenade = input {
+            if self.cooking.is_none() && !self.world.player.health.is_dead() {
+                self.cooking = self.world.player.wep.utilities.pull_pin(ctx, &mut s.mplayer).unwrap();
+            }
+        }
+    }
     fn event_up(&mut self, s: &mut State, ctx: &mut Context, input: Input) {
         match input {
+            Input::QuickSave => match self.save_game(s).map(|save| save.save(QUICKSAVE_SLOT)) {
+                Some(Ok(())) => {
+                    self.message_text.update(0, "Game saved").unwrap();
+                    self.message_time = MESSAGE_TIME;
+                }
+                Some(Err(e)) => error!("Couldn't save game: {}", e),
+                None => (),
+            },
+            Input::QuickLoad => match SaveGame::load(QUICKSAVE_SLOT) {
+                Ok(save) => s.switch(StateSwitch::Load(Box::new(save))),
+                Err(e) => error!("Couldn't load quicksave: {}", e),
+            },
             Input::WeaponLast => self.world.player.wep.switch(self.world.player.wep.last_active),
             Input::Weapon1 => self.world.player.wep.switch(ActiveSlot::Knife),
             Input::Weapon2 => self.world.player.wep.switch(ActiveSlot::Holster),
@@ -473,17 +1173,24 @@ impl GameState for Play {
                     wep.reload(ctx, &mut s.mplayer).unwrap()
                 } else {
                     let weapon = &weapon::WEAPONS["glock"];
-                    self.world.bullets.push(Bullet{obj: self.world.player.obj.clone(), vel: vector!(weapon.bullet_speed, 0.), weapon});
+                    self.world.bullets.push(Bullet{obj: self.world.player.obj.clone(), vel: vector!(weapon.bullet_speed, 0.), weapon, from_player: false});
                 }
             },
             Input::PickupWeapon => {
                 if let Some(i) = self.cur_pickup {
+                    self.script_events.push(ScriptEvent::Pickup(self.world.weapons[i].weapon.id.to_owned()));
+                    self.weapons_found.push(self.world.weapons[i].weapon.id);
                     if let Some(new_drop) = self.world.player.wep.add_weapon(WeaponInstance::from_drop(self.world.weapons.remove(i))) {
                         self.world.weapons.push(new_drop.into_drop(self.world.player.obj.pos));
                     }
                     self.cur_pickup = None;
+                } else if let Some(i) = self.world.panel_in_reach() {
+                    self.world.alarm_panels[i].disabled = true;
+                    s.mplayer.play(ctx, "click_pistol").unwrap();
                 }
             },
+            Input::DragBody => self.world.toggle_drag(),
+            Input::ToggleVisibilityCones => self.vision_cones.toggle(),
             Input::Shoot => {
                 if let Some(wep) = self.world.player.wep.get_active_mut() {
                     if let Some(bm) = wep.shoot(ctx, &mut s.mplayer).unwrap() {
@@ -491,46 +1198,26 @@ impl GameState for Play {
                         let mut bul = Object::new(pos);
                         bul.rot = self.world.player.obj.rot;
 
-                        for bullet in bm.make(bul) {
+                        for mut bullet in bm.make(bul) {
+                            bullet.from_player = true;
+                            self.score.shots_fired += 1;
                             self.world.bullets.push(bullet);
                         }
+                        self.world.noise(pos, GUNSHOT_NOISE);
                     }
-                } else {
-                    // TODO do knives with bullets too
-                    let player = &mut self.world.player;
-                    let mut backstab = false;
-
-                    let killed_enemy = iterate_and_kill_one_mut(&mut self.world.enemies, |enemy| {
-                        let dist = player.obj.pos-enemy.pl.obj.pos;
-                        let dist_len = dist.norm();
-                        if dist_len < 44. {
-                            backstab = angle_to_vec(enemy.pl.obj.rot).dot(&dist) / dist_len < COS_45_D;
-
-                            self.world.decal_queue.push(new_blood(enemy.pl.obj.clone()));
-                            enemy.pl.health.weapon_damage(if backstab { 165. } else { 33. }, 0.92);
-
-                            // Kill enemy if dead
-                            enemy.pl.health.is_dead()
-                        } else { false }
-                    });
-                    if let Some(Enemy{pl: Player{wep, obj: Object{pos, ..}, ..}, ..}) = killed_enemy {
-                        s.mplayer.play(ctx, "death").unwrap();
-
-                        for wep in wep {
-                            self.world.weapons.push(wep.into_drop(pos));
-                        }
-                    }
-
-                    s.mplayer.play(ctx, if backstab {"shuk"} else {"hling"}).unwrap();
+                } else if self.world.player.wep.melee.start_swing() {
+                    s.mplayer.play(ctx, self.world.player.wep.melee.weapon.swing_snd).unwrap();
                 }
             }
+            Input::NextThrowable => self.world.player.wep.utilities.select_next(),
             Input::ThrowGrenade => {
-                if let Some(gm) = self.world.player.wep.utilities.throw_grenade(ctx, &mut s.mplayer).unwrap() {
-                    let pos = self.world.player.obj.pos + 20. * angle_to_vec(self.world.player.obj.rot);
-                    let mut gren = Object::new(pos);
-                    gren.rot = self.world.player.obj.rot;
+                if let Some(gm) = self.cooking.take() {
+                    s.mplayer.play(ctx, "throw").unwrap();
+                    let (gm, gren) = self.aim_throw(gm, s.mouse - s.offset);
 
-                    self.world.grenades.push(gm.make(gren));
+                    let mut grenade = gm.make(gren);
+                    grenade.from_player = true;
+                    self.world.grenades.push(grenade);
                 }
             }
             _ => (),
@@ -543,6 +1230,26 @@ impl GameState for Play {
     fn get_mut_world(&mut self) -> Option<&mut World> {
         Some(&mut self.world)
     }
+    fn save_game(&self, s: &State) -> Option<SaveGame> {
+        // Nothing to continue once the level is over, and survival is played in one go
+        if self.world.player.health.is_dead() || self.victory_time > 0. || self.survival.is_some() {
+            return None
+        }
+
+        Some(SaveGame {
+            content: s.content.to_save(),
+            level: self.level.clone(),
+            initial_health: self.initial.0,
+            initial_weapons: (&self.initial.1).into(),
+            world: (&self.uncooked_world()).into(),
+            time: self.time,
+            script: self.script.as_ref().map(Script::save_state),
+            checkpoint: self.checkpoint.as_deref().map(From::from),
+            weapons_found: self.weapons_found.iter().map(|&id| id.to_owned()).collect(),
+            score: self.score.clone(),
+            cheated: s.cheated,
+        })
+    }
 }
 
 #[derive(Debug)]
@@ -571,6 +1278,7 @@ impl Hud {
             .rectangle(DrawMode::fill(), Rect{x:137.,y:2.,h: 32., w: 32.}, Color{r: 0.5, g: 0.5, b: 0.5, a: 1.})?
             .rectangle(DrawMode::fill(), Rect{x:104.,y:35.,h: 32., w: 32.}, Color{r: 0.5, g: 0.5, b: 0.5, a: 1.})?
             .rectangle(DrawMode::fill(), Rect{x:137.,y:35.,h: 32., w: 32.}, Color{r: 0.5, g: 0.5, b: 0.5, a: 1.})?
+            .rectangle(DrawMode::fill(), Rect{x:170.,y:2.,h: 32., w: 32.}, Color{r: 0.5, g: 0.5, b: 0.5, a: 1.})?
             ;
 
         let hud_bar = Mesh::from_data(ctx, hud_bar_builder.build());
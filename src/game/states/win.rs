@@ -5,11 +5,13 @@ use topskud::{
         tex::PosText,
         btn::Button,
         ctrl::Input,
+        leaderboard::{Leaderboard, Entry, Ghost},
+        achievement::Event,
     },
-    obj::{health::Health, player::WepSlots},
+    obj::{health::Health, player::WepSlots, weapon::WeaponInstance},
 };
 use crate::game::{
-    State, Content, GameState, StateSwitch,
+    State, Content, GameState, StateSwitch, Mode,
 };
 use ggez::{
     Context, GameResult,
@@ -33,10 +35,18 @@ pub struct Win {
     time_text: PosText,
     enemies_text: PosText,
     health_text: PosText,
+    points_text: PosText,
+    score_text: PosText,
+    grade_text: PosText,
+    board_text: PosText,
+    achievements_text: PosText,
     buttons: WinButtons,
     health: Health,
     level: Level,
     weapon: WepSlots,
+    exit: usize,
+    points: u32,
+    weapons_left: Vec<WeaponInstance<'static>>,
 }
 
 impl Win {
@@ -45,10 +55,102 @@ impl Win {
         let w = s.width as f32;
 
         let level_complete = s.assets.text(point!(s.width as f32/ 2., 10.)).and_text("LEVEL COMPLETE").centered();
-        let time_text = s.assets.text(point!(4., 20.)).and_text(format!("Time: {:.1}s", stats.time as f32 * DELTA));
+        let time = stats.time as f32 * DELTA;
+        let time_text = s.assets.text(point!(4., 20.)).and_text(match stats.level.par_time {
+            Some(par) => format!("Time: {:.1}s (par {:.1}s)", time, par),
+            None => format!("Time: {:.1}s", time),
+        });
         let enemy_total = stats.level.enemies.len();
         let enemies_text = s.assets.text(point!(4., 36.)).and_text(format!("Enemies killed: {} / {}", enemy_total - stats.enemies_left, enemy_total));
         let health_text = s.assets.text(point!(4., 52.)).and_text(format!("Health left: {:02.0} / {:02.0}", stats.health_left.hp, stats.health_left.armour));
+        let breakdown = stats.breakdown();
+        let points = breakdown.total.max(0) as u32;
+        let points_text = s.assets.text(point!(4., 68.)).and_text(format!("Points: {}", points));
+
+        let score = &stats.score;
+        let mut lines = Vec::new();
+        lines.push(match score.accuracy() {
+            Some(accuracy) => format!("Shots hit: {} / {} ({:.0}%)", score.shots_hit, score.shots_fired, 100. * accuracy),
+            None => "Shots hit: none fired".to_owned(),
+        });
+        if !score.kills.is_empty() {
+            let kills: Vec<_> = score.kills.iter().map(|(weapon, n)| format!("{} {}", weapon, n)).collect();
+            lines.push(format!("Kills by weapon: {}", kills.join(", ")));
+        }
+        lines.push(format!("Backstabs: {}", score.backstabs));
+        lines.push(format!("Damage taken: {:.0}", score.damage_taken));
+        lines.push(format!("Detected: {}", if score.detected { "yes" } else { "no" }));
+        lines.push(String::new());
+        for (part, p) in &breakdown.parts {
+            lines.push(format!("{}: {:+}", part, p));
+        }
+        lines.push(format!("Score: {}", breakdown.total));
+        let score_text = s.assets.text(point!(4., 100.)).and_text(lines.join("\n"));
+        let grade_text = s.assets.text_sized(point!(3. * w / 4., 64.), 64.).and_text(format!("{}", breakdown.grade)).centered();
+
+        let board = match s.content.level_key() {
+            Some(key) => {
+                let mut board = Leaderboard::load(&key);
+                // Cheated runs are shown the board but don't make it onto it
+                if !s.cheated {
+                    let best_time = board.add(Entry {
+                        name: s.profile.name().to_owned(),
+                        time,
+                        score: breakdown.total,
+                        grade: breakdown.grade.to_string(),
+                    });
+                    if let Err(e) = board.save(&key) {
+                        error!("Couldn't save leaderboard: {}", e);
+                    }
+                    // The best run is kept so it can be raced in time attack
+                    if best_time {
+                        // A run without a recording can't be raced, so it mustn't leave a slower ghost behind either
+                        let saved = match &stats.recording {
+                            Some(recording) => recording.save(&key),
+                            None => Ghost::delete(&key),
+                        };
+                        if let Err(e) = saved {
+                            error!("Couldn't save ghost: {}", e);
+                        }
+                    }
+                }
+
+                let mut lines = vec!["Best scores".to_owned()];
+                lines.extend(board.best_scores().take(5).map(|e| format!("{}  {} ({})", e.name, e.score, e.grade)));
+                lines.push(String::new());
+                lines.push("Best times".to_owned());
+                lines.extend(board.best_times().take(5).map(|e| format!("{}  {:.1}s", e.name, e.time)));
+                lines.join("\n")
+            }
+            None => String::new(),
+        };
+        let board_text = s.assets.text(point!(3. * w / 4., 140.)).and_text(board).centered();
+
+        let mut unlocked = Vec::new();
+        if !s.cheated {
+            unlocked.extend(s.profile.achieve(Event::LevelWon{damage_taken: stats.score.damage_taken}));
+            if let Content::Campaign(cam) = &s.content {
+                if cam.after_exit(stats.exit) >= cam.levels.len() {
+                    unlocked.extend(s.profile.achieve(Event::CampaignFinished));
+                }
+            }
+        }
+        let achievements: Vec<_> = unlocked.iter().map(|a| format!("Achievement unlocked: {}", a.name)).collect();
+        let achievements_text = s.assets.text(point!(w / 2., s.height - 24. * achievements.len() as f32 - 8.)).and_text(achievements.join("\n")).centered();
+
+        // Neither does a cheated run count as progress
+        if let (false, Content::Campaign(cam)) = (s.cheated, &s.content) {
+            if let Some(i) = cam.playing() {
+                s.profile.complete_level(&cam.name, i, stats.time as f32 * DELTA, stats.kills());
+                let next = cam.after_exit(stats.exit);
+                if next < cam.levels.len() {
+                    s.profile.reach_level(&cam.name, next);
+                }
+                if let Err(e) = s.profile.save() {
+                    error!("Couldn't save profile: {}", e);
+                }
+            }
+        }
 
         Ok(Box::new(Win {
             buttons: {
@@ -66,9 +168,17 @@ impl Win {
             time_text,
             enemies_text,
             health_text,
+            points_text,
+            score_text,
+            grade_text,
+            board_text,
+            achievements_text,
             level: stats.level,
             health: stats.health_left,
             weapon: stats.weapon,
+            exit: stats.exit,
+            points,
+            weapons_left: stats.weapons_left,
         }))
     }
     fn restart(&self, s: &mut State) {
@@ -78,19 +188,28 @@ impl Win {
         s.switch(StateSwitch::Editor(Some(self.level.clone())));
     }
     fn continue_play(&self, s: &mut State) {
-        let lvl;
-        match &mut s.content {
+        let switch = match &mut s.content {
+            // Other modes are for one level at a time
+            Content::Campaign(_) if s.mode != Mode::Normal => StateSwitch::LevelSelect,
             Content::Campaign(cam) => {
-                if let Some(l) = cam.next_level() {
-                    lvl = l;
+                cam.take_exit(self.exit);
+                cam.cheated |= s.cheated;
+                if cam.current < cam.levels.len() {
+                    StateSwitch::Intermission {
+                        health: self.health,
+                        wep: self.weapon.clone(),
+                        points: self.points,
+                        weapons_left: self.weapons_left.clone(),
+                    }
                 } else {
-                    return
+                    // Back to the menu once the campaign is over
+                    StateSwitch::Menu
                 }
             }
             Content::None | Content::File(_) => return,
-        }
+        };
 
-        s.switch(StateSwitch::PlayWith{health: self.health, wep: self.weapon.clone(), lvl: Box::new(lvl)});
+        s.switch(switch);
     }
 }
 
@@ -110,6 +229,11 @@ impl GameState for Win {
         self.time_text.draw_text(canvas);
         self.enemies_text.draw_text(canvas);
         self.health_text.draw_text(canvas);
+        self.points_text.draw_text(canvas);
+        self.score_text.draw_text(canvas);
+        self.grade_text.draw_text(canvas);
+        self.board_text.draw_text(canvas);
+        self.achievements_text.draw_text(canvas);
 
         Ok(())
     }
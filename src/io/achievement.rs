@@ -0,0 +1,111 @@
+//! Achievements, defined in `resources/achievements.toml` like
+//!
+//! ```toml
+//! [[achievements]]
+//! id = "first_blood"
+//! name = "First Blood"
+//! description = "Kill an enemy"
+//! requirement = { kills = 1 }
+//! ```
+
+use lazy_static::lazy_static;
+
+use std::fs;
+
+lazy_static! {
+    pub static ref ACHIEVEMENTS: Vec<Achievement> = {
+        #[derive(Deserialize)]
+        struct AchievementFile {
+            achievements: Vec<Achievement>,
+        }
+
+        fs::read_to_string("resources/achievements.toml")
+            .map_err(|e| e.to_string())
+            .and_then(|s| toml::from_str::<AchievementFile>(&s).map_err(|e| e.to_string()))
+            .map(|f| f.achievements)
+            .unwrap_or_else(|e| {
+                warn!("Couldn't load achievements: {}", e);
+                Vec::new()
+            })
+    };
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Requirement {
+    /// Kill this many enemies in total
+    Kills(u32),
+    /// Backstab this many enemies in total
+    Backstabs(u32),
+    /// Kill this many enemies with one grenade
+    GrenadeMultiKill(u32),
+    /// Win a level without taking any damage
+    Flawless,
+    /// Win the last level of a campaign
+    FinishCampaign,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Achievement {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub requirement: Requirement,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Something done in game that can unlock achievements
+pub enum Event {
+    Kill,
+    Backstab,
+    /// A grenade exploded killing this many enemies
+    GrenadeKills(u32),
+    LevelWon {
+        damage_taken: f32,
+    },
+    CampaignFinished,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// The player's achievements and what counts towards them
+pub struct AchievementProgress {
+    /// Ids of the unlocked achievements
+    pub unlocked: Vec<String>,
+    pub kills: u32,
+    pub backstabs: u32,
+}
+
+impl AchievementProgress {
+    #[inline]
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.unlocked.iter().any(|u| u == id)
+    }
+    fn met(&self, requirement: Requirement, event: Event) -> bool {
+        match (requirement, event) {
+            (Requirement::Kills(n), Event::Kill) => self.kills >= n,
+            (Requirement::Backstabs(n), Event::Backstab) => self.backstabs >= n,
+            (Requirement::GrenadeMultiKill(n), Event::GrenadeKills(kills)) => kills >= n,
+            (Requirement::Flawless, Event::LevelWon{damage_taken}) => damage_taken <= 0.,
+            (Requirement::FinishCampaign, Event::CampaignFinished) => true,
+            _ => false,
+        }
+    }
+    /// Counts an event and unlocks the achievements it completes
+    ///
+    /// ### Returns
+    /// The achievements that were unlocked
+    pub fn record(&mut self, event: Event) -> Vec<&'static Achievement> {
+        match event {
+            Event::Kill => self.kills += 1,
+            Event::Backstab => self.backstabs += 1,
+            _ => (),
+        }
+
+        let unlocked: Vec<_> = ACHIEVEMENTS.iter()
+            .filter(|a| !self.is_unlocked(&a.id) && self.met(a.requirement, event))
+            .collect();
+        self.unlocked.extend(unlocked.iter().map(|a| a.id.clone()));
+        unlocked
+    }
+}
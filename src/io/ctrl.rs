@@ -188,6 +188,7 @@ pub enum Input {
     // Gameplay
     Shoot,
     ThrowGrenade,
+    NextThrowable,
     Reload,
     WeaponLast,
     Weapon1,
@@ -196,6 +197,9 @@ pub enum Input {
     Weapon4,
     DropWeapon,
     PickupWeapon,
+    DragBody,
+    QuickSave,
+    QuickLoad,
 
     // Editor bindings
     SaveLevel,
@@ -209,6 +213,9 @@ pub enum Input {
     RotateRight,
     MakeWaypoints,
     ToggleCyclicPath,
+    ToggleFog,
+    CycleAmbient,
+    CycleDifficulty,
     DragUp,
     DragDown,
     DragLeft,
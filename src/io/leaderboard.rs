@@ -0,0 +1,262 @@
+//! Local high-score tables and ghost recordings of the best run of each level
+
+use ggez::{GameResult, error::GameError, graphics::{Canvas, Image, Color}};
+
+use crate::{
+    util::{Point2, Vector2},
+    obj::Object,
+    world::{Palette, Grid},
+};
+
+use std::{cmp::Reverse, fs::{self, File}, io::{BufReader, BufWriter, ErrorKind}, path::{Path, PathBuf}};
+
+/// Directory the level files are kept in
+const LEVELS_DIR: &str = "levels";
+
+/// Key of the level at `path`, made from its path relative to the levels directory
+/// so levels with the same name in different directories are kept apart
+pub fn level_key(path: &Path) -> String {
+    let full = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+    let relative = Path::new(LEVELS_DIR).canonicalize().ok()
+        .and_then(|root| full.strip_prefix(root).ok().map(Path::to_owned))
+        .unwrap_or(full);
+    relative.with_extension("").to_string_lossy().into_owned()
+}
+
+/// Makes a key safe to use as a file name
+fn file_name(key: &str) -> String {
+    key.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' }).collect()
+}
+
+/// How many of the best scores and best times are kept
+pub const MAX_ENTRIES: usize = 10;
+
+const GHOST_COLOUR: Color = Color{r: 1., g: 1., b: 1., a: 0.35};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// Name of the profile that made the run
+    pub name: String,
+    /// Time in seconds
+    pub time: f32,
+    pub score: i32,
+    pub grade: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Leaderboard {
+    entries: Vec<Entry>,
+}
+
+impl Leaderboard {
+    fn path(key: &str) -> PathBuf {
+        format!("scores/{}.toml", file_name(key)).into()
+    }
+    /// Loads the leaderboard of a level, starting an empty one if there isn't one
+    pub fn load(key: &str) -> Self {
+        match fs::read_to_string(Self::path(key)) {
+            Ok(s) => toml::from_str(&s).unwrap_or_else(|e| {
+                warn!("Couldn't read leaderboard: {}", e);
+                Leaderboard::default()
+            }),
+            Err(_) => Leaderboard::default(),
+        }
+    }
+    pub fn save(&self, key: &str) -> GameResult<()> {
+        let path = Self::path(key);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let s = toml::to_string(self).map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        fs::write(path, s)?;
+        Ok(())
+    }
+    pub fn best_time(&self) -> Option<f32> {
+        self.entries.iter().map(|e| e.time).min_by(|a, b| a.total_cmp(b))
+    }
+    /// The entries from highest to lowest score
+    pub fn best_scores(&self) -> impl Iterator<Item=&Entry> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|e| Reverse(e.score));
+        entries.into_iter().take(MAX_ENTRIES)
+    }
+    /// The entries from fastest to slowest
+    pub fn best_times(&self) -> impl Iterator<Item=&Entry> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by(|a, b| a.time.total_cmp(&b.time));
+        entries.into_iter().take(MAX_ENTRIES)
+    }
+    /// Adds a run, dropping any that are no longer among the best scores or times
+    ///
+    /// ### Returns
+    /// Whether the run is the new best time
+    pub fn add(&mut self, entry: Entry) -> bool {
+        let best_time = self.best_time().map(|t| entry.time < t).unwrap_or(true);
+        self.entries.push(entry);
+
+        let mut by_score: Vec<usize> = (0..self.entries.len()).collect();
+        by_score.sort_by_key(|&i| Reverse(self.entries[i].score));
+        let mut by_time: Vec<usize> = (0..self.entries.len()).collect();
+        by_time.sort_by(|&a, &b| self.entries[a].time.total_cmp(&self.entries[b].time));
+
+        let keep: Vec<usize> = by_score.iter().take(MAX_ENTRIES).chain(by_time.iter().take(MAX_ENTRIES)).copied().collect();
+        let mut i = 0;
+        self.entries.retain(|_| {
+            i += 1;
+            keep.contains(&(i - 1))
+        });
+
+        best_time
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// The player's movement input during one update
+pub struct GhostFrame {
+    pub vel: (f32, f32),
+    pub speed: f32,
+    pub rot: f32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// A recording of the player's input through a level
+pub struct Ghost {
+    pub frames: Vec<GhostFrame>,
+}
+
+impl Ghost {
+    fn path(key: &str) -> PathBuf {
+        format!("scores/{}.ghost", file_name(key)).into()
+    }
+    #[inline]
+    pub fn record(&mut self, vel: Vector2, speed: f32, rot: f32) {
+        self.frames.push(GhostFrame{vel: (vel.x, vel.y), speed, rot});
+    }
+    pub fn load(key: &str) -> GameResult<Self> {
+        bincode::deserialize_from(BufReader::new(File::open(Self::path(key))?))
+            .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))
+    }
+    pub fn save(&self, key: &str) -> GameResult<()> {
+        let path = Self::path(key);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        bincode::serialize_into(BufWriter::new(File::create(path)?), self)
+            .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))
+    }
+    /// Deletes the ghost of a level, if there is one
+    pub fn delete(key: &str) -> GameResult<()> {
+        match fs::remove_file(Self::path(key)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// A ghost being played back next to the player
+pub struct GhostReplay {
+    ghost: Ghost,
+    pub obj: Object,
+    tick: usize,
+}
+
+impl GhostReplay {
+    pub fn new(ghost: Ghost, start: Point2) -> Self {
+        GhostReplay {
+            ghost,
+            obj: Object::new(start),
+            tick: 0,
+        }
+    }
+    /// Moves the ghost the same way the player moved in the recorded run
+    pub fn update(&mut self, palette: &Palette, grid: &Grid) {
+        if let Some(&GhostFrame{vel: (x, y), speed, rot}) = self.ghost.frames.get(self.tick) {
+            self.obj.rot = rot;
+            self.obj.move_on_grid(vector!(x, y), speed, palette, grid);
+            self.tick += 1;
+        }
+    }
+    #[inline]
+    pub fn finished(&self) -> bool {
+        self.tick >= self.ghost.frames.len()
+    }
+    #[inline]
+    pub fn draw(&self, canvas: &mut Canvas, img: &Image) {
+        self.obj.draw(canvas, img, GHOST_COLOUR);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(time: f32, score: i32) -> Entry {
+        Entry {
+            name: "test".to_owned(),
+            time,
+            score,
+            grade: "C".to_owned(),
+        }
+    }
+
+    #[test]
+    fn add_tells_new_best_time() {
+        let mut board = Leaderboard::default();
+        assert!(board.add(entry(30., 100)));
+        assert!(!board.add(entry(40., 200)));
+        assert!(!board.add(entry(30., 300)));
+        assert!(board.add(entry(20., 0)));
+        assert_eq!(board.best_time(), Some(20.));
+    }
+
+    #[test]
+    fn best_scores_and_times_are_ordered() {
+        let mut board = Leaderboard::default();
+        board.add(entry(50., 10));
+        board.add(entry(20., 5));
+        board.add(entry(35., 30));
+
+        let scores: Vec<_> = board.best_scores().map(|e| e.score).collect();
+        assert_eq!(scores, [30, 10, 5]);
+        let times: Vec<_> = board.best_times().map(|e| e.time).collect();
+        assert_eq!(times, [20., 35., 50.]);
+    }
+
+    #[test]
+    fn add_keeps_best_scores_and_best_times() {
+        let mut board = Leaderboard::default();
+        // Slow runs with high scores
+        for i in 0..MAX_ENTRIES {
+            board.add(entry(100. + i as f32, 1000 + i as i32));
+        }
+        // Fast runs with low scores
+        for i in 0..MAX_ENTRIES {
+            board.add(entry(10. + i as f32, i as i32));
+        }
+        assert_eq!(board.entries.len(), 2 * MAX_ENTRIES);
+
+        // Neither the best score nor the best time
+        board.add(entry(50., 500));
+        assert_eq!(board.entries.len(), 2 * MAX_ENTRIES);
+        assert!(board.entries.iter().all(|e| e.score != 500));
+
+        // A best score pushes out the lowest of the high scores
+        board.add(entry(200., 2000));
+        assert_eq!(board.entries.len(), 2 * MAX_ENTRIES);
+        assert!(board.entries.iter().all(|e| e.score != 1000));
+        assert_eq!(board.best_scores().next().map(|e| e.score), Some(2000));
+
+        // A best time pushes out the slowest of the fast runs
+        board.add(entry(1., -1));
+        assert_eq!(board.entries.len(), 2 * MAX_ENTRIES);
+        assert!(board.entries.iter().all(|e| e.time != 10. + (MAX_ENTRIES - 1) as f32));
+        assert_eq!(board.best_time(), Some(1.));
+    }
+
+    #[test]
+    fn file_names_are_sanitised() {
+        assert_eq!(file_name("story/house 1"), "story_house_1");
+        assert_eq!(file_name("../secret"), ".._secret");
+    }
+}
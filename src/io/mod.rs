@@ -1,5 +1,8 @@
 pub mod tex;
 pub mod save;
+pub mod profile;
+pub mod leaderboard;
+pub mod achievement;
 pub mod snd;
 pub mod btn;
 pub mod ctrl;
\ No newline at end of file
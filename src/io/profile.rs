@@ -0,0 +1,220 @@
+//! The player's progress through the campaigns
+
+use ggez::{GameResult, error::GameError};
+
+use super::achievement::{Achievement, AchievementProgress, Event};
+use crate::world::Difficulty;
+
+use std::{collections::HashMap, fs};
+
+const PROFILE_PATH: &str = "profile.toml";
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct LevelRecord {
+    /// Whether the campaign has led the player to this level
+    #[serde(default)]
+    pub reached: bool,
+    pub completed: bool,
+    /// Best time in seconds
+    pub best_time: f32,
+    /// Most enemies killed in one go
+    pub kills: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CampaignProgress {
+    pub levels: Vec<LevelRecord>,
+    /// The level the campaign last led the player to
+    #[serde(default)]
+    pub last_reached: Option<usize>,
+}
+
+impl CampaignProgress {
+    #[inline]
+    pub fn completed(&self, level: usize) -> bool {
+        self.levels.get(level).map(|l| l.completed).unwrap_or(false)
+    }
+    /// Whether the level can be picked in the level select
+    ///
+    /// `followers` gives the levels following each level of the campaign, through any of its exits.
+    /// Levels following a completed one are unlocked too, for progress recorded before levels were marked as reached.
+    pub fn unlocked(&self, level: usize, followers: &[Vec<usize>]) -> bool {
+        level == 0
+            || self.levels.get(level).map(|l| l.reached || l.completed).unwrap_or(false)
+            || followers.iter().enumerate().any(|(i, next)| self.completed(i) && next.contains(&level))
+    }
+    /// The level to continue the campaign from, `None` if it has been finished
+    pub fn next_level(&self, followers: &[Vec<usize>]) -> Option<usize> {
+        match self.last_reached {
+            Some(level) if self.completed(level) => None,
+            Some(level) => Some(level),
+            None => (0..followers.len()).find(|&i| self.unlocked(i, followers) && !self.completed(i)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    /// Name put on the leaderboards
+    #[serde(default)]
+    pub name: String,
+    campaigns: HashMap<String, CampaignProgress>,
+    #[serde(default)]
+    pub achievements: AchievementProgress,
+    /// Most waves survived on each level
+    #[serde(default)]
+    survival: HashMap<String, u32>,
+    /// The difficulty last picked, used for the next game
+    #[serde(default)]
+    pub difficulty: Difficulty,
+}
+
+impl Profile {
+    /// Loads the profile, starting a new one if there isn't one
+    pub fn load() -> Self {
+        match fs::read_to_string(PROFILE_PATH) {
+            Ok(s) => toml::from_str(&s).unwrap_or_else(|e| {
+                warn!("Couldn't read profile: {}", e);
+                Profile::default()
+            }),
+            Err(_) => Profile::default(),
+        }
+    }
+    pub fn save(&self) -> GameResult<()> {
+        let s = toml::to_string(self).map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        fs::write(PROFILE_PATH, s)?;
+        Ok(())
+    }
+    /// The name to put on the leaderboards
+    pub fn name(&self) -> &str {
+        if self.name.is_empty() {
+            "Player"
+        } else {
+            &self.name
+        }
+    }
+    pub fn progress(&self, campaign: &str) -> Option<&CampaignProgress> {
+        self.campaigns.get(campaign)
+    }
+    /// Records an event towards achievements, saving the profile if any were unlocked
+    ///
+    /// ### Returns
+    /// The achievements that were unlocked
+    pub fn achieve(&mut self, event: Event) -> Vec<&'static Achievement> {
+        let unlocked = self.achievements.record(event);
+        if !unlocked.is_empty() {
+            for achievement in &unlocked {
+                info!("Achievement unlocked: {}", achievement.name);
+            }
+            if let Err(e) = self.save() {
+                error!("Couldn't save profile: {}", e);
+            }
+        }
+        unlocked
+    }
+    /// Records the waves survived on a level
+    ///
+    /// ### Returns
+    /// The most waves survived on the level
+    pub fn record_survival(&mut self, level: &str, waves: u32) -> u32 {
+        let best = self.survival.entry(level.to_owned()).or_insert(0);
+        *best = (*best).max(waves);
+        *best
+    }
+    fn record_mut(&mut self, campaign: &str, level: usize) -> &mut LevelRecord {
+        let levels = &mut self.campaigns.entry(campaign.to_owned()).or_default().levels;
+        if levels.len() <= level {
+            levels.resize(level + 1, LevelRecord::default());
+        }
+        &mut levels[level]
+    }
+    /// Records a level being won, keeping the best time and kills
+    pub fn complete_level(&mut self, campaign: &str, level: usize, time: f32, kills: usize) {
+        let record = self.record_mut(campaign, level);
+        if !record.completed || time < record.best_time {
+            record.best_time = time;
+        }
+        record.kills = record.kills.max(kills);
+        record.reached = true;
+        record.completed = true;
+    }
+    /// Records the campaign leading the player to a level
+    ///
+    /// The campaign is continued from there unless the level has been completed already,
+    /// so replaying earlier levels doesn't set the player back.
+    pub fn reach_level(&mut self, campaign: &str, level: usize) {
+        let record = self.record_mut(campaign, level);
+        record.reached = true;
+        if !record.completed {
+            self.campaigns.entry(campaign.to_owned()).or_default().last_reached = Some(level);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three levels in a row, the first branching to the third through its second exit
+    fn followers() -> Vec<Vec<usize>> {
+        vec![vec![1, 2], vec![2], vec![]]
+    }
+
+    #[test]
+    fn first_level_starts_unlocked() {
+        let progress = CampaignProgress::default();
+        assert!(progress.unlocked(0, &followers()));
+        assert!(!progress.unlocked(1, &followers()));
+        assert!(!progress.unlocked(2, &followers()));
+        assert_eq!(progress.next_level(&followers()), Some(0));
+    }
+
+    #[test]
+    fn reaching_a_level_unlocks_it() {
+        let mut profile = Profile::default();
+        profile.complete_level("test", 0, 10., 3);
+        profile.reach_level("test", 2);
+
+        let progress = profile.progress("test").unwrap();
+        assert!(progress.unlocked(2, &followers()));
+        assert_eq!(progress.next_level(&followers()), Some(2));
+    }
+
+    #[test]
+    fn completed_levels_unlock_their_followers() {
+        // Progress from before levels were marked as reached
+        let progress = CampaignProgress {
+            levels: vec![LevelRecord{completed: true, ..LevelRecord::default()}],
+            last_reached: None,
+        };
+        assert!(progress.unlocked(1, &followers()));
+        assert!(progress.unlocked(2, &followers()));
+        assert_eq!(progress.next_level(&followers()), Some(1));
+    }
+
+    #[test]
+    fn finished_campaign_has_no_next_level() {
+        let mut profile = Profile::default();
+        profile.complete_level("test", 0, 10., 3);
+        profile.reach_level("test", 2);
+        profile.complete_level("test", 2, 10., 3);
+
+        assert_eq!(profile.progress("test").unwrap().next_level(&followers()), None);
+    }
+
+    #[test]
+    fn replaying_keeps_the_furthest_level() {
+        let mut profile = Profile::default();
+        profile.complete_level("test", 0, 10., 3);
+        profile.reach_level("test", 1);
+        // Going back to the completed first level and winning it again
+        profile.complete_level("test", 0, 8., 1);
+        profile.reach_level("test", 1);
+
+        let progress = profile.progress("test").unwrap();
+        assert_eq!(progress.next_level(&followers()), Some(1));
+        let record = progress.levels[0];
+        assert_eq!(record.best_time, 8.);
+        assert_eq!(record.kills, 3);
+    }
+}
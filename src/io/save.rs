@@ -1,5 +1,27 @@
-use serde::{Serialize, Deserialize, Serializer, Deserializer};
-use crate::util::{Vector2, Point2};
+//! Serialisation helpers and save games
+
+use serde::{Serialize, Deserialize, Serializer, Deserializer, ser, de};
+use ggez::{GameResult, error::GameError};
+use crate::{
+    util::{Vector2, Point2, sstr},
+    obj::{
+        Object,
+        player::{Player, WepSlots, ActiveSlot},
+        enemy::{Enemy, Behaviour, Aim, Tactics},
+        health::Health,
+        bullet::Bullet,
+        grenade::{Grenade, GrenadeState, Utilities, Throwable},
+        weapon::{WeaponInstance, WeaponDrop, WEAPONS},
+        pickup::{Pickup, PICKUPS},
+        decal::Decal,
+        security::{Camera, AlarmPanel},
+        corpse::{Corpse, Blood},
+        light::Light,
+    },
+    world::{World, Level, Palette, Grid, Checkpoint, ObjectiveState, Trigger, ScriptState, Score, Difficulty},
+};
+
+use std::{fs::{self, File}, io::{BufReader, BufWriter}, path::PathBuf};
 
 /// Serialize a `Vector2`
 #[allow(dead_code)]
@@ -12,6 +34,15 @@ pub fn vec_ser<S: Serializer>(p: &Vector2, ser: S) -> Result<S::Ok, S::Error> {
 pub fn vec_des<'de, D: Deserializer<'de>>(des: D) -> Result<Vector2, D::Error> {
     <(f32, f32)>::deserialize(des).map(|(x, y)| vector!(x, y))
 }
+/// Serialize an `Option<Point2>`
+#[allow(clippy::trivially_copy_pass_by_ref)]
+pub fn opt_point_ser<S: Serializer>(p: &Option<Point2>, ser: S) -> Result<S::Ok, S::Error> {
+    p.map(|p| (p.x, p.y)).serialize(ser)
+}
+/// Deserialize an `Option<Point2>`
+pub fn opt_point_des<'de, D: Deserializer<'de>>(des: D) -> Result<Option<Point2>, D::Error> {
+    <Option<(f32, f32)>>::deserialize(des).map(|p| p.map(|(x, y)| point!(x, y)))
+}
 
 #[inline]
 #[allow(clippy::trivially_copy_pass_by_ref, dead_code)]
@@ -65,4 +96,438 @@ impl From<Point2DefVec> for Vec<Point2> {
 //     fn from(def: Vector2Def) -> Self {
 //         vector!(def.coords.0, def.coords.1)
 //     }
-// }
\ No newline at end of file
+// }
+
+/// Serialize a `Level` in the level file format
+pub fn level_ser<S: Serializer>(level: &Level, ser: S) -> Result<S::Ok, S::Error> {
+    let mut bytes = Vec::new();
+    level.write_to(&mut bytes).map_err(ser::Error::custom)?;
+    bytes.serialize(ser)
+}
+/// Deserialize a `Level` in the level file format
+pub fn level_des<'de, D: Deserializer<'de>>(des: D) -> Result<Level, D::Error> {
+    let bytes = <Vec<u8>>::deserialize(des)?;
+    Level::read_from(&*bytes).map_err(de::Error::custom)
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeaponSave {
+    id: String,
+    cur_clip: u16,
+    ammo: u16,
+    loading_time: f32,
+    jerk: f32,
+    jerk_decay: f32,
+    spray_index: usize,
+}
+
+impl From<&WeaponInstance<'_>> for WeaponSave {
+    fn from(wep: &WeaponInstance) -> Self {
+        WeaponSave {
+            id: wep.weapon.id.to_string(),
+            cur_clip: wep.cur_clip,
+            ammo: wep.ammo,
+            loading_time: wep.loading_time,
+            jerk: wep.jerk,
+            jerk_decay: wep.jerk_decay,
+            spray_index: wep.spray_index,
+        }
+    }
+}
+
+impl WeaponSave {
+    pub fn restore(self) -> Option<WeaponInstance<'static>> {
+        let weapon = WEAPONS.get(&*self.id);
+        if weapon.is_none() {
+            warn!("no weapon called {}", self.id);
+        }
+        weapon.map(|weapon| WeaponInstance {
+            cur_clip: self.cur_clip,
+            ammo: self.ammo,
+            loading_time: self.loading_time,
+            jerk: self.jerk,
+            jerk_decay: self.jerk_decay,
+            spray_index: self.spray_index,
+            weapon,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The weapons and utilities carried by someone
+pub struct WepSlotsSave {
+    active: ActiveSlot,
+    last_active: ActiveSlot,
+    utilities: Utilities,
+    holster: Option<WeaponSave>,
+    holster2: Option<WeaponSave>,
+    sling: Option<WeaponSave>,
+}
+
+impl From<&WepSlots> for WepSlotsSave {
+    fn from(wep: &WepSlots) -> Self {
+        WepSlotsSave {
+            active: wep.active,
+            last_active: wep.last_active,
+            utilities: wep.utilities.clone(),
+            holster: wep.holster.as_ref().map(From::from),
+            holster2: wep.holster2.as_ref().map(From::from),
+            sling: wep.sling.as_ref().map(From::from),
+        }
+    }
+}
+
+impl WepSlotsSave {
+    pub fn restore(self) -> WepSlots {
+        let mut wep = WepSlots {
+            active: self.active,
+            last_active: self.last_active,
+            utilities: self.utilities,
+            melee: Default::default(),
+            holster: self.holster.and_then(WeaponSave::restore),
+            holster2: self.holster2.and_then(WeaponSave::restore),
+            sling: self.sling.and_then(WeaponSave::restore),
+        };
+        if !wep.slot_has_weapon(wep.active) {
+            wep.init_active();
+        }
+        wep
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PlayerSave {
+    obj: Object,
+    health: Health,
+    wep: WepSlotsSave,
+}
+
+impl From<&Player> for PlayerSave {
+    fn from(pl: &Player) -> Self {
+        PlayerSave {
+            obj: pl.obj.clone(),
+            health: pl.health,
+            wep: (&pl.wep).into(),
+        }
+    }
+}
+
+impl PlayerSave {
+    fn restore(self) -> Player {
+        Player::new(self.obj).with_health(self.health).with_weapon(self.wep.restore())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EnemySave {
+    pl: PlayerSave,
+    behaviour: Behaviour,
+    cur_target: Option<(f32, f32)>,
+    id: usize,
+    aim: Aim,
+    tactics: Tactics,
+}
+
+impl From<&Enemy> for EnemySave {
+    fn from(enemy: &Enemy) -> Self {
+        EnemySave {
+            pl: (&enemy.pl).into(),
+            behaviour: enemy.behaviour.clone(),
+            cur_target: enemy.behaviour.cur_target.map(|p| (p.x, p.y)),
+            id: enemy.id,
+            aim: enemy.aim,
+            tactics: enemy.tactics,
+        }
+    }
+}
+
+impl EnemySave {
+    fn restore(self) -> Enemy {
+        let mut behaviour = self.behaviour;
+        behaviour.cur_target = self.cur_target.map(|(x, y)| point!(x, y));
+        Enemy {
+            pl: self.pl.restore(),
+            behaviour,
+            id: self.id,
+            aim: self.aim,
+            tactics: self.tactics,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BulletSave {
+    obj: Object,
+    #[serde(serialize_with = "vec_ser", deserialize_with = "vec_des")]
+    vel: Vector2,
+    weapon: String,
+    from_player: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GrenadeSave {
+    obj: Object,
+    #[serde(serialize_with = "vec_ser", deserialize_with = "vec_des")]
+    vel: Vector2,
+    state: GrenadeState,
+    from_player: bool,
+    throwable: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WeaponDropSave {
+    #[serde(with = "Point2Def")]
+    pos: Point2,
+    cur_clip: u16,
+    ammo: u16,
+    weapon: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TriggerSave {
+    trigger: Trigger,
+    fired: bool,
+    player_inside: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Everything in a world that has to be saved to continue playing it later
+pub struct WorldSave {
+    player: PlayerSave,
+    palette: Vec<String>,
+    grid: Grid,
+    /// Decals drawn so far and those waiting to be drawn
+    decals: Vec<Decal>,
+    exit: Option<(f32, f32)>,
+    intels: Vec<(f32, f32)>,
+    intels_collected: u16,
+    checkpoints: Vec<(f32, f32)>,
+    enemies: Vec<EnemySave>,
+    next_enemy_id: usize,
+    npcs: Vec<PlayerSave>,
+    objectives: Vec<ObjectiveState>,
+    triggers: Vec<TriggerSave>,
+    bullets: Vec<BulletSave>,
+    grenades: Vec<GrenadeSave>,
+    weapons: Vec<WeaponDropSave>,
+    pickups: Vec<((f32, f32), u8)>,
+    difficulty: Difficulty,
+    alarm: bool,
+    alarm_panels: Vec<AlarmPanel>,
+    cameras: Vec<Camera>,
+    corpses: Vec<Corpse>,
+    blood: Vec<Blood>,
+    fog: bool,
+    lights: Vec<Light>,
+    ambient: f32,
+}
+
+impl From<&World> for WorldSave {
+    fn from(world: &World) -> Self {
+        WorldSave {
+            player: (&world.player).into(),
+            palette: world.palette.materials().iter().map(|&m| m.to_owned()).collect(),
+            grid: world.grid.clone(),
+            decals: world.decals.iter().chain(&world.decal_queue).cloned().collect(),
+            exit: world.exit.map(|p| (p.x, p.y)),
+            intels: world.intels.iter().map(|p| (p.x, p.y)).collect(),
+            intels_collected: world.intels_collected,
+            checkpoints: world.checkpoints.iter().map(|p| (p.x, p.y)).collect(),
+            enemies: world.enemies.iter().map(From::from).collect(),
+            next_enemy_id: world.next_enemy_id,
+            npcs: world.npcs.iter().map(From::from).collect(),
+            objectives: world.objectives.clone(),
+            triggers: world.triggers.iter().map(|t| TriggerSave {
+                trigger: t.clone(),
+                fired: t.fired,
+                player_inside: t.player_inside,
+            }).collect(),
+            bullets: world.bullets.iter().map(|b| BulletSave {
+                obj: b.obj.clone(),
+                vel: b.vel,
+                weapon: b.weapon.id.to_string(),
+                from_player: b.from_player,
+            }).collect(),
+            // Explosions and flashes have already done their damage so aren't kept
+            grenades: world.grenades.iter().filter_map(|g| match g.state {
+                GrenadeState::Fused{..} | GrenadeState::Smoke{..} | GrenadeState::Burning{..} => Some(GrenadeSave {
+                    obj: g.obj.clone(),
+                    vel: g.vel,
+                    state: g.state.clone(),
+                    from_player: g.from_player,
+                    throwable: g.throwable.id.clone(),
+                }),
+                GrenadeState::Explosion{..} | GrenadeState::Flash{..} => None,
+            }).collect(),
+            weapons: world.weapons.iter().map(|w| WeaponDropSave {
+                pos: w.pos,
+                cur_clip: w.cur_clip,
+                ammo: w.ammo,
+                weapon: w.weapon.id.to_string(),
+            }).collect(),
+            pickups: world.pickups.iter().filter_map(|p| {
+                PICKUPS.iter()
+                    .position(|t| t.spr == p.pickup_type.spr)
+                    .map(|i| ((p.pos.x, p.pos.y), i as u8))
+            }).collect(),
+            difficulty: world.difficulty,
+            alarm: world.alarm,
+            alarm_panels: world.alarm_panels.clone(),
+            cameras: world.cameras.clone(),
+            corpses: world.corpses.clone(),
+            blood: world.blood.clone(),
+            fog: world.fog,
+            lights: world.lights.clone(),
+            ambient: world.ambient,
+        }
+    }
+}
+
+impl WorldSave {
+    pub fn restore(self) -> World {
+        World {
+            player: self.player.restore(),
+            palette: Palette::new(self.palette.into_iter().map(|m| sstr(m)).collect()),
+            grid: self.grid,
+            canvas: None,
+            decal_queue: self.decals,
+            decals: Vec::new(),
+            exit: self.exit.map(|(x, y)| point!(x, y)),
+            intels: self.intels.into_iter().map(|(x, y)| point!(x, y)).collect(),
+            intels_collected: self.intels_collected,
+            checkpoints: self.checkpoints.into_iter().map(|(x, y)| point!(x, y)).collect(),
+            enemies: self.enemies.into_iter().map(EnemySave::restore).collect(),
+            next_enemy_id: self.next_enemy_id,
+            npcs: self.npcs.into_iter().map(PlayerSave::restore).collect(),
+            objectives: self.objectives,
+            triggers: self.triggers.into_iter().map(|TriggerSave{mut trigger, fired, player_inside}| {
+                trigger.fired = fired;
+                trigger.player_inside = player_inside;
+                trigger
+            }).collect(),
+            bullets: self.bullets.into_iter().filter_map(|BulletSave{obj, vel, weapon, from_player}| {
+                WEAPONS.get(&*weapon).map(|weapon| Bullet{obj, vel, weapon, from_player})
+            }).collect(),
+            grenades: self.grenades.into_iter().filter_map(|GrenadeSave{obj, vel, state, from_player, throwable}| {
+                Throwable::get(&throwable).map(|throwable| Grenade{obj, vel, state, from_player, throwable})
+            }).collect(),
+            weapons: self.weapons.into_iter().filter_map(|WeaponDropSave{pos, cur_clip, ammo, weapon}| {
+                WEAPONS.get(&*weapon).map(|weapon| WeaponDrop{pos, cur_clip, ammo, weapon})
+            }).collect(),
+            pickups: self.pickups.into_iter().map(|((x, y), i)| Pickup::new(point!(x, y), i)).collect(),
+            difficulty: self.difficulty,
+            alarm: self.alarm,
+            alarm_panels: self.alarm_panels,
+            cameras: self.cameras,
+            corpses: self.corpses,
+            blood: self.blood,
+            dragging: None,
+            fog: self.fog,
+            lights: self.lights,
+            ambient: self.ambient,
+            light_map: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointSave {
+    world: WorldSave,
+    time: usize,
+    script: Option<ScriptState>,
+    score: Score,
+}
+
+impl From<&Checkpoint> for CheckpointSave {
+    fn from(checkpoint: &Checkpoint) -> Self {
+        CheckpointSave {
+            world: (&checkpoint.world).into(),
+            time: checkpoint.time,
+            script: checkpoint.script.clone(),
+            score: checkpoint.score.clone(),
+        }
+    }
+}
+
+impl CheckpointSave {
+    pub fn restore(self) -> Checkpoint {
+        Checkpoint {
+            world: self.world.restore(),
+            time: self.time,
+            script: self.script,
+            score: self.score,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What was being played when the game was saved
+pub enum ContentSave {
+    None,
+    File(PathBuf),
+    /// The campaign is loaded again from its file
+    Campaign {
+        path: PathBuf,
+        current: usize,
+        points: u32,
+        stash: Vec<WeaponSave>,
+    },
+}
+
+/// Number of save slots, slot 0 being the quicksave
+pub const SAVE_SLOTS: u8 = 10;
+pub const QUICKSAVE_SLOT: u8 = 0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The full state of a level being played
+pub struct SaveGame {
+    pub content: ContentSave,
+    #[serde(serialize_with = "level_ser", deserialize_with = "level_des")]
+    pub level: Level,
+    /// Health and weapons the player started the level with
+    pub initial_health: Health,
+    pub initial_weapons: WepSlotsSave,
+    pub world: WorldSave,
+    pub time: usize,
+    pub script: Option<ScriptState>,
+    pub checkpoint: Option<CheckpointSave>,
+    pub weapons_found: Vec<String>,
+    pub score: Score,
+    /// Whether cheats had been used, disabling achievements
+    pub cheated: bool,
+}
+
+impl SaveGame {
+    /// Path of the file of a save slot
+    pub fn path(slot: u8) -> PathBuf {
+        format!("saves/slot{}.sav", slot).into()
+    }
+    #[inline]
+    pub fn exists(slot: u8) -> bool {
+        Self::path(slot).is_file()
+    }
+    /// Name of a save slot to show the player
+    pub fn slot_name(slot: u8) -> String {
+        if slot == QUICKSAVE_SLOT {
+            "Quicksave".to_owned()
+        } else {
+            format!("Slot {}", slot)
+        }
+    }
+    pub fn save(&self, slot: u8) -> GameResult<()> {
+        let path = Self::path(slot);
+        info!("Saving game to {}", path.display());
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        bincode::serialize_into(BufWriter::new(File::create(path)?), self)
+            .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))
+    }
+    pub fn load(slot: u8) -> GameResult<Self> {
+        let path = Self::path(slot);
+        info!("Loading game from {}", path.display());
+
+        bincode::deserialize_from(BufReader::new(File::open(path)?))
+            .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))
+    }
+}
@@ -50,6 +50,10 @@ impl MediaPlayer {
         self.music_sources.insert(s, cache);
         Ok(())
     }
+    #[inline]
+    pub fn has_music(&self, s: &str) -> bool {
+        self.music_sources.contains_key(s)
+    }
     pub fn play(&mut self, ctx: &mut Context, s: &str) -> GameResult<()> {
         let snd;
 
@@ -13,11 +13,14 @@ pub struct Bullet<'a> {
     pub obj: Object,
     pub weapon: &'a Weapon,
     pub vel: Vector2,
+    /// Whether the player fired it, for keeping score
+    pub from_player: bool,
 }
 
 impl Bullet<'_> {
-    pub fn apply_damage(&self, health: &mut Health) {
-        let dmg = self.weapon.damage * self.vel.norm() / self.weapon.bullet_speed;
+    /// Damages `health`, multiplying the damage by `scale`
+    pub fn apply_damage(&self, health: &mut Health, scale: f32) {
+        let dmg = scale * self.weapon.damage * self.vel.norm() / self.weapon.bullet_speed;
 
         health.weapon_damage(dmg, self.weapon.penetration);
     }
@@ -26,7 +29,8 @@ impl Bullet<'_> {
         let img = a.get_img(self.weapon.get_bullet_spr());
         self.obj.draw(canvas, &*img, Color::WHITE)
     }
-    pub fn update(&mut self, palette: &Palette, grid: &Grid, player: &mut Player, enemies: &mut [Enemy]) -> Hit {
+    /// `damage_taken` is what the damage to the player is multiplied by, see `Difficulty`
+    pub fn update(&mut self, palette: &Palette, grid: &Grid, player: &mut Player, damage_taken: f32, enemies: &mut [Enemy], npcs: &mut [Player]) -> Hit {
         let start = self.obj.pos;
         let d_pos = self.vel * DELTA;
 
@@ -38,15 +42,21 @@ impl Bullet<'_> {
         
         // Check if we've hit a player or an enemy
         if Grid::dist_line_circle(start, d_pos, player.obj.pos) <= 16. {
-            self.apply_damage(&mut player.health);
+            self.apply_damage(&mut player.health, damage_taken);
             return Hit::Player;
         }
         for (i, enem) in enemies.iter_mut().enumerate() {
             if Grid::dist_line_circle(start, d_pos, enem.pl.obj.pos) <= 16. {
-                self.apply_damage(&mut enem.pl.health);
+                self.apply_damage(&mut enem.pl.health, 1.);
                 return Hit::Enemy(i);
             }
         }
+        for (i, npc) in npcs.iter_mut().enumerate().filter(|(_, npc)| !npc.health.is_dead()) {
+            if Grid::dist_line_circle(start, d_pos, npc.obj.pos) <= 16. {
+                self.apply_damage(&mut npc.health, 1.);
+                return Hit::Npc(i);
+            }
+        }
 
         // Decrease velocity after damage could've been dealt
         self.vel -= self.vel.normalize() * VELOCITY_DECREASE;
@@ -67,5 +77,6 @@ pub enum Hit {
     Wall,
     Player,
     Enemy(usize),
+    Npc(usize),
     None,
 }
\ No newline at end of file
@@ -0,0 +1,57 @@
+//! Bodies left behind by dead enemies and blood that hasn't dried yet
+
+use ggez::graphics::{Canvas, Color};
+
+use crate::{
+    util::Point2,
+    io::tex::Assets,
+};
+
+use super::Object;
+
+/// What enemy sprites are tinted with to look dead
+const CORPSE_TINT: Color = Color{r: 0.55, g: 0.35, b: 0.35, a: 1.};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The body of a dead enemy, which other enemies get suspicious about
+pub struct Corpse {
+    pub obj: Object,
+    /// Whether an enemy has already found the body where it is now
+    pub noticed: bool,
+}
+
+impl Corpse {
+    #[inline]
+    pub fn new(obj: Object) -> Self {
+        Corpse {
+            obj,
+            noticed: false,
+        }
+    }
+    #[inline]
+    pub fn draw(&self, canvas: &mut Canvas, a: &Assets) {
+        self.obj.draw(canvas, &a.get_img("common/enemy"), CORPSE_TINT);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// A spot of blood still fresh enough to give away that something happened
+pub struct Blood {
+    #[serde(with = "crate::io::save::Point2Def")]
+    pub pos: Point2,
+    /// Seconds since it was spilled
+    pub age: f32,
+    /// Whether an enemy has already found it
+    pub noticed: bool,
+}
+
+impl Blood {
+    #[inline]
+    pub fn new(pos: Point2) -> Self {
+        Blood {
+            pos,
+            age: 0.,
+            noticed: false,
+        }
+    }
+}
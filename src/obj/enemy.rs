@@ -1,8 +1,10 @@
 use crate::util::{BLUE, Vector2, Point2};
 
+use std::iter;
+
 use ggez::{
     Context, GameResult,
-    graphics::{Color, DrawMode, DrawParam, Canvas, Mesh},
+    graphics::{self, Color, DrawMode, DrawParam, Canvas, Mesh, MeshData, Rect},
 };
 use rand::{thread_rng, Rng};
 
@@ -13,10 +15,10 @@ use crate::{
         tex::{Assets, },
     },
     DELTA,
-    world::{Grid, Palette},
+    world::{Grid, Palette, Difficulty, GRENADE},
 };
 
-use super::{Object, player::Player};
+use super::{Object, player::{Player, ActiveSlot}, weapon::{WeaponDrop, WeaponInstance, WeaponSlot}};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Behaviour {
@@ -24,7 +26,7 @@ pub struct Behaviour {
     pub path: Vec<Point2>,
     pub cyclical_path: bool,
     #[serde(skip)]
-    cur_target: Option<Point2>,
+    pub(crate) cur_target: Option<Point2>,
 }
 
 impl Behaviour {
@@ -58,6 +60,128 @@ impl Default for Behaviour {
     }
 }
 
+/// How many times the aim error an enemy's aim starts off by
+const INITIAL_AIM_ERROR: f32 = 6.;
+/// How quickly an enemy's aim settles, per second
+const AIM_SETTLE: f32 = 2.;
+/// How much an enemy's aim wanders each update, as a fraction of the aim error
+const AIM_DRIFT: f32 = 0.45;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+/// An enemy getting ready to shoot at a target it can see
+pub struct Aim {
+    /// Seconds the enemy has been seeing its target
+    pub sighted: f32,
+    /// Seconds it takes this enemy to react
+    reaction: f32,
+    /// Radians the enemy's aim is off by
+    pub offset: f32,
+    /// Shots left of the current burst
+    burst: u8,
+    /// Seconds until the enemy can fire the next burst
+    pause: f32,
+}
+
+impl Aim {
+    /// Keeps aiming at a visible target
+    ///
+    /// ### Returns
+    /// Whether the enemy is ready to fire
+    pub fn update(&mut self, difficulty: Difficulty) -> bool {
+        let mut rng = thread_rng();
+        let error = difficulty.aim_error();
+
+        if self.sighted == 0. {
+            // Just spotted, so react a bit slower or faster than usual and start off badly aimed
+            self.reaction = difficulty.reaction_time() * rng.gen_range(0.75..=1.25);
+            self.offset = if rng.gen() { INITIAL_AIM_ERROR * error } else { -INITIAL_AIM_ERROR * error };
+        }
+        self.sighted += DELTA;
+        self.offset = self.offset * (1. - AIM_SETTLE * DELTA) + rng.gen_range(-1. ..= 1.) * AIM_DRIFT * error;
+        if self.pause > 0. {
+            self.pause -= DELTA;
+        }
+
+        self.sighted >= self.reaction && self.pause <= 0.
+    }
+    /// Counts a shot, pausing after a burst if the weapon is automatic
+    pub fn fired(&mut self, auto: bool) {
+        if !auto {
+            return
+        }
+        let mut rng = thread_rng();
+        if self.burst == 0 {
+            self.burst = rng.gen_range(3..=5);
+        }
+        self.burst -= 1;
+        if self.burst == 0 {
+            self.pause = rng.gen_range(0.4..=0.8);
+        }
+    }
+    /// Forgets about the target, the enemy has to react again when it next sees one
+    #[inline]
+    pub fn lose_sight(&mut self) {
+        *self = Aim::default();
+    }
+}
+
+/// Hit points under which an enemy pulls back to cover
+const RETREAT_HP: f32 = 35.;
+/// How many tiles away from itself an enemy looks for cover
+const COVER_SEARCH: i32 = 5;
+/// Seconds the player has to stay hidden before an enemy throws a grenade at them
+const GRENADE_WAIT: f32 = 1.5;
+/// Seconds between the grenades an enemy throws
+const GRENADE_COOLDOWN: f32 = 6.;
+const GRENADE_MIN_RANGE: f32 = 128.;
+const GRENADE_MAX_RANGE: f32 = 360.;
+/// Seconds without seeing the player before an enemy feels safe enough to go for weapons lying around
+const SCAVENGE_WAIT: f32 = 4.;
+/// How far away an enemy notices weapons lying around
+pub const SCAVENGE_RANGE: f32 = 192.;
+/// How close an enemy has to be to a weapon to pick it up
+pub const PICKUP_RANGE: f32 = 16.;
+/// How much better a weapon has to be for an enemy to swap for it
+const SWAP_MARGIN: f32 = 1.25;
+/// Fraction of the detection meter filled per second by someone right in front of an enemy
+const DETECTION_SPEED: f32 = 2.5;
+/// Fraction of the closest detection speed someone at the edge of an enemy's sight is spotted at
+const DETECTION_FAR: f32 = 0.15;
+/// How many times faster an enemy that knows the player is around spots them
+const DETECTION_ALERT: f32 = 3.;
+/// Fraction of the detection meter lost per second out of sight
+const DETECTION_DECAY: f32 = 0.4;
+
+/// A distance no one can see further than in the grid
+fn level_extent(grid: &Grid) -> f32 {
+    32. * (f32::from(grid.width()) + f32::from(grid.height()))
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+/// What an enemy knows about the player and what it's doing about it
+pub struct Tactics {
+    /// Where the player was last seen
+    #[serde(serialize_with = "crate::io::save::opt_point_ser", deserialize_with = "crate::io::save::opt_point_des")]
+    pub last_seen: Option<Point2>,
+    /// Seconds since the player was last seen
+    pub unseen: f32,
+    /// The cover the enemy is staying in
+    #[serde(serialize_with = "crate::io::save::opt_point_ser", deserialize_with = "crate::io::save::opt_point_des")]
+    pub cover: Option<Point2>,
+    /// The weapon lying around the enemy is going for
+    #[serde(serialize_with = "crate::io::save::opt_point_ser", deserialize_with = "crate::io::save::opt_point_des")]
+    pub scavenging: Option<Point2>,
+    /// The alarm panel the enemy is running to
+    #[serde(serialize_with = "crate::io::save::opt_point_ser", deserialize_with = "crate::io::save::opt_point_des")]
+    pub raising_alarm: Option<Point2>,
+    /// Seconds until the enemy can throw another grenade
+    grenade_cooldown: f32,
+    /// How close the enemy is to noticing the player, it sees them once this reaches 1
+    pub detection: f32,
+    /// Seconds until the enemy can see again after being flashed
+    pub blinded: f32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OldEnemy {
     pub pl: Player,
@@ -67,32 +191,74 @@ pub struct OldEnemy {
 
 impl From<OldEnemy> for Enemy {
     fn from(OldEnemy {pl, behaviour}: OldEnemy) -> Self {
-        Enemy {pl, behaviour }
+        Enemy {pl, behaviour, id: 0, aim: Aim::default(), tactics: Tactics::default() }
     }
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Enemy {
     pub pl: Player,
     pub behaviour: Behaviour,
+    /// Index of the enemy in the level it was loaded from
+    #[serde(skip)]
+    pub id: usize,
+    #[serde(skip)]
+    pub aim: Aim,
+    #[serde(skip)]
+    pub tactics: Tactics,
 }
 
 pub const VISIBILITY: f32 = ::std::f32::consts::FRAC_PI_4;
+/// How many rays make up a drawn field of vision
+const CONE_RAYS: u32 = 48;
+/// How see-through a drawn field of vision is
+const CONE_ALPHA: f32 = 0.2;
+
+/// Whether something at `obj` looking the way it's rotated can see a point no further than `range` away
+pub fn can_see_from(obj: &Object, p: Point2, range: f32, palette: &Palette, grid: &Grid) -> bool {
+    let dist = p-obj.pos;
+    let dir = angle_to_vec(obj.rot);
+
+    dist.norm() <= range && dir.angle(&dist) <= VISIBILITY && grid.ray_cast(palette, obj.pos, dist, true).full()
+}
 
 impl Enemy {
     pub fn new(obj: Object) -> Enemy {
         Enemy {
             pl: Player::new(obj),
             behaviour: Behaviour::default(),
+            id: 0,
+            aim: Aim::default(),
+            tactics: Tactics::default(),
         }
     }
-    /// Draws two lines from the enemy indicating the field of vision
-    pub fn draw_visibility_cone(&self, ctx: &mut Context, canvas: &mut Canvas, length: f32) -> GameResult<()> {
+    /// Fills in the field of vision up to `length` away, stopping at walls
+    ///
+    /// Blue if the enemy is unaware, yellow if suspicious and red if it sees the player
+    pub fn draw_visibility_cone(&self, ctx: &mut Context, canvas: &mut Canvas, length: f32, palette: &Palette, grid: &Grid) -> GameResult<()> {
         let Object{pos, rot} = self.pl.obj;
-        let dir1 = angle_to_vec(rot - VISIBILITY);
-        let dir2 = angle_to_vec(rot + VISIBILITY);
+        let color = if self.spotted().is_some() {
+            Color::RED
+        } else if !self.unaware() || self.tactics.detection > 0. {
+            Color::YELLOW
+        } else {
+            BLUE
+        };
+        let color = [color.r, color.g, color.b, CONE_ALPHA];
 
-        let mesh = Mesh::new_polyline(ctx, DrawMode::stroke(1.5), &[pos + (length * dir1), pos, pos + (length * dir2)], BLUE)?;
+        // Sight can be unlimited, but the cone can't go further than across the level
+        let length = length.min(level_extent(grid));
+        let sight = grid.visibility_polygon(palette, pos, length, rot - VISIBILITY, 2. * VISIBILITY, CONE_RAYS);
+        let vertices: Vec<_> = iter::once(pos).chain(sight).map(|p| graphics::Vertex {
+            position: p.into(),
+            uv: [0., 0.],
+            color,
+        }).collect();
+        let indices: Vec<u32> = (1..=CONE_RAYS).flat_map(|i| [0, i, i + 1]).collect();
 
+        let mesh = Mesh::from_data(ctx, MeshData {
+            vertices: &vertices,
+            indices: &indices,
+        });
         canvas.draw(&mesh, DrawParam::default());
         Ok(())
     }
@@ -103,18 +269,18 @@ impl Enemy {
     /// Look in the direction of a given vector
     /// ### Returns
     /// `true` if the enemy is now facing that direction
-    fn look_towards(&mut self, dist: Vector2) -> bool {
+    fn look_towards(&mut self, dist: Vector2, turn_speed: f32) -> bool {
         let dir = angle_to_vec(self.pl.obj.rot);
 
         let rotation = dir.angle(&dist);
 
-        const ROTATION: f32 = 6. * DELTA;
+        let step = turn_speed * DELTA;
 
-        if rotation > ROTATION {
+        if rotation > step {
             if dir.perp(&dist) > 0. {
-                self.pl.obj.rot += ROTATION;
+                self.pl.obj.rot += step;
             } else {
-                self.pl.obj.rot -= ROTATION;
+                self.pl.obj.rot -= step;
             }
             false
         } else {
@@ -122,10 +288,12 @@ impl Enemy {
             true
         }
     }
-    pub fn update<F: FnOnce() -> Point2>(&mut self, ctx: &mut Context, mplayer: &mut MediaPlayer, wander_finder: F) -> GameResult<()> {
+    pub fn update<F: FnOnce() -> Point2>(&mut self, ctx: &mut Context, mplayer: &mut MediaPlayer, difficulty: Difficulty, wander_finder: F) -> GameResult<()> {
         if let Some(wep) = self.pl.wep.get_active_mut() {
             wep.update(ctx, mplayer)?;
-            if wep.cur_clip == 0 && wep.loading_time == 0. {
+            // Top up during a lull in the fighting rather than running dry in the middle of it
+            let low = wep.cur_clip < wep.weapon.clip_size.get() / 2 && self.aim.sighted == 0.;
+            if (wep.cur_clip == 0 || low) && wep.loading_time == 0. {
                 wep.reload(ctx, mplayer)?;
             }
         }
@@ -151,7 +319,7 @@ impl Enemy {
         if let Some(t) = self.behaviour.cur_target {
             let dist = t - self.pl.obj.pos;
 
-            if self.look_towards(dist) {
+            if self.look_towards(dist, difficulty.turn_speed()) {
                 let distance = dist.norm();
                 const CHASE_SPEED: f32 = 100. * DELTA;
         
@@ -166,10 +334,206 @@ impl Enemy {
         }
         Ok(())
     }
-    pub fn can_see(&self, p: Point2, palette: &Palette, grid: &Grid) -> bool {
-        let dist = p-self.pl.obj.pos;
-        let dir = angle_to_vec(self.pl.obj.rot);
+    /// Whether the enemy is reloading or has to
+    pub fn reloading(&self) -> bool {
+        self.pl.wep.get_active()
+            .map(|wep| wep.cur_clip == 0 || wep.loading_time > wep.weapon.fire_rate)
+            .unwrap_or(false)
+    }
+    /// Looks for the closest tile nearby that the enemy can get to in a straight line and that hides it from `threat`
+    ///
+    /// When `away` is set, only tiles further from the threat are considered and those furthest away are preferred
+    pub fn find_cover(&self, threat: Point2, away: bool, palette: &Palette, grid: &Grid) -> Option<Point2> {
+        let pos = self.pl.obj.pos;
+        let (x, y) = Grid::snap(pos);
+        let threat_dist = (threat - pos).norm();
+
+        (-COVER_SEARCH..=COVER_SEARCH)
+            .flat_map(|dx| (-COVER_SEARCH..=COVER_SEARCH).map(move |dy| (x as i32 + dx, y as i32 + dy)))
+            .filter(|&(cx, cy)| cx >= 0 && cy >= 0 && !grid.is_solid(palette, cx as u16, cy as u16))
+            .map(|(cx, cy)| point!(cx as f32 * 32. + 16., cy as f32 * 32. + 16.))
+            .filter(|&c| !away || (threat - c).norm() > threat_dist)
+            .filter(|&c| !grid.ray_cast(palette, c, threat - c, true).full() && grid.ray_cast(palette, pos, c - pos, true).full())
+            .map(|c| {
+                let cost = if away { (c - pos).norm() - (threat - c).norm() } else { (c - pos).norm() };
+                (c, cost)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(c, _)| c)
+    }
+    /// Takes cover when reloading or badly hurt and decides when to throw a grenade
+    ///
+    /// `player` is where the player is if the enemy can see them.
+    /// Whilst the enemy is in cover it shouldn't be sent chasing after targets.
+    ///
+    /// ### Returns
+    /// Where to throw a grenade at, if the enemy should throw one
+    pub fn plan(&mut self, player: Option<Point2>, palette: &Palette, grid: &Grid) -> Option<Point2> {
+        if let Some(p) = player {
+            self.tactics.last_seen = Some(p);
+            self.tactics.unseen = 0.;
+        } else {
+            self.tactics.unseen += DELTA;
+        }
+        if self.tactics.grenade_cooldown > 0. {
+            self.tactics.grenade_cooldown -= DELTA;
+        }
+        if self.tactics.blinded > 0. {
+            self.tactics.blinded -= DELTA;
+        }
+        let threat = self.tactics.last_seen?;
 
-        dir.angle(&dist) <= VISIBILITY && grid.ray_cast(palette, self.pl.obj.pos, dist, true).full()
+        let hurt = self.pl.health.hp < RETREAT_HP;
+        let reloading = self.reloading();
+        match self.tactics.cover {
+            // Come back out once reloaded, unless too hurt to
+            Some(_) if !reloading && !hurt => {
+                self.tactics.cover = None;
+                self.behaviour.chase_then_wander(threat);
+            }
+            Some(_) => (),
+            None if player.is_some() && (reloading || hurt) && self.tactics.raising_alarm.is_none() => {
+                if let Some(cover) = self.find_cover(threat, hurt, palette, grid) {
+                    self.tactics.cover = Some(cover);
+                    self.behaviour.path = Vec::new();
+                    self.behaviour.cyclical_path = false;
+                    self.behaviour.cur_target = Some(cover);
+                }
+            }
+            None => (),
+        }
+
+        let dist = (threat - self.pl.obj.pos).norm();
+        if player.is_none()
+            && self.tactics.unseen >= GRENADE_WAIT
+            && self.tactics.grenade_cooldown <= 0.
+            && self.pl.wep.utilities.count(GRENADE) > 0
+            && (GRENADE_MIN_RANGE..=GRENADE_MAX_RANGE).contains(&dist)
+            && grid.ray_cast(palette, self.pl.obj.pos, threat - self.pl.obj.pos, true).full()
+        {
+            self.tactics.grenade_cooldown = GRENADE_COOLDOWN;
+            Some(threat)
+        } else {
+            None
+        }
+    }
+    /// Whether the enemy is hiding or raising the alarm and shouldn't be sent chasing after targets
+    #[inline]
+    pub fn busy(&self) -> bool {
+        self.tactics.cover.is_some() || self.tactics.raising_alarm.is_some()
+    }
+    /// Where the player is if the enemy has just seen them or been told by another enemy
+    #[inline]
+    pub fn spotted(&self) -> Option<Point2> {
+        self.tactics.last_seen.filter(|_| self.tactics.unseen == 0.)
+    }
+    /// Whether the enemy has no idea the player is around
+    #[inline]
+    pub fn unaware(&self) -> bool {
+        self.tactics.last_seen.is_none() && self.aim.sighted == 0.
+    }
+    /// Whether the enemy hasn't seen the player for a while and isn't busy
+    pub fn safe(&self) -> bool {
+        !self.busy() && (self.tactics.last_seen.is_none() || self.tactics.unseen >= SCAVENGE_WAIT)
+    }
+    /// Whether the enemy would want a weapon lying around, either for its ammo or because it's better
+    pub fn wants(&self, drop: &WeaponDrop) -> bool {
+        if drop.cur_clip == 0 && drop.ammo == 0 {
+            return false
+        }
+        let rounds = |wep: &WeaponInstance| wep.cur_clip + wep.ammo;
+        // Topping up the ammo of a weapon it already has
+        if let Some((_, wep)) = self.pl.wep.iter().find(|(_, wep)| wep.weapon.id == drop.weapon.id) {
+            return rounds(wep) < wep.weapon.max_rounds()
+        }
+        let slots: &[ActiveSlot] = match drop.weapon.slot {
+            WeaponSlot::Holster => &[ActiveSlot::Holster, ActiveSlot::Holster2],
+            WeaponSlot::Sling => &[ActiveSlot::Sling],
+        };
+        slots.iter().any(|&slot| match self.pl.wep.get(slot) {
+            None => true,
+            Some(wep) => rounds(wep) == 0 || drop.weapon.damage_rate() > SWAP_MARGIN * wep.weapon.damage_rate(),
+        })
+    }
+    /// Takes a weapon lying around, either for its ammo or swapping it for a worse one
+    ///
+    /// ### Returns
+    /// The weapon that was swapped out
+    pub fn pick_up(&mut self, drop: WeaponDrop<'static>) -> Option<WeaponDrop<'static>> {
+        let pos = self.pl.obj.pos;
+        let same = self.pl.wep.iter().find(|(_, wep)| wep.weapon.id == drop.weapon.id).map(|(slot, _)| slot);
+
+        let old = if let Some(wep) = same.and_then(|slot| self.pl.wep.get_mut(slot)) {
+            wep.ammo = wep.ammo.saturating_add(drop.cur_clip).saturating_add(drop.ammo);
+            None
+        } else {
+            // Make sure the worst holstered weapon is the one that gets replaced
+            if let WeaponSlot::Holster = drop.weapon.slot {
+                let worse = |slot| self.pl.wep.get(slot).map(|w| (w.cur_clip + w.ammo, w.weapon.damage_rate()));
+                self.pl.wep.active = match (worse(ActiveSlot::Holster), worse(ActiveSlot::Holster2)) {
+                    (Some(a), Some(b)) if a.0 == 0 || (b.0 > 0 && a.1 < b.1) => ActiveSlot::Holster,
+                    _ => ActiveSlot::Holster2,
+                };
+            }
+            self.pl.wep.add_weapon(WeaponInstance::from_drop(drop)).map(|old| old.into_drop(pos))
+        };
+        self.equip_best();
+        old
+    }
+    /// Switches to the best weapon that still has ammo, falling back to the knife
+    pub fn equip_best(&mut self) {
+        let best = self.pl.wep.iter()
+            .filter(|(_, wep)| wep.cur_clip + wep.ammo > 0)
+            .max_by(|(_, a), (_, b)| a.weapon.damage_rate().total_cmp(&b.weapon.damage_rate()))
+            .map(|(slot, _)| slot)
+            .unwrap_or(ActiveSlot::Knife);
+        if best != self.pl.wep.active {
+            self.pl.wep.switch(best);
+        }
+    }
+    /// Whether the active weapon has run out of ammo, or there is none
+    pub fn out_of_ammo(&self) -> bool {
+        self.pl.wep.get_active().map(|wep| wep.cur_clip == 0 && wep.ammo == 0).unwrap_or(true)
+    }
+    /// Fills the detection meter whilst the player is in view, faster the closer they are and the more lit,
+    /// and slower if they're sneaking, unless something like smoke is in the way
+    ///
+    /// ### Returns
+    /// Whether the enemy sees the player
+    #[allow(clippy::too_many_arguments)]
+    pub fn detect(&mut self, p: Point2, sneaking: bool, obscured: bool, light: f32, range: f32, palette: &Palette, grid: &Grid) -> bool {
+        if !obscured && self.can_see(p, range, palette, grid) {
+            // The detection speed falls off over the whole sight range, or the level if the sight is unlimited
+            let closeness = (1. - (p - self.pl.obj.pos).norm() / range.min(level_extent(grid))).max(0.);
+            // Sneaking makes the player a lot harder to spot the further away they are
+            let closeness = if sneaking { closeness * closeness } else { closeness };
+            let alert = if self.tactics.last_seen.is_some() { DETECTION_ALERT } else { 1. };
+
+            let rate = DETECTION_SPEED * (DETECTION_FAR + (1. - DETECTION_FAR) * closeness) * light * alert;
+            self.tactics.detection = (self.tactics.detection + rate * DELTA).min(1.);
+        } else {
+            self.tactics.detection = (self.tactics.detection - DETECTION_DECAY * DELTA).max(0.);
+            return false
+        }
+        self.tactics.detection >= 1.
+    }
+    /// Draws how close the enemy is to spotting the player above it
+    pub fn draw_detection(&self, ctx: &mut Context, canvas: &mut Canvas) -> GameResult<()> {
+        let detection = self.tactics.detection;
+        if detection <= 0. {
+            return Ok(())
+        }
+        let pos = self.pl.obj.pos;
+        let color = if detection >= 1. { Color::RED } else { Color::YELLOW };
+        let rect = Rect{x: pos.x - 12., y: pos.y - 28., w: 24. * detection, h: 4.};
+
+        let mesh = Mesh::new_rectangle(ctx, DrawMode::fill(), rect, color)?;
+        canvas.draw(&mesh, DrawParam::default());
+        Ok(())
+    }
+    /// Whether the enemy can see a point no further than `range` away
+    #[inline]
+    pub fn can_see(&self, p: Point2, range: f32, palette: &Palette, grid: &Grid) -> bool {
+        self.tactics.blinded <= 0. && can_see_from(&self.pl.obj, p, range, palette, grid)
     }
 }
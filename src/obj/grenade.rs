@@ -1,13 +1,26 @@
+//! Throwables, defined in `resources/weapons/utilities.toml` like
+//!
+//! ```toml
+//! [[throwables]]
+//! id = "flashbang"
+//! name = "Flashbang"
+//! sprite = "weapons/flashbang"
+//! fuse = 1.5
+//! radius = 320.0
+//! effect = { type = "flash", duration = 4.0 }
+//! ```
+
 use ggez::{Context, GameResult, graphics::{self, Color, Mesh, DrawParam, Canvas, MeshData}};
-use std::{iter, f32::consts::{PI, FRAC_PI_2 as HALF_PI}};
+use lazy_static::lazy_static;
+use std::{iter, fs, collections::BTreeMap, f32::consts::{PI, FRAC_PI_2 as HALF_PI}};
 use rand::{thread_rng, Rng};
 
 const PI_MUL_2: f32 = 2. * PI;
 
 use crate::{
-    util::{angle_to_vec, Vector2},
+    util::{angle_to_vec, Vector2, Point2},
     DELTA,
-    world::{Grid, Palette},
+    world::{Grid, Palette, GRENADE},
     io::{
         snd::MediaPlayer,
         tex::{Assets, },
@@ -15,9 +28,75 @@ use crate::{
 };
 use super::{Object, player::Player, enemy::Enemy, health::Health};
 
-#[derive(Debug, Default, Clone, Copy)]
+lazy_static! {
+    pub static ref THROWABLES: Vec<Throwable> = {
+        #[derive(Deserialize)]
+        struct UtilitiesFile {
+            throwables: Vec<Throwable>,
+        }
+
+        let file = fs::read_to_string("resources/weapons/utilities.toml").expect("utilities.toml file");
+        let throwables = toml::from_str::<UtilitiesFile>(&file).expect("well-defined throwables").throwables;
+        assert!(!throwables.is_empty(), "there has to be at least one throwable");
+        throwables
+    };
+}
+
+#[derive(Debug, Clone, Deserialize)]
+/// A kind of thing that can be thrown
+pub struct Throwable {
+    pub id: String,
+    pub name: String,
+    pub sprite: String,
+    /// Seconds from being thrown until it goes off
+    pub fuse: f32,
+    /// How far its effect reaches
+    pub radius: f32,
+    pub effect: Effect,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// What a throwable does when it goes off
+pub enum Effect {
+    /// Hurts everyone in the radius, more so within `lethal_radius`
+    Explosion {
+        damage: f32,
+        lethal_damage: f32,
+        lethal_radius: f32,
+        /// Fraction of armour damage rediverted to hp damage
+        penetration: f32,
+    },
+    /// Blinds everyone in the radius that it's in plain sight of
+    Flash {
+        duration: f32,
+    },
+    /// Leaves a cloud that can't be seen through
+    Smoke {
+        duration: f32,
+    },
+    /// Sets the floor alight, burning anyone on it
+    Fire {
+        duration: f32,
+        /// Damage per second
+        damage: f32,
+    },
+}
+
+impl Throwable {
+    /// The throwable with the given id
+    pub fn get(id: &str) -> Option<&'static Self> {
+        THROWABLES.iter().find(|t| t.id == id)
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+/// The throwables someone carries
 pub struct Utilities {
-    pub grenades: u8,
+    /// How many of each throwable is carried, by id
+    pub carried: BTreeMap<String, u8>,
+    /// Id of the throwable picked to be thrown next, the first one carried if it's not set
+    pub selected: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -25,36 +104,63 @@ pub struct Grenade {
     pub obj: Object,
     pub vel: Vector2,
     pub state: GrenadeState,
+    /// Whether the player threw it, for keeping score
+    pub from_player: bool,
+    pub throwable: &'static Throwable,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GrenadeState {
     Fused {
         fuse: f32,
     },
+    #[serde(skip)]
     Explosion {
         alive_time: f32,
         mesh: Mesh,
-    }
-
+    },
+    #[serde(skip)]
+    Flash {
+        alive_time: f32,
+    },
+    Smoke {
+        time_left: f32,
+        /// The cloud, made the first time it's updated
+        #[serde(skip)]
+        mesh: Option<Mesh>,
+    },
+    Burning {
+        time_left: f32,
+        /// Seconds since the fire last burnt anyone
+        tick: f32,
+        tiles: Vec<(u16, u16)>,
+    },
 }
 
 const EXPLOSION_LIFETIME: f32 = 0.5;
 const DEC: f32 = 1.4;
-
-const RANGE: f32 = 144.;
-const LETHAL_RANGE: f32 = 64.;
+/// Seconds between each time fire burns whoever is in it
+const BURN_TICK: f32 = 0.5;
+/// Fraction of armour damage from fire rediverted to hp damage
+const FIRE_PENETRATION: f32 = 0.9;
+/// Seconds smoke takes to clear up at the end
+const SMOKE_FADE: f32 = 2.;
 
 impl Grenade {
     #[inline]
-    pub fn apply_damage(health: &mut Health, high: bool) {
-        health.weapon_damage(if high { 105.} else {55.}, 0.85);
+    pub fn apply_damage(health: &mut Health, high: bool, scale: f32, effect: Effect) {
+        if let Effect::Explosion{damage, lethal_damage, penetration, ..} = effect {
+            health.weapon_damage(scale * if high { lethal_damage } else { damage }, penetration);
+        }
+    }
+    /// Whether the smoke from this grenade is in the way between two points
+    pub fn blocks_sight(&self, from: Point2, to: Point2) -> bool {
+        matches!(self.state, GrenadeState::Smoke{..}) && Grid::dist_line_circle(from, to - from, self.obj.pos) < self.throwable.radius
     }
-    #[inline]
     pub fn draw(&self, canvas: &mut Canvas, a: &Assets) {
         match &self.state {
             GrenadeState::Fused{..} => {
-                let img = a.get_img("weapons/pineapple");
+                let img = a.get_img(&self.throwable.sprite);
                 self.obj.draw(canvas, &*img, Color::WHITE);
             }
             GrenadeState::Explosion { mesh, alive_time } => {
@@ -73,12 +179,40 @@ impl Grenade {
 
                 canvas.draw_textured_mesh(mesh.clone(), image, dp);
             }
+            GrenadeState::Flash { alive_time } => {
+                let scale = 2. + 4. * alive_time / EXPLOSION_LIFETIME;
+                let dp = DrawParam::from(self.obj.pos)
+                    .offset(point!(0.5, 0.5))
+                    .scale(vector!(scale, scale))
+                    .color(Color::new(1., 1., 1., 1. - alive_time / EXPLOSION_LIFETIME));
+                canvas.draw(&*a.get_img("weapons/explosion"), dp);
+            }
+            GrenadeState::Smoke { time_left, mesh: Some(mesh) } => {
+                let alpha = 0.9 * (time_left / SMOKE_FADE).min(1.);
+                let image = (a.get_img("weapons/smoke")).clone();
+
+                canvas.draw_textured_mesh(mesh.clone(), image, DrawParam::from(self.obj.pos).color(Color::new(0.8, 0.8, 0.8, alpha)));
+            }
+            GrenadeState::Smoke { mesh: None, .. } => (),
+            GrenadeState::Burning { time_left, tiles, .. } => {
+                let img = a.get_img("weapons/explosion");
+                let mut rng = thread_rng();
+                let alpha = time_left.min(1.);
+                for &(x, y) in tiles {
+                    let flicker = rng.gen_range(0.6..=1.);
+                    let dp = DrawParam::from(point!(f32::from(x) * 32., f32::from(y) * 32.))
+                        .color(Color::new(1., 0.5 * flicker, 0.1, 0.7 * flicker * alpha));
+                    canvas.draw(&*img, dp);
+                }
+            }
         }
     }
+    /// A mesh of the area the grenade reaches, textured with a random rotation
     fn make_mesh(&self, ctx: &mut Context, palette: &Palette, grid: &Grid) -> Mesh {
         const NUM_VERTICES: u32 = 120;
         const RADIANS_PER_VERT: f32 = (360. / NUM_VERTICES as f32) * PI/180.;
 
+        let range = self.throwable.radius;
         let random_offset = thread_rng().gen_range(0. ..= PI_MUL_2);
 
         let centre = graphics::Vertex {
@@ -87,16 +221,16 @@ impl Grenade {
             color: [1.0, 1.0, 1.0, 1.0],
         };
         let vertices: Vec<_> = (0..NUM_VERTICES).map(|i| {
-            let angle = RANGE * angle_to_vec(i as f32 * RADIANS_PER_VERT);
+            let angle = range * angle_to_vec(i as f32 * RADIANS_PER_VERT);
             let angle_uv = 0.5 * angle_to_vec(i as f32 * RADIANS_PER_VERT + random_offset);
             let cast = grid.ray_cast(palette, self.obj.pos, angle, true);
             graphics::Vertex{
                 position: (cast.into_point() - self.obj.pos).into(),
-                uv: (vector!(0.5, 0.5) + (cast.clip().norm()-RANGE)/RANGE * angle_uv).into(),
+                uv: (vector!(0.5, 0.5) + (cast.clip().norm()-range)/range * angle_uv).into(),
                 color: [1.0, 1.0, 1.0, 1.0],
             }
         }).chain(iter::once(centre)).collect();
-        
+
         let indices = (0..NUM_VERTICES).flat_map(|i| iter::once(NUM_VERTICES).chain(iter::once(i)).chain(iter::once((i + 1) % NUM_VERTICES))).collect::<Vec<_>>();
         let raw = MeshData {
             vertices: &vertices,
@@ -105,36 +239,29 @@ impl Grenade {
 
         Mesh::from_data(ctx, raw)
     }
-    pub fn update_fused(obj: &mut Object, vel: &mut Vector2, fuse: &mut f32, palette: &Palette, grid: &Grid, player: &mut Player, enemies: &mut [Enemy]) -> GrenadeUpdate {
+    /// How far away a point is if it's in reach of the grenade going off
+    fn reach(&self, p: Point2, palette: &Palette, grid: &Grid) -> Option<f32> {
+        let dist = p - self.obj.pos;
+        let dist_len = dist.norm();
+
+        if dist_len < self.throwable.radius && (dist_len == 0. || grid.ray_cast(palette, self.obj.pos, dist, true).full()) {
+            Some(dist_len)
+        } else {
+            None
+        }
+    }
+    /// Moves the grenade along, bouncing off walls and people
+    ///
+    /// ### Returns
+    /// `true` once the fuse has run out
+    pub fn update_fused(obj: &mut Object, vel: &mut Vector2, fuse: &mut f32, palette: &Palette, grid: &Grid, player: &Player, enemies: &[Enemy]) -> bool {
         let start = obj.pos;
-        let d_vel = -DEC * (*vel) * DELTA;
-        let d_pos = 0.5 * DELTA * d_vel + (*vel) * DELTA;
-        *vel += d_vel;
+        let d_pos = Self::slow_down(vel);
         if *fuse > DELTA {
             *fuse -= DELTA;
         } else {
             *fuse = 0.;
-
-            let player_hit;
-            let mut enemy_hits = Vec::new();
-
-            let d_player = player.obj.pos-start;
-            if d_player.norm() < RANGE && grid.ray_cast(palette, start, d_player, true).full() {
-                Self::apply_damage(&mut player.health, d_player.norm() <= LETHAL_RANGE);
-                player_hit = true;
-            } else {
-                player_hit = false;
-            }
-
-            for (i, enem) in enemies.iter_mut().enumerate().rev() {
-                let d_enemy = enem.pl.obj.pos - start;
-                if d_enemy.norm() < 144. && grid.ray_cast(palette, start, d_enemy, true).full() {
-                    Self::apply_damage(&mut enem.pl.health, d_enemy.norm() <= 64.);
-                    enemy_hits.push(i);
-                }
-            }
-
-            return GrenadeUpdate::Explosion{player_hit, enemy_hits};
+            return true;
         }
 
         let closest_p = Grid::closest_point_of_line_to_circle(start, d_pos, player.obj.pos);
@@ -144,9 +271,9 @@ impl Grenade {
             let clip = (start + d_pos) - closest_p;
 
             obj.pos = closest_p + clip -  2. * clip.dot(&r_player)/r_player.norm_squared()*r_player;
-            return GrenadeUpdate::None;
+            return false;
         }
-        for enem in enemies.iter_mut() {
+        for enem in enemies.iter() {
             let closest_e = Grid::closest_point_of_line_to_circle(start, d_pos, enem.pl.obj.pos);
             let r_enemy = enem.pl.obj.pos - closest_e;
             if r_enemy.norm() <= 16. {
@@ -154,22 +281,116 @@ impl Grenade {
                 let clip = (start + d_pos) - closest_p;
 
                 obj.pos = closest_e + clip - 2. * clip.dot(&r_enemy)/r_enemy.norm_squared()*r_enemy;
-                return GrenadeUpdate::None;
+                return false;
             }
         }
+        obj.pos = Self::bounce_off_walls(start, d_pos, vel, palette, grid);
+        false
+    }
+    /// Applies a frame of drag to `vel`
+    ///
+    /// ### Returns
+    /// How far the grenade moves this frame
+    fn slow_down(vel: &mut Vector2) -> Vector2 {
+        let d_vel = -DEC * (*vel) * DELTA;
+        let d_pos = 0.5 * DELTA * d_vel + (*vel) * DELTA;
+        *vel += d_vel;
+        d_pos
+    }
+    /// Moves from `start` by `d_pos`, reflecting the movement and `vel` off any wall in the way
+    ///
+    /// ### Returns
+    /// Where the grenade ends up
+    fn bounce_off_walls(start: Point2, d_pos: Vector2, vel: &mut Vector2, palette: &Palette, grid: &Grid) -> Point2 {
         let cast = grid.ray_cast(palette, start, d_pos, true);
-        obj.pos = cast.into_point();
+        let mut pos = cast.into_point();
         if let Some(to_wall) = cast.half_vec() {
             let clip = cast.clip();
-            obj.pos += clip -  2. * clip.dot(&to_wall)/to_wall.norm_squared() * to_wall;
+            pos += clip -  2. * clip.dot(&to_wall)/to_wall.norm_squared() * to_wall;
             *vel -= 2. * vel.dot(&to_wall)/to_wall.norm_squared() * to_wall;
         }
-        GrenadeUpdate::None
+        pos
+    }
+    /// Sets off the grenade's effect once its fuse has run out
+    #[allow(clippy::too_many_arguments)]
+    fn go_off(&mut self, ctx: &mut Context, palette: &Palette, grid: &Grid, player: &mut Player, damage_taken: f32, enemies: &mut [Enemy], npcs: &mut [Player]) -> GrenadeUpdate {
+        match self.throwable.effect {
+            effect @ Effect::Explosion{lethal_radius, ..} => {
+                let player_hit = if let Some(dist) = self.reach(player.obj.pos, palette, grid) {
+                    Self::apply_damage(&mut player.health, dist <= lethal_radius, damage_taken, effect);
+                    true
+                } else {
+                    false
+                };
+
+                let mut enemy_hits = Vec::new();
+                for (i, enem) in enemies.iter_mut().enumerate().rev() {
+                    if let Some(dist) = self.reach(enem.pl.obj.pos, palette, grid) {
+                        Self::apply_damage(&mut enem.pl.health, dist <= lethal_radius, 1., effect);
+                        enemy_hits.push(i);
+                    }
+                }
+                let mut npc_hits = Vec::new();
+                for (i, npc) in npcs.iter_mut().enumerate().filter(|(_, npc)| !npc.health.is_dead()) {
+                    if let Some(dist) = self.reach(npc.obj.pos, palette, grid) {
+                        Self::apply_damage(&mut npc.health, dist <= lethal_radius, 1., effect);
+                        npc_hits.push(i);
+                    }
+                }
+
+                self.state = GrenadeState::Explosion {
+                    alive_time: 0.,
+                    mesh: self.make_mesh(ctx, palette, grid)
+                };
+                GrenadeUpdate::Explosion{player_hit, enemy_hits, npc_hits}
+            }
+            Effect::Flash{duration} => {
+                for enem in enemies.iter_mut() {
+                    if self.reach(enem.pl.obj.pos, palette, grid).is_some() {
+                        enem.tactics.blinded = duration;
+                    }
+                }
+                let player_blinded = if self.reach(player.obj.pos, palette, grid).is_some() { duration } else { 0. };
+
+                self.state = GrenadeState::Flash{alive_time: 0.};
+                GrenadeUpdate::Flash{player_blinded}
+            }
+            Effect::Smoke{duration} => {
+                self.state = GrenadeState::Smoke{time_left: duration, mesh: Some(self.make_mesh(ctx, palette, grid))};
+                GrenadeUpdate::Spread
+            }
+            Effect::Fire{duration, ..} => {
+                let radius = self.throwable.radius;
+                let tiles_out = (radius / 32.).ceil() as i16;
+                let (x, y) = Grid::snap(self.obj.pos);
+
+                let tiles = (-tiles_out..=tiles_out)
+                    .flat_map(|dy| (-tiles_out..=tiles_out).map(move |dx| (dx, dy)))
+                    .filter_map(|(dx, dy)| Some((x.checked_add_signed(dx)?, y.checked_add_signed(dy)?)))
+                    .filter(|&(x, y)| {
+                        let centre = point!(f32::from(x) * 32. + 16., f32::from(y) * 32. + 16.);
+                        !grid.is_solid(palette, x, y) && self.reach(centre, palette, grid).is_some()
+                    })
+                    .collect();
+
+                self.state = GrenadeState::Burning{time_left: duration, tick: 0., tiles};
+                GrenadeUpdate::Spread
+            }
+        }
     }
 
-    pub fn update(&mut self, ctx: &mut Context, palette: &Palette, grid: &Grid, player: &mut Player, enemies: &mut [Enemy]) -> GameResult<GrenadeUpdate> {
+    /// `damage_taken` is what the damage to the player is multiplied by, see `Difficulty`
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(&mut self, ctx: &mut Context, palette: &Palette, grid: &Grid, player: &mut Player, damage_taken: f32, enemies: &mut [Enemy], npcs: &mut [Player]) -> GameResult<GrenadeUpdate> {
+        // Smoke loaded from a save still needs its cloud
+        if let GrenadeState::Smoke{mesh: None, ..} = self.state {
+            let cloud = self.make_mesh(ctx, palette, grid);
+            if let GrenadeState::Smoke{ref mut mesh, ..} = self.state {
+                *mesh = Some(cloud);
+            }
+        }
         let update = match self.state {
-            GrenadeState::Explosion{ref mut alive_time, ..} => {
+            GrenadeState::Explosion{ref mut alive_time, ..} | GrenadeState::Flash{ref mut alive_time} => {
                 *alive_time += DELTA;
                 if *alive_time >= EXPLOSION_LIFETIME {
                     GrenadeUpdate::Dead
@@ -178,42 +399,184 @@ impl Grenade {
                 }
             }
             GrenadeState::Fused{ref mut fuse} => {
-                Self::update_fused(&mut self.obj, &mut self.vel, fuse, palette, grid, player, enemies)
+                if Self::update_fused(&mut self.obj, &mut self.vel, fuse, palette, grid, player, enemies) {
+                    self.go_off(ctx, palette, grid, player, damage_taken, enemies, npcs)
+                } else {
+                    GrenadeUpdate::None
+                }
+            }
+            GrenadeState::Smoke{ref mut time_left, ..} => {
+                *time_left -= DELTA;
+                if *time_left <= 0. { GrenadeUpdate::Dead } else { GrenadeUpdate::None }
+            }
+            GrenadeState::Burning{ref mut time_left, ref mut tick, ref tiles} => {
+                *time_left -= DELTA;
+                *tick += DELTA;
+                if *time_left <= 0. {
+                    GrenadeUpdate::Dead
+                } else if *tick >= BURN_TICK {
+                    *tick -= BURN_TICK;
+                    let damage = match self.throwable.effect {
+                        Effect::Fire{damage, ..} => damage * BURN_TICK,
+                        _ => 0.,
+                    };
+                    let on_fire = |p| tiles.contains(&Grid::snap(p));
+
+                    let player_hit = on_fire(player.obj.pos);
+                    if player_hit {
+                        player.health.weapon_damage(damage_taken * damage, FIRE_PENETRATION);
+                    }
+                    let mut enemy_hits = Vec::new();
+                    for (i, enem) in enemies.iter_mut().enumerate().rev().filter(|(_, e)| on_fire(e.pl.obj.pos)) {
+                        enem.pl.health.weapon_damage(damage, FIRE_PENETRATION);
+                        enemy_hits.push(i);
+                    }
+                    let mut npc_hits = Vec::new();
+                    for (i, npc) in npcs.iter_mut().enumerate().filter(|(_, npc)| !npc.health.is_dead() && on_fire(npc.obj.pos)) {
+                        npc.health.weapon_damage(damage, FIRE_PENETRATION);
+                        npc_hits.push(i);
+                    }
+
+                    if player_hit || !enemy_hits.is_empty() || !npc_hits.is_empty() {
+                        GrenadeUpdate::Burn{player_hit, enemy_hits, npc_hits}
+                    } else {
+                        GrenadeUpdate::None
+                    }
+                } else {
+                    GrenadeUpdate::None
+                }
             }
         };
-        if let GrenadeUpdate::Explosion{..} = update {
-            self.state = GrenadeState::Explosion {
-                alive_time: 0.,
-                mesh: self.make_mesh(ctx, palette, grid)
-            };
-        }
         Ok(update)
     }
 }
 
 impl Utilities {
-    pub fn throw_grenade(&mut self, ctx: &mut Context, mplayer: &mut MediaPlayer) -> GameResult<Option<GrenadeMaker>> {
-        if self.grenades > 0 {
-            self.grenades -= 1;
-
-            mplayer.play(ctx, "throw")?;
-            Ok(Some(GrenadeMaker(620.)))
+    /// How many of a throwable are carried
+    #[inline]
+    pub fn count(&self, id: &str) -> u8 {
+        self.carried.get(id).copied().unwrap_or(0)
+    }
+    #[inline]
+    pub fn set(&mut self, id: &str, count: u8) {
+        self.carried.insert(id.to_owned(), count);
+    }
+    /// Adds more of a throwable
+    ///
+    /// ### Returns
+    /// `false` if no more could be carried
+    pub fn add(&mut self, id: &str, count: u8) -> bool {
+        let carried = self.carried.entry(id.to_owned()).or_insert(0);
+        if *carried == u8::MAX {
+            false
         } else {
-            mplayer.play(ctx, "cock")?;
-            Ok(None)
+            *carried = carried.saturating_add(count);
+            true
+        }
+    }
+    /// The throwable thrown next, falling back to the first one carried if the one picked isn't
+    pub fn selected(&self) -> &'static Throwable {
+        self.selected.as_deref()
+            .and_then(Throwable::get)
+            .filter(|t| self.count(&t.id) > 0)
+            .or_else(|| THROWABLES.iter().find(|t| self.count(&t.id) > 0))
+            .unwrap_or(&THROWABLES[0])
+    }
+    /// Selects the next kind of throwable carried, staying on the current one if there are no others
+    pub fn select_next(&mut self) {
+        let len = THROWABLES.len();
+        let current = self.selected();
+        let i = THROWABLES.iter().position(|t| t.id == current.id).unwrap_or(0);
+        if let Some(next) = (1..len).map(|n| &THROWABLES[(i + n) % len]).find(|t| self.count(&t.id) > 0) {
+            self.selected = Some(next.id.clone());
+        }
+    }
+    /// Pulls the pin on the selected throwable, starting its fuse until it's thrown
+    pub fn pull_pin(&mut self, ctx: &mut Context, mplayer: &mut MediaPlayer) -> GameResult<Option<GrenadeMaker>> {
+        let gm = self.take(self.selected());
+        mplayer.play(ctx, if gm.is_some() { "cock2" } else { "cock" })?;
+        Ok(gm)
+    }
+    /// Throws a grenade straight away, whatever is selected
+    pub fn throw_grenade(&mut self, ctx: &mut Context, mplayer: &mut MediaPlayer) -> GameResult<Option<GrenadeMaker>> {
+        let gm = Throwable::get(GRENADE).and_then(|grenade| self.take(grenade));
+        mplayer.play(ctx, if gm.is_some() { "throw" } else { "cock" })?;
+        Ok(gm)
+    }
+    fn take(&mut self, throwable: &'static Throwable) -> Option<GrenadeMaker> {
+        let count = self.carried.get_mut(&throwable.id).filter(|count| **count > 0)?;
+        *count -= 1;
+        // Move on to whatever else is carried once the last one has been thrown
+        if *count == 0 && self.selected.as_deref() == Some(&*throwable.id) {
+            self.selected = None;
         }
+        Some(GrenadeMaker{speed: MAX_THROW_SPEED, fuse: throwable.fuse, throwable})
     }
 }
 
-pub struct GrenadeMaker(f32);
+/// Speed of a throw with all of one's might
+const MAX_THROW_SPEED: f32 = 620.;
+
+#[derive(Debug, Clone)]
+pub struct GrenadeMaker {
+    speed: f32,
+    /// Seconds left of the fuse, counting down already if the grenade is being cooked
+    fuse: f32,
+    pub throwable: &'static Throwable,
+}
 impl GrenadeMaker {
+    /// Throws the grenade softer so it goes off after rolling `distance`, if it doesn't hit anything
+    pub fn with_range(self, distance: f32) -> Self {
+        let speed = if self.fuse > 0. {
+            distance * DEC / (1. - (-DEC * self.fuse).exp())
+        } else {
+            0.
+        };
+        GrenadeMaker {
+            speed: speed.min(MAX_THROW_SPEED),
+            ..self
+        }
+    }
+    /// Lets the fuse burn for a frame while the grenade is held
+    ///
+    /// ### Returns
+    /// `true` if the fuse ran out in the hand
+    pub fn cook(&mut self) -> bool {
+        self.fuse = (self.fuse - DELTA).max(0.);
+        self.fuse <= 0.
+    }
+    #[inline]
+    pub fn fuse(&self) -> f32 {
+        self.fuse
+    }
+    /// Where the grenade would go thrown from `from` at the angle `rot`,
+    /// one point per frame until the fuse runs out
+    ///
+    /// Only walls are taken into account, anyone in the way is ignored.
+    pub fn predict_path(&self, from: Point2, rot: f32, palette: &Palette, grid: &Grid) -> Vec<Point2> {
+        let mut pos = from;
+        let mut vel = angle_to_vec(rot) * self.speed;
+        let mut fuse = self.fuse;
+
+        let mut path = vec![pos];
+        while fuse > DELTA {
+            fuse -= DELTA;
+            let d_pos = Grenade::slow_down(&mut vel);
+            pos = Grenade::bounce_off_walls(pos, d_pos, &mut vel, palette, grid);
+            path.push(pos);
+        }
+        path
+    }
     pub fn make(self, mut obj: Object) -> Grenade {
-        let vel = angle_to_vec(obj.rot) * self.0;
+        let GrenadeMaker{speed, fuse, throwable} = self;
+        let vel = angle_to_vec(obj.rot) * speed;
         obj.rot = 0.;
         Grenade {
-            state: GrenadeState::Fused{fuse: 1.5},
+            state: GrenadeState::Fused{fuse},
             vel,
             obj,
+            from_player: false,
+            throwable,
         }
     }
 }
@@ -223,7 +586,82 @@ pub enum GrenadeUpdate {
     Explosion {
         player_hit: bool,
         enemy_hits: Vec<usize>,
+        npc_hits: Vec<usize>,
+    },
+    /// A flashbang went off, blinding the player for `player_blinded` seconds
+    Flash {
+        player_blinded: f32,
+    },
+    /// Smoke started pouring out or the floor caught fire
+    Spread,
+    /// Whoever was standing in the fire got burnt
+    Burn {
+        player_hit: bool,
+        enemy_hits: Vec<usize>,
+        npc_hits: Vec<usize>,
     },
     Dead,
     None,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throwable(id: &str) -> &'static Throwable {
+        Throwable::get(id).unwrap()
+    }
+
+    #[test]
+    fn add_stops_at_the_most_carried() {
+        let mut utilities = Utilities::default();
+        assert!(utilities.add("smoke", 2));
+        assert_eq!(utilities.count("smoke"), 2);
+        assert!(utilities.add("smoke", u8::MAX));
+        assert_eq!(utilities.count("smoke"), u8::MAX);
+        assert!(!utilities.add("smoke", 1));
+        assert_eq!(utilities.count("flashbang"), 0);
+    }
+
+    #[test]
+    fn selected_falls_back_to_the_first_carried() {
+        let mut utilities = Utilities::default();
+        assert_eq!(utilities.selected().id, THROWABLES[0].id);
+
+        utilities.set("smoke", 1);
+        assert_eq!(utilities.selected().id, "smoke");
+        utilities.selected = Some("molotov".to_owned());
+        assert_eq!(utilities.selected().id, "smoke");
+        utilities.set("molotov", 1);
+        assert_eq!(utilities.selected().id, "molotov");
+    }
+
+    #[test]
+    fn select_next_skips_what_isnt_carried() {
+        let mut utilities = Utilities::default();
+        utilities.set("grenade", 1);
+        utilities.set("smoke", 1);
+
+        utilities.select_next();
+        assert_eq!(utilities.selected().id, "smoke");
+        utilities.select_next();
+        assert_eq!(utilities.selected().id, "grenade");
+
+        utilities.set("smoke", 0);
+        utilities.select_next();
+        assert_eq!(utilities.selected().id, "grenade");
+    }
+
+    #[test]
+    fn running_out_moves_on_to_the_next_carried() {
+        let mut utilities = Utilities::default();
+        utilities.set("grenade", 1);
+        utilities.set("flashbang", 1);
+        utilities.selected = Some("flashbang".to_owned());
+
+        assert!(utilities.take(throwable("flashbang")).is_some());
+        assert_eq!(utilities.selected, None);
+        assert_eq!(utilities.selected().id, "grenade");
+        assert!(utilities.take(throwable("flashbang")).is_none());
+    }
+}
@@ -0,0 +1,118 @@
+//! Light given off by decals like lamps, defined in `resources/decorations/lights.toml` like
+//!
+//! ```toml
+//! ["decorations/desk_lamp"]
+//! radius = 160.0
+//! color = [1.0, 0.85, 0.6]
+//! intensity = 0.8
+//! ```
+
+use ggez::{graphics::{self, Mesh, MeshData, GraphicsContext}, context::Has};
+use lazy_static::lazy_static;
+
+use std::{fs, iter, collections::HashMap};
+
+use crate::{
+    util::Point2,
+    world::{Grid, Palette},
+};
+
+use super::decal::Decal;
+
+lazy_static! {
+    pub static ref LIGHTS: HashMap<String, LightSpec> = {
+        fs::read_to_string("resources/decorations/lights.toml")
+            .map_err(|e| e.to_string())
+            .and_then(|s| toml::from_str(&s).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                warn!("Couldn't load lights: {}", e);
+                HashMap::new()
+            })
+    };
+}
+
+/// How many rays make up the area a light reaches
+const LIGHT_RAYS: u32 = 120;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// What kind of light a decal gives off
+pub struct LightSpec {
+    pub radius: f32,
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// A light shining out from a point until it reaches its radius or hits a wall
+pub struct Light {
+    #[serde(with = "crate::io::save::Point2Def")]
+    pub pos: Point2,
+    pub radius: f32,
+    pub color: [f32; 3],
+    /// How lit it is right by the light, from 0 to 1
+    pub intensity: f32,
+}
+
+impl Light {
+    #[inline]
+    pub fn new(pos: Point2, LightSpec{radius, color, intensity}: LightSpec) -> Self {
+        Light {
+            pos,
+            radius,
+            color,
+            intensity,
+        }
+    }
+    /// The light given off by a decal, if it gives off any
+    pub fn from_decal(decal: &Decal, palette: &Palette, grid: &Grid) -> Option<Self> {
+        LIGHTS.get(decal.spr).map(|&spec| Light::new(Self::source(decal.obj.pos, palette, grid), spec))
+    }
+    /// Where a light placed at a point shines from
+    ///
+    /// Lights mounted on walls are moved just out of the wall so they don't light up nothing
+    fn source(p: Point2, palette: &Palette, grid: &Grid) -> Point2 {
+        let (x, y) = Grid::snap(p);
+        if !grid.is_solid(palette, x, y) {
+            return p
+        }
+        (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .filter_map(|(dx, dy)| Some((x.checked_add_signed(dx)?, y.checked_add_signed(dy)?)))
+            .filter(|&(x, y)| !grid.is_solid(palette, x, y))
+            .map(|(x, y)| {
+                let (min_x, min_y) = (f32::from(x) * 32. + 1., f32::from(y) * 32. + 1.);
+                point!(p.x.clamp(min_x, min_x + 30.), p.y.clamp(min_y, min_y + 30.))
+            })
+            .min_by(|a, b| (a - p).norm().total_cmp(&(b - p).norm()))
+            .unwrap_or(p)
+    }
+    /// How lit a point is by this light alone
+    pub fn brightness_at(&self, p: Point2, palette: &Palette, grid: &Grid) -> f32 {
+        let dist = p - self.pos;
+        let dist_len = dist.norm();
+
+        if dist_len >= self.radius {
+            0.
+        } else if dist_len == 0. || grid.ray_cast(palette, self.pos, dist, true).full() {
+            self.intensity * (1. - dist_len / self.radius)
+        } else {
+            0.
+        }
+    }
+    /// The area the light reaches, fading out towards its radius
+    pub fn make_mesh(&self, gfx: &impl Has<GraphicsContext>, palette: &Palette, grid: &Grid) -> Mesh {
+        let [r, g, b] = self.color;
+
+        let lit = grid.visibility_polygon(palette, self.pos, self.radius, 0., 2. * std::f32::consts::PI, LIGHT_RAYS);
+        let vertices: Vec<_> = iter::once(self.pos).chain(lit).map(|p| graphics::Vertex {
+            position: p.into(),
+            uv: [0., 0.],
+            color: [r, g, b, self.intensity * (1. - (p - self.pos).norm() / self.radius)],
+        }).collect();
+        let indices: Vec<u32> = (1..=LIGHT_RAYS).flat_map(|i| [0, i, i + 1]).collect();
+
+        Mesh::from_data(gfx, MeshData {
+            vertices: &vertices,
+            indices: &indices,
+        })
+    }
+}
@@ -11,6 +11,9 @@ pub mod bullet;
 pub mod pickup;
 pub mod decal;
 pub mod grenade;
+pub mod security;
+pub mod corpse;
+pub mod light;
 
 use crate::world::{Grid, Palette};
 use crate::DELTA;
@@ -6,7 +6,7 @@ use ggez::graphics::{self, Canvas};
 
 use std::fmt::{self, Debug};
 
-use super::health::Health;
+use super::{health::Health, player::Player};
 
 #[derive(Debug, Clone)]
 pub struct Pickup {
@@ -22,10 +22,13 @@ impl Pickup {
             pickup_type: &PICKUPS[i as usize]
         }
     }
-    #[inline]
+    /// Gives the pickup's health or armour, multiplied by `amount`, or its throwable
     #[must_use]
-    pub fn apply(&self, health: &mut Health) -> bool {
-        (self.pickup_type.ability)(health)
+    pub fn apply(&self, pl: &mut Player, amount: f32) -> bool {
+        match self.pickup_type.ability {
+            Ability::Heal(heal) => heal(&mut pl.health, amount),
+            Ability::Throwable(id) => pl.wep.utilities.add(id, 1),
+        }
     }
     #[inline]
     pub fn draw(&self, canvas: &mut Canvas, assets: &Assets) {
@@ -36,7 +39,14 @@ impl Pickup {
 #[derive(Copy, Clone)]
 pub struct PickupType {
     pub spr: &'static str,
-    ability: fn(&mut Health) -> bool,
+    ability: Ability,
+}
+
+#[derive(Copy, Clone)]
+enum Ability {
+    Heal(fn(&mut Health, f32) -> bool),
+    /// Gives one of the throwable with this id
+    Throwable(&'static str),
 }
 
 impl PickupType {
@@ -58,77 +68,93 @@ impl Debug for PickupType {
     }
 }
 
-pub const PICKUPS: [PickupType; 6] = [
+pub const PICKUPS: [PickupType; 10] = [
     PickupType {
         spr: "pickups/health_pack",
-        ability: health_pack
+        ability: Ability::Heal(health_pack)
     },
     PickupType {
         spr: "pickups/armour",
-        ability: armour
+        ability: Ability::Heal(armour)
     },
     PickupType {
         spr: "pickups/adrenaline",
-        ability: adrenaline,
+        ability: Ability::Heal(adrenaline),
     },
     PickupType {
         spr: "pickups/super_armour",
-        ability: super_armour
+        ability: Ability::Heal(super_armour)
     },
     PickupType {
         spr: "pickups/plaster",
-        ability: plaster
+        ability: Ability::Heal(plaster)
     },
     PickupType {
         spr: "pickups/small_armour",
-        ability: small_armour
+        ability: Ability::Heal(small_armour)
+    },
+    PickupType {
+        spr: "pickups/grenade",
+        ability: Ability::Throwable("grenade")
+    },
+    PickupType {
+        spr: "pickups/flashbang",
+        ability: Ability::Throwable("flashbang")
+    },
+    PickupType {
+        spr: "pickups/smoke",
+        ability: Ability::Throwable("smoke")
+    },
+    PickupType {
+        spr: "pickups/molotov",
+        ability: Ability::Throwable("molotov")
     },
 ];
-fn health_pack(health: &mut Health) -> bool {
+fn health_pack(health: &mut Health, amount: f32) -> bool {
     if health.hp >= 100. {
         false
     } else {
-        health.hp = (health.hp + 75.).min(100.);
+        health.hp = (health.hp + 75. * amount).min(100.);
         true
     }
 }
-fn armour(health: &mut Health) -> bool {
+fn armour(health: &mut Health, amount: f32) -> bool {
     if health.armour >= 100. {
         false
     } else {
-        health.armour = (health.armour + 75.).min(100.);
+        health.armour = (health.armour + 75. * amount).min(100.);
         true
     }
 }
-fn adrenaline(health: &mut Health) -> bool {
+fn adrenaline(health: &mut Health, amount: f32) -> bool {
     if health.hp >= 200. {
         false
     } else {
-        health.hp += 125.;
+        health.hp += 125. * amount;
         true
     }
 }
-fn super_armour(health: &mut Health) -> bool {
+fn super_armour(health: &mut Health, amount: f32) -> bool {
     if health.armour >= 200. {
         false
     } else {
-        health.armour += 125.;
+        health.armour += 125. * amount;
         true
     }
 }
-fn plaster(health: &mut Health) -> bool {
+fn plaster(health: &mut Health, amount: f32) -> bool {
     if health.hp >= 100. {
         false
     } else {
-        health.hp = (health.hp + 10.).min(100.);
+        health.hp = (health.hp + 10. * amount).min(100.);
         true
     }
 }
-fn small_armour(health: &mut Health) -> bool {
+fn small_armour(health: &mut Health, amount: f32) -> bool {
     if health.armour >= 100. {
         false
     } else {
-        health.armour = (health.armour + 10.).min(100.);
+        health.armour = (health.armour + 10. * amount).min(100.);
         true
     }
 }
\ No newline at end of file
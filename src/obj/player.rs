@@ -10,7 +10,7 @@ use crate::{
     },
 };
 
-use super::{Object, health::Health, weapon::{Weapon, WeaponInstance, WeaponSlot}, grenade::Utilities};
+use super::{Object, health::Health, weapon::{Weapon, WeaponInstance, WeaponSlot, MeleeInstance}, grenade::Utilities};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Player {
@@ -22,7 +22,7 @@ pub struct Player {
 }
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ActiveSlot {
     Knife = 0,
     Holster = 1,
@@ -55,6 +55,8 @@ pub struct WepSlots {
     pub active: ActiveSlot,
     pub last_active: ActiveSlot,
     pub utilities: Utilities,
+    /// What's used when the knife slot is active
+    pub melee: MeleeInstance<'static>,
     pub holster: Option<WeaponInstance<'static>>,
     pub holster2: Option<WeaponInstance<'static>>,
     pub sling: Option<WeaponInstance<'static>>,
@@ -101,8 +103,8 @@ impl WepSlots {
         wep
     }
     #[inline(always)]
-    pub fn get_active(&self) -> Option<&WeaponInstance<'static>> {
-        match self.active {
+    pub fn get(&self, slot: ActiveSlot) -> Option<&WeaponInstance<'static>> {
+        match slot {
             ActiveSlot::Knife => None,
             ActiveSlot::Holster => self.holster.as_ref(),
             ActiveSlot::Holster2 => self.holster2.as_ref(),
@@ -110,14 +112,27 @@ impl WepSlots {
         }
     }
     #[inline(always)]
-    pub fn get_active_mut(&mut self) -> Option<&mut WeaponInstance<'static>> {
-        match self.active {
+    pub fn get_mut(&mut self, slot: ActiveSlot) -> Option<&mut WeaponInstance<'static>> {
+        match slot {
             ActiveSlot::Knife => None,
             ActiveSlot::Holster => self.holster.as_mut(),
             ActiveSlot::Holster2 => self.holster2.as_mut(),
             ActiveSlot::Sling => self.sling.as_mut(),
         }
     }
+    #[inline(always)]
+    pub fn get_active(&self) -> Option<&WeaponInstance<'static>> {
+        self.get(self.active)
+    }
+    #[inline(always)]
+    pub fn get_active_mut(&mut self) -> Option<&mut WeaponInstance<'static>> {
+        self.get_mut(self.active)
+    }
+    /// The slots holding a weapon and the weapons in them
+    pub fn iter(&self) -> impl Iterator<Item=(ActiveSlot, &WeaponInstance<'static>)> {
+        [ActiveSlot::Holster, ActiveSlot::Holster2, ActiveSlot::Sling].into_iter()
+            .filter_map(move |slot| self.get(slot).map(|wep| (slot, wep)))
+    }
     #[must_use]
     pub fn insert(&mut self, weapon: &Weapon) -> &mut Option<WeaponInstance<'static>> {
         match (weapon.slot, self) {
@@ -186,7 +201,7 @@ impl Player {
             let hands_sprite = if let Some(wep) = self.wep.get_active() {
                 wep.weapon.hands_sprite
             } else {
-                "weapons/knife_hands"
+                self.wep.melee.weapon.hands_sprite
             };
 
             let dp = self.obj.drawparams()
@@ -0,0 +1,83 @@
+//! Security cameras and alarm panels, which put the whole level on alert
+
+use ggez::graphics::{Canvas, Color, DrawParam};
+
+use crate::{
+    util::{Point2, RED},
+    io::tex::Assets,
+    world::{Grid, Palette},
+    DELTA,
+};
+
+use super::{Object, enemy::can_see_from};
+
+use std::f32::consts::{FRAC_PI_4, PI};
+
+/// How far cameras can see
+const CAMERA_RANGE: f32 = 400.;
+/// Seconds for a camera to sweep there and back
+const SWEEP_PERIOD: f32 = 6.;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A camera that sweeps back and forth, raising the alarm if it sees the player
+pub struct Camera {
+    /// Where the camera is and the direction it sweeps around
+    pub obj: Object,
+    /// Radians the camera turns to either side
+    pub sweep: f32,
+    #[serde(skip)]
+    time: f32,
+    /// Whether the camera can see the player
+    #[serde(skip)]
+    pub spotted: bool,
+}
+
+impl Camera {
+    pub fn new(obj: Object) -> Self {
+        Camera {
+            obj,
+            sweep: FRAC_PI_4,
+            time: 0.,
+            spotted: false,
+        }
+    }
+    #[inline]
+    pub fn update(&mut self) {
+        self.time += DELTA;
+    }
+    /// Where the camera is looking now
+    pub fn looking(&self) -> Object {
+        Object::with_rot(self.obj.pos, self.obj.rot + self.sweep * (2. * PI * self.time / SWEEP_PERIOD).sin())
+    }
+    #[inline]
+    pub fn can_see(&self, p: Point2, palette: &Palette, grid: &Grid) -> bool {
+        can_see_from(&self.looking(), p, CAMERA_RANGE, palette, grid)
+    }
+    #[inline]
+    pub fn draw(&self, canvas: &mut Canvas, a: &Assets, color: Color) {
+        let color = if self.spotted { RED } else { color };
+        self.looking().draw(canvas, &a.get_img("common/camera"), color);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// A panel enemies can raise the alarm with, unless the player has disabled it
+pub struct AlarmPanel {
+    #[serde(with = "crate::io::save::Point2Def")]
+    pub pos: Point2,
+    pub disabled: bool,
+}
+
+impl AlarmPanel {
+    #[inline]
+    pub fn new(pos: Point2) -> Self {
+        AlarmPanel {
+            pos,
+            disabled: false,
+        }
+    }
+    pub fn draw(&self, canvas: &mut Canvas, a: &Assets) {
+        let img = a.get_img(if self.disabled { "common/alarm_off" } else { "common/alarm" });
+        canvas.draw(&*img, DrawParam::default().dest(self.pos).offset(point!(0.5, 0.5)));
+    }
+}
@@ -72,8 +72,23 @@ pub struct Weapon {
 
 mod consts;
 pub use self::consts::*;
+mod melee;
+pub use self::melee::*;
 
 impl Weapon {
+    /// Rough damage per second, for telling which weapons are better
+    pub fn damage_rate(&self) -> f32 {
+        let bullets = match self.fire_mode {
+            FireMode::PumpAction{shell_load} => shell_load as f32,
+            _ => 1.,
+        };
+        bullets * self.damage / self.fire_rate.max(DELTA)
+    }
+    /// Rounds in a full clip and all the spare clips
+    #[inline]
+    pub fn max_rounds(&self) -> u16 {
+        self.clip_size.get() * (self.clips.get() + 1)
+    }
     pub fn make_instance(&self) -> WeaponInstance<'_> {
         let cur_clip = self.clip_size.get();
         WeaponInstance {
@@ -259,6 +274,7 @@ impl<'a> BulletMaker<'a> {
                 vel: weapon.bullet_speed * angle_to_vec(obj.rot),
                 obj,
                 weapon,
+                from_player: false,
             }
         })
     }
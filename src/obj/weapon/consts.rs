@@ -1,4 +1,4 @@
-use super::{FireMode, Weapon, WeaponSlot};
+use super::{FireMode, Weapon, WeaponSlot, MeleeWeapon};
 use crate::util::{sstr, add_sstr, Sstr};
 
 use lazy_static::lazy_static;
@@ -9,20 +9,66 @@ use std::num::NonZeroU16;
 use std::collections::HashMap;
 use std::f32::consts::PI;
 
+fn read_templates() -> HashMap<Box<str>, Template> {
+    let mut file = File::open("resources/weapons/specs.toml").expect("specs.toml file");
+    let mut file_contents = String::new();
+    file.read_to_string(&mut file_contents).expect("Reading to succeed");
+
+    toml::from_str(&file_contents).expect("well-defined weapons")
+}
+
 lazy_static!{
     pub static ref WEAPONS: HashMap<&'static str, Weapon> = {
-        let mut file = File::open("resources/weapons/specs.toml").expect("specs.toml file");
-        let mut file_contents = String::new();
-        file.read_to_string(&mut file_contents).expect("Reading to succeed");
-
-        let templates: HashMap<Box<str>, WeaponTemplate> = toml::from_str(&file_contents).expect("well-defined weapons");
-        templates.into_iter().map(|(k, v)| {
-            let k = sstr(k);
-            (k, v.build(k))
+        read_templates().into_iter().filter_map(|(k, v)| match v {
+            Template::Gun(v) => {
+                let k = sstr(k);
+                Some((k, v.build(k)))
+            }
+            Template::Melee(_) => None,
+        }).collect()
+    };
+    pub static ref MELEE_WEAPONS: HashMap<&'static str, MeleeWeapon> = {
+        read_templates().into_iter().filter_map(|(k, v)| match v {
+            Template::Melee(v) => {
+                let k = sstr(k);
+                Some((k, v.build(k)))
+            }
+            Template::Gun(_) => None,
         }).collect()
     };
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+/// Either kind of weapon, told apart by which fields it has
+pub enum Template {
+    Gun(WeaponTemplate),
+    Melee(MeleeTemplate),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MeleeTemplate {
+    #[serde(deserialize_with = "crate::util::deserialize_sstr")]
+    name: Sstr,
+    damage: f32,
+    penetration: f32,
+    reach: f32,
+    /// Degrees
+    arc: f32,
+    swing_time: f32,
+    cooldown: f32,
+    #[serde(default = "def_backstab")]
+    backstab: f32,
+    #[serde(default = "def_swing")]
+    #[serde(deserialize_with = "crate::util::deserialize_sstr")]
+    swing_snd: Sstr,
+    #[serde(default = "def_backstab_snd")]
+    #[serde(deserialize_with = "crate::util::deserialize_sstr")]
+    backstab_snd: Sstr,
+    #[serde(deserialize_with = "crate::util::deserialize_sstr")]
+    entity_sprite: Sstr,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct WeaponTemplate {
     #[serde(deserialize_with = "crate::util::deserialize_sstr")]
@@ -72,6 +118,18 @@ fn def_reload() -> Sstr {
 fn def_impact() -> Sstr {
     add_sstr("impact")
 }
+#[inline]
+const fn def_backstab() -> f32 {
+    1.
+}
+#[inline]
+fn def_swing() -> Sstr {
+    add_sstr("hling")
+}
+#[inline]
+fn def_backstab_snd() -> Sstr {
+    add_sstr("shuk")
+}
 
 const DEG2RAD: f32 = PI / 180.;
 
@@ -124,3 +182,37 @@ impl WeaponTemplate {
         }
     }
 }
+
+impl MeleeTemplate {
+    fn build(self, id: &'static str) -> MeleeWeapon {
+        let MeleeTemplate {
+            name,
+            damage,
+            penetration,
+            reach,
+            arc,
+            swing_time,
+            cooldown,
+            backstab,
+            swing_snd,
+            backstab_snd,
+            entity_sprite,
+        } = self;
+
+        MeleeWeapon {
+            id,
+            name,
+            damage,
+            penetration,
+            reach,
+            arc: arc * DEG2RAD,
+            swing_time,
+            cooldown,
+            backstab,
+            swing_snd,
+            backstab_snd,
+            hands_sprite: sstr(entity_sprite.to_string() + "_hands"),
+            entity_sprite,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{
+    util::{Point2, angle_to_vec, Sstr},
+    DELTA,
+};
+
+use super::{Object, MELEE_WEAPONS};
+
+use std::f32::consts::FRAC_1_SQRT_2 as COS_45_D;
+
+#[derive(Debug, Clone)]
+pub struct MeleeWeapon {
+    pub id: Sstr,
+    pub name: Sstr,
+    pub damage: f32,
+    /// Fraction of armour damage rediverted to hp damage
+    pub penetration: f32,
+    /// How far in front of the wielder a swing reaches
+    pub reach: f32,
+    /// Radians to either side of where the wielder is facing that a swing reaches
+    pub arc: f32,
+    /// Time from starting a swing until it lands
+    pub swing_time: f32,
+    /// Time after a swing lands before the next one can start
+    pub cooldown: f32,
+    /// What the damage is multiplied by when hitting someone from behind
+    pub backstab: f32,
+    pub swing_snd: Sstr,
+    pub backstab_snd: Sstr,
+    pub entity_sprite: Sstr,
+    pub hands_sprite: Sstr,
+}
+
+impl MeleeWeapon {
+    pub fn make_instance(&self) -> MeleeInstance<'_> {
+        MeleeInstance {
+            weapon: self,
+            swing: 0.,
+            cooldown: 0.,
+        }
+    }
+    /// Whether a swing from `from` would reach a point
+    pub fn in_reach(&self, from: &Object, p: Point2) -> bool {
+        let dist = p - from.pos;
+        let dist_len = dist.norm();
+
+        dist_len <= self.reach && (dist_len == 0. || angle_to_vec(from.rot).angle(&dist) <= self.arc)
+    }
+    /// Whether someone at `from` is behind `target`
+    pub fn behind(from: Point2, target: &Object) -> bool {
+        let dist = from - target.pos;
+        let dist_len = dist.norm();
+
+        dist_len > 0. && angle_to_vec(target.rot).dot(&dist) / dist_len < COS_45_D
+    }
+    /// The damage of a hit, with the multiplier if it's a backstab
+    #[inline]
+    pub fn damage(&self, backstab: bool) -> f32 {
+        if backstab { self.backstab * self.damage } else { self.damage }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct MeleeInstance<'a> {
+    /// Time until the current swing lands, 0 if not swinging
+    pub swing: f32,
+    /// Time until a new swing can start
+    pub cooldown: f32,
+    pub weapon: &'a MeleeWeapon,
+}
+
+impl Default for MeleeInstance<'static> {
+    fn default() -> Self {
+        MELEE_WEAPONS["knife"].make_instance()
+    }
+}
+
+impl MeleeInstance<'_> {
+    /// Starts a swing if the last one is done
+    ///
+    /// ### Returns
+    /// `true` if a swing was started
+    pub fn start_swing(&mut self) -> bool {
+        if self.swing > 0. || self.cooldown > 0. {
+            return false
+        }
+        self.swing = self.weapon.swing_time.max(DELTA);
+        self.cooldown = self.swing + self.weapon.cooldown;
+        true
+    }
+    /// ### Returns
+    /// `true` if a swing lands now
+    pub fn update(&mut self) -> bool {
+        if self.cooldown > 0. {
+            self.cooldown -= DELTA;
+        }
+        if self.swing > 0. {
+            self.swing -= DELTA;
+            self.swing <= 0.
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Enemies telling each other about the player and putting the whole level on alert
+
+use crate::util::Point2;
+
+use super::World;
+
+/// How far away enemies hear another one that has spotted the player
+const SHOUT_RANGE: f32 = 320.;
+/// How far an enemy that has spotted the player will run to raise the alarm
+const PANEL_RANGE: f32 = 256.;
+/// How close someone has to be to an alarm panel to use it
+pub const PANEL_REACH: f32 = 24.;
+
+impl World {
+    /// Sends every enemy that isn't busy searching for the player from where they were seen
+    ///
+    /// ### Returns
+    /// `true` if the alarm wasn't already raised
+    pub fn raise_alarm(&mut self, at: Point2) -> bool {
+        for enemy in self.enemies.iter_mut().filter(|e| !e.busy()) {
+            enemy.behaviour.chase_then_wander(at);
+        }
+        !std::mem::replace(&mut self.alarm, true)
+    }
+    /// Lets enemies that have spotted the player tell those nearby and run for alarm panels,
+    /// and lets cameras raise the alarm
+    ///
+    /// ### Returns
+    /// `true` if the alarm was raised
+    pub fn update_alarm(&mut self) -> bool {
+        let mut raised_at = None;
+
+        let player = self.player.obj.pos;
+        let World{ref mut cameras, ref palette, ref grid, ref grenades, ..} = *self;
+        for camera in cameras.iter_mut() {
+            camera.update();
+            let sees = camera.can_see(player, palette, grid)
+                && !grenades.iter().any(|g| g.blocks_sight(camera.obj.pos, player));
+            // Only alert the level when the player first comes into view
+            if sees && !camera.spotted {
+                raised_at = Some(player);
+            }
+            camera.spotted = sees;
+        }
+
+        let spotters: Vec<_> = self.enemies.iter()
+            .filter_map(|e| e.spotted().map(|p| (e.pl.obj.pos, p)))
+            .collect();
+        for enemy in self.enemies.iter_mut().filter(|e| e.spotted().is_none()) {
+            let heard = spotters.iter().find(|&&(from, _)| (from - enemy.pl.obj.pos).norm() <= SHOUT_RANGE);
+            if let Some(&(_, p)) = heard {
+                enemy.tactics.last_seen = Some(p);
+                enemy.tactics.unseen = 0.;
+                if !enemy.busy() {
+                    enemy.behaviour.chase_then_wander(p);
+                }
+            }
+        }
+
+        let World{ref mut enemies, ref alarm_panels, alarm, ..} = *self;
+        for enemy in enemies.iter_mut() {
+            let pos = enemy.pl.obj.pos;
+            match enemy.tactics.raising_alarm {
+                Some(panel) if (panel - pos).norm() <= PANEL_REACH => {
+                    enemy.tactics.raising_alarm = None;
+                    let working = alarm_panels.iter().any(|p| p.pos == panel && !p.disabled);
+                    if working && !alarm {
+                        raised_at = enemy.tactics.last_seen.or(raised_at);
+                    }
+                    if let Some(p) = enemy.tactics.last_seen {
+                        enemy.behaviour.chase_then_wander(p);
+                    }
+                }
+                // Stop if the player got there first
+                Some(panel) if alarm || alarm_panels.iter().any(|p| p.pos == panel && p.disabled) => {
+                    enemy.tactics.raising_alarm = None;
+                    if let Some(p) = enemy.tactics.last_seen {
+                        enemy.behaviour.chase_then_wander(p);
+                    }
+                }
+                Some(panel) => enemy.behaviour.cur_target = Some(panel),
+                None if !alarm && !enemy.busy() && enemy.spotted().is_some() => {
+                    let nearest = alarm_panels.iter()
+                        .filter(|p| !p.disabled && (p.pos - pos).norm() <= PANEL_RANGE)
+                        .min_by(|a, b| (a.pos - pos).norm().total_cmp(&(b.pos - pos).norm()));
+                    if let Some(panel) = nearest {
+                        enemy.tactics.raising_alarm = Some(panel.pos);
+                        enemy.behaviour.path = Vec::new();
+                        enemy.behaviour.cyclical_path = false;
+                        enemy.behaviour.cur_target = Some(panel.pos);
+                    }
+                }
+                None => (),
+            }
+        }
+
+        raised_at.map(|at| self.raise_alarm(at)).unwrap_or(false)
+    }
+    /// The alarm panel the player is close enough to disable
+    pub fn panel_in_reach(&self) -> Option<usize> {
+        let pos = self.player.obj.pos;
+        self.alarm_panels.iter().position(|p| !p.disabled && (p.pos - pos).norm() <= PANEL_REACH)
+    }
+}
@@ -0,0 +1,85 @@
+//! Dead bodies, the blood they leave and enemies getting suspicious about them
+
+use crate::{
+    DELTA,
+    util::angle_from_vec,
+    obj::{Object, corpse::Blood},
+};
+
+use super::World;
+
+/// Seconds before spilled blood has dried and stops giving anything away
+const BLOOD_FRESH: f32 = 30.;
+/// How far apart the blood left by a dragged body is
+const BLOOD_SPACING: f32 = 28.;
+/// How close the player has to be to a body to drag it
+pub const DRAG_REACH: f32 = 32.;
+/// How far behind the player a dragged body trails
+const DRAG_LENGTH: f32 = 24.;
+/// How fast the player moves whilst dragging a body
+pub const DRAG_SPEED: f32 = 90.;
+
+impl World {
+    /// The body the player is close enough to drag
+    pub fn corpse_in_reach(&self) -> Option<usize> {
+        let pos = self.player.obj.pos;
+        self.corpses.iter().position(|c| (c.obj.pos - pos).norm() <= DRAG_REACH)
+    }
+    /// Starts dragging a body in reach, or lets go of the one being dragged
+    pub fn toggle_drag(&mut self) {
+        self.dragging = match self.dragging {
+            Some(_) => None,
+            None => self.corpse_in_reach(),
+        };
+    }
+    /// Pulls the dragged body along behind the player
+    ///
+    /// ### Returns
+    /// Where to smear some blood, if the body has been dragged far enough since the last smear
+    pub fn drag_corpse(&mut self) -> Option<Object> {
+        let player = self.player.obj.pos;
+        let corpse = &mut self.corpses[self.dragging?];
+        // Wherever it ends up it will have to be found again
+        corpse.noticed = false;
+
+        let dist = corpse.obj.pos - player;
+        if dist.norm() > DRAG_LENGTH {
+            corpse.obj.pos = player + DRAG_LENGTH * dist.normalize();
+            corpse.obj.rot = angle_from_vec(-dist);
+        }
+        let pos = corpse.obj.pos;
+        if self.blood.last().map(|b| (b.pos - pos).norm() >= BLOOD_SPACING).unwrap_or(true) {
+            self.blood.push(Blood::new(pos));
+            Some(corpse.obj.clone())
+        } else {
+            None
+        }
+    }
+    /// Dries out old blood and sends enemies that see bodies or fresh blood to investigate
+    pub fn notice_bodies(&mut self) {
+        self.blood.retain_mut(|b| {
+            b.age += DELTA;
+            b.age < BLOOD_FRESH
+        });
+
+        let sight_range = self.difficulty.sight_range();
+        let World{ref mut enemies, ref mut corpses, ref mut blood, ref palette, ref grid, ..} = *self;
+        for enemy in enemies.iter_mut().filter(|e| !e.busy() && e.spotted().is_none()) {
+            let corpse = corpses.iter_mut()
+                .find(|c| !c.noticed && enemy.can_see(c.obj.pos, sight_range, palette, grid))
+                .map(|c| {
+                    c.noticed = true;
+                    c.obj.pos
+                });
+            let found = corpse.or_else(|| blood.iter_mut()
+                .find(|b| !b.noticed && enemy.can_see(b.pos, sight_range, palette, grid))
+                .map(|b| {
+                    b.noticed = true;
+                    b.pos
+                }));
+            if let Some(p) = found {
+                enemy.behaviour.chase_then_wander(p);
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! How hard the game is on the player
+
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+    /// What damage dealt to the player is multiplied by
+    pub fn damage_taken(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Normal => 1.,
+            Difficulty::Hard => 1.5,
+        }
+    }
+    /// Seconds an enemy has to see its target before it starts shooting
+    pub fn reaction_time(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.6,
+            Difficulty::Normal => 0.25,
+            Difficulty::Hard => 0.1,
+        }
+    }
+    /// Radians an enemy's aim is off by once it has settled on its target
+    pub fn aim_error(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.12,
+            Difficulty::Normal => 0.05,
+            Difficulty::Hard => 0.02,
+        }
+    }
+    /// How many grenades enemies start with
+    pub fn enemy_grenades(self) -> u8 {
+        match self {
+            Difficulty::Easy => 0,
+            Difficulty::Normal => 1,
+            Difficulty::Hard => 2,
+        }
+    }
+    /// How far enemies can see, only limited on easy
+    pub fn sight_range(self) -> f32 {
+        match self {
+            Difficulty::Easy => 450.,
+            Difficulty::Normal | Difficulty::Hard => f32::INFINITY,
+        }
+    }
+    /// Radians per second enemies turn at
+    pub fn turn_speed(self) -> f32 {
+        match self {
+            Difficulty::Easy => 4.,
+            Difficulty::Normal => 6.,
+            Difficulty::Hard => 9.,
+        }
+    }
+    /// What the health and armour the player gets from pickups is multiplied by
+    pub fn pickup_amount(self) -> f32 {
+        match self {
+            Difficulty::Easy => 1.5,
+            Difficulty::Normal => 1.,
+            Difficulty::Hard => 0.6,
+        }
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }.fmt(f)
+    }
+}
@@ -0,0 +1,53 @@
+//! Fog of war hiding whatever the player has no line of sight to
+
+use std::f32::consts::PI;
+
+use ggez::{Context, GameResult, graphics::{self, Canvas, Mesh, MeshData, DrawParam}};
+
+use crate::util::Point2;
+
+use super::World;
+
+/// How many rays make up the player's visibility polygon
+const FOG_RAYS: u32 = 360;
+/// How dark the fog is
+const FOG_COLOR: [f32; 4] = [0., 0., 0., 0.75];
+
+impl World {
+    /// Whether the player can see a point, always `true` without fog
+    pub fn player_sees(&self, p: Point2) -> bool {
+        let pos = self.player.obj.pos;
+        !self.fog || p == pos || self.grid.ray_cast(&self.palette, pos, p - pos, true).full()
+    }
+    /// Darkens everything outside of the player's line of sight
+    pub fn draw_fog(&self, ctx: &mut Context, canvas: &mut Canvas) -> GameResult<()> {
+        if !self.fog {
+            return Ok(())
+        }
+        let pos = self.player.obj.pos;
+        // Far enough to reach past every corner of the level from anywhere in it
+        let range = 32. * (f32::from(self.grid.width()) + f32::from(self.grid.height()));
+        let sight = self.grid.visibility_polygon(&self.palette, pos, range, 0., 2. * PI, FOG_RAYS);
+
+        // Each pair of neighbouring rays gets a quad from where they stopped out past the edge of the level
+        let vertices: Vec<_> = sight.iter().flat_map(|&p| {
+            let beyond = p + range * (p - pos).try_normalize(0.).unwrap_or_default();
+            [p, beyond]
+        }).map(|p| graphics::Vertex {
+            position: p.into(),
+            uv: [0., 0.],
+            color: FOG_COLOR,
+        }).collect();
+        let indices: Vec<u32> = (0..FOG_RAYS).flat_map(|i| {
+            let (near, far, next_near, next_far) = (2*i, 2*i + 1, 2*i + 2, 2*i + 3);
+            [near, far, next_far, near, next_far, next_near]
+        }).collect();
+
+        let mesh = Mesh::from_data(ctx, MeshData {
+            vertices: &vertices,
+            indices: &indices,
+        });
+        canvas.draw(&mesh, DrawParam::default());
+        Ok(())
+    }
+}
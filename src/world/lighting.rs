@@ -0,0 +1,53 @@
+//! Darkening the level everywhere its lights don't reach
+
+use ggez::{
+    GameResult,
+    graphics::{Canvas, Image, GraphicsContext, Color, DrawParam, BlendMode},
+    context::{Has, HasMut},
+};
+
+use crate::util::Point2;
+
+use super::World;
+
+impl World {
+    /// How lit a point is, from 0 in the dark to 1 in full light
+    pub fn light_at(&self, p: Point2) -> f32 {
+        let lit: f32 = self.lights.iter().map(|l| l.brightness_at(p, &self.palette, &self.grid)).sum();
+        (self.ambient + lit).min(1.)
+    }
+    /// Darkens everything drawn so far by how lit it is
+    pub fn draw_lighting(&mut self, gfx: &mut (impl Has<GraphicsContext> + HasMut<GraphicsContext>), canvas: &mut Canvas) -> GameResult<()> {
+        // Light can't make anything brighter than it already is
+        if self.ambient >= 1. {
+            return Ok(())
+        }
+        // Lights don't move so what they light up is only drawn once
+        let light_map = if let Some(img) = &self.light_map {
+            img
+        } else {
+            self.light_map = Some({
+                let format = Has::<GraphicsContext>::retrieve(gfx).surface_format();
+                let image = Image::new_canvas_image(gfx, format, self.grid.width() as u32 * 32, self.grid.height() as u32 * 32, 1);
+                let ambient = Color::new(self.ambient, self.ambient, self.ambient, 1.);
+                let mut canvas = Canvas::from_image(gfx, image.clone(), Some(ambient));
+                canvas.set_blend_mode(BlendMode::ADD);
+                for light in &self.lights {
+                    canvas.draw(&light.make_mesh(gfx, &self.palette, &self.grid), DrawParam::default());
+                }
+
+                canvas.finish(gfx)?;
+
+                image
+            });
+            self.light_map.as_ref().unwrap()
+        };
+
+        let blend_mode = canvas.blend_mode();
+        canvas.set_blend_mode(BlendMode::MULTIPLY);
+        canvas.draw(light_map, DrawParam::default());
+        canvas.set_blend_mode(blend_mode);
+
+        Ok(())
+    }
+}
@@ -50,6 +50,13 @@ pub fn is_solid(mat: &str) -> bool {
     MATS.read().unwrap()[mat].props.solid
 }
 
+#[inline]
+pub fn get_spr(mat: &str) -> Sstr {
+    ensure(mat);
+
+    MATS.read().unwrap()[mat].spr
+}
+
 #[inline]
 pub fn get_img<'a>(assets: &'a Assets, mat: &str) -> Ref<'a, Image> {
     ensure(mat);
@@ -138,6 +145,10 @@ impl Palette {
         self.materials.iter().position(|s| &mat == s).map(|i| i as u8)
     }
     #[inline]
+    pub fn materials(&self) -> &[&'static str] {
+        &self.materials
+    }
+    #[inline]
     pub fn len(&self) -> usize {
         self.materials.len()
     }
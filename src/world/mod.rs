@@ -2,17 +2,21 @@
 //! Stuff related to things in the world
 
 use crate::{
-    util::{Point2, Vector2, sstr, iterate_and_kill_one},
-    io::tex::Assets,
+    DELTA,
+    util::{Point2, Vector2, sstr, angle_to_vec, iterate_and_kill_one},
+    io::{tex::Assets, leaderboard::Ghost},
     obj::{
         player::{Player, WepSlots},
-        enemy::{Enemy, OldEnemy},
+        enemy::{Enemy, OldEnemy, SCAVENGE_RANGE, PICKUP_RANGE},
         health::Health,
         bullet::Bullet,
         grenade::Grenade,
         weapon::{WeaponInstance, WeaponDrop, WEAPONS},
         pickup::Pickup,
         decal::{Decal, OldDecoration},
+        security::{Camera, AlarmPanel},
+        corpse::{Corpse, Blood},
+        light::Light,
     }
 };
 use ggez::{
@@ -28,8 +32,28 @@ use bincode;
 
 mod material;
 pub use material::*;
+mod objective;
+pub use objective::*;
+mod trigger;
+pub use trigger::*;
+mod script;
+pub use script::*;
+mod score;
+pub use score::*;
+mod survival;
+pub use survival::*;
+mod difficulty;
+pub use difficulty::*;
+mod alarm;
+pub use alarm::*;
+mod bodies;
+pub use bodies::*;
+mod noise;
+pub use noise::*;
+mod fog;
+mod lighting;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 /// All the objects in the current world
 pub struct World {
     pub player: Player,
@@ -37,13 +61,40 @@ pub struct World {
     pub grid: Grid,
     pub canvas: Option<Image>,
     pub decal_queue: Vec<Decal>,
+    /// Decals already drawn onto the canvas
+    pub decals: Vec<Decal>,
     pub exit: Option<Point2>,
     pub intels: Vec<Point2>,
+    pub intels_collected: u16,
+    /// Checkpoints that haven't been reached yet
+    pub checkpoints: Vec<Point2>,
     pub enemies: Vec<Enemy>,
+    /// Id given to the next enemy spawned, continuing after the level's own
+    pub next_enemy_id: usize,
+    pub npcs: Vec<Player>,
+    pub objectives: Vec<ObjectiveState>,
+    pub triggers: Vec<Trigger>,
     pub bullets: Vec<Bullet<'static>>,
     pub grenades: Vec<Grenade>,
     pub weapons: Vec<WeaponDrop<'static>>,
     pub pickups: Vec<Pickup>,
+    pub difficulty: Difficulty,
+    /// Whether the whole level has been put on alert
+    pub alarm: bool,
+    pub alarm_panels: Vec<AlarmPanel>,
+    pub cameras: Vec<Camera>,
+    pub corpses: Vec<Corpse>,
+    /// Blood that hasn't dried yet
+    pub blood: Vec<Blood>,
+    /// The body the player is dragging
+    pub dragging: Option<usize>,
+    /// Whether what the player has no line of sight to is hidden
+    pub fog: bool,
+    pub lights: Vec<Light>,
+    /// How lit the level is where no light reaches, from 0 to 1
+    pub ambient: f32,
+    /// What the lights light up, drawn the first time it's needed
+    pub light_map: Option<Image>,
 }
 
 impl World {
@@ -64,11 +115,50 @@ impl World {
                 (pickup.pos - enemy.pl.obj.pos).norm() <= 16.
             });
             if let Some(pickup) = pickup {
-                let _action_done = pickup.apply(&mut enemy.pl.health);
+                let _action_done = pickup.apply(&mut enemy.pl, 1.);
             }
             enemy.pl.wep.init_active();
         }
     }
+    /// Lets enemies pick up the weapons they've reached, go for ones nearby when it's safe
+    /// and switch weapons when they run out of ammo
+    pub fn enemy_scavenge(&mut self) {
+        let World{ref mut enemies, ref mut weapons, ref palette, ref grid, ..} = *self;
+
+        for enemy in enemies.iter_mut() {
+            let pos = enemy.pl.obj.pos;
+            if let Some(i) = weapons.iter().position(|wd| (wd.pos - pos).norm() <= PICKUP_RANGE && enemy.wants(wd)) {
+                let drop = weapons.remove(i);
+                if enemy.tactics.scavenging == Some(drop.pos) {
+                    enemy.tactics.scavenging = None;
+                }
+                weapons.extend(enemy.pick_up(drop));
+            }
+
+            match enemy.tactics.scavenging {
+                // Give up if someone else got there first or something came up
+                Some(target) if enemy.behaviour.cur_target != Some(target) || !weapons.iter().any(|wd| wd.pos == target) => {
+                    enemy.tactics.scavenging = None;
+                }
+                Some(_) => (),
+                None if enemy.safe() => {
+                    let nearest = weapons.iter()
+                        .filter(|wd| (wd.pos - pos).norm() <= SCAVENGE_RANGE && enemy.wants(wd))
+                        .filter(|wd| grid.ray_cast(palette, pos, wd.pos - pos, true).full())
+                        .min_by(|a, b| (a.pos - pos).norm().total_cmp(&(b.pos - pos).norm()));
+                    if let Some(wd) = nearest {
+                        enemy.tactics.scavenging = Some(wd.pos);
+                        enemy.behaviour.go_to_then_go_back(pos, wd.pos);
+                    }
+                }
+                None => (),
+            }
+
+            if enemy.out_of_ammo() {
+                enemy.equip_best();
+            }
+        }
+    }
     pub fn player_pickup(&mut self) {
         let player = &mut self.player;
         {
@@ -88,7 +178,7 @@ impl World {
             (pickup.pos - player.obj.pos).norm() <= 16.
         });
         if let Some(pickup) = pickup {
-            let _action_done = pickup.apply(&mut player.health);
+            let _action_done = pickup.apply(player, self.difficulty.pickup_amount());
         }
         player.wep.init_active();
     }
@@ -100,6 +190,7 @@ impl World {
             for decal in self.decal_queue.drain(..) {
                 decal.preload(gfx, a)?;
                 decal.draw(&mut canvas, a, Color::WHITE);
+                self.decals.push(decal);
             }
 
             canvas.finish(gfx)?;
@@ -125,6 +216,28 @@ impl World {
 
         Ok(())
     }
+    /// A copy of the world that can be carried over to a new canvas
+    pub fn snapshot(&self) -> World {
+        let mut world = World {
+            canvas: None,
+            light_map: None,
+            decals: Vec::new(),
+            decal_queue: Vec::with_capacity(self.decals.len() + self.decal_queue.len()),
+            .. self.clone()
+        };
+        world.decal_queue.extend(self.decals.iter().cloned());
+        world.decal_queue.extend(self.decal_queue.iter().cloned());
+        world
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The state of a level when the player reached a checkpoint
+pub struct Checkpoint {
+    pub world: World,
+    pub time: usize,
+    pub script: Option<ScriptState>,
+    pub score: Score,
 }
 
 pub struct Statistics {
@@ -133,6 +246,31 @@ pub struct Statistics {
     pub health_left: Health,
     pub level: Level,
     pub weapon: WepSlots,
+    pub checkpoint: Option<Box<Checkpoint>>,
+    /// The exit the level was won through, see `Action::Exit`
+    pub exit: usize,
+    /// Weapons the player picked up during the level and left behind, as they were left
+    pub weapons_left: Vec<WeaponInstance<'static>>,
+    pub score: Score,
+    /// The player's input through the level, if it was played from the start
+    pub recording: Option<Ghost>,
+    /// Waves cleared in survival
+    pub waves_survived: Option<u32>,
+}
+
+impl Statistics {
+    #[inline]
+    pub fn kills(&self) -> usize {
+        self.level.enemies.len().saturating_sub(self.enemies_left)
+    }
+    #[inline]
+    pub fn breakdown(&self) -> Breakdown {
+        self.score.breakdown(self.time as f32 * DELTA, self.level.par_time, self.level.enemies.len())
+    }
+    /// Points earned in the level that can be spent between levels
+    pub fn points(&self) -> u32 {
+        self.breakdown().total.max(0) as u32
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -141,11 +279,30 @@ pub struct Level {
     pub grid: Grid,
     pub start_point: Option<Point2>,
     pub enemies: Vec<Enemy>,
+    pub npcs: Vec<Player>,
     pub exit: Option<Point2>,
     pub intels: Vec<Point2>,
+    pub checkpoints: Vec<Point2>,
+    /// Where enemies come from in survival
+    pub spawns: Vec<Point2>,
+    /// Alarm panels enemies can put the level on alert with
+    pub alarms: Vec<Point2>,
+    pub cameras: Vec<Camera>,
     pub pickups: Vec<(Point2, u8)>,
     pub decals: Vec<Decal>,
     pub weapons: Vec<WeaponDrop<'static>>,
+    pub objectives: Vec<Objective>,
+    pub triggers: Vec<Trigger>,
+    /// Path of the script run with the level
+    pub script: Option<String>,
+    /// Time in seconds to finish the level in for the full speed bonus
+    pub par_time: Option<f32>,
+    /// Whether the level is played with fog of war
+    pub fog: bool,
+    /// How lit the level is where no light reaches, from 0 to 1
+    pub ambient: f32,
+    /// Difficulty the level is played on instead of the one picked
+    pub difficulty: Option<Difficulty>,
 }
 
 impl Level {
@@ -155,17 +312,66 @@ impl Level {
             grid: Grid::new(width, height),
             start_point: None,
             enemies: Vec::new(),
+            npcs: Vec::new(),
             exit: None,
             intels: Vec::new(),
+            checkpoints: Vec::new(),
+            spawns: Vec::new(),
+            alarms: Vec::new(),
+            cameras: Vec::new(),
             pickups: Vec::new(),
             decals: Vec::new(),
             weapons: Vec::new(),
+            objectives: Vec::new(),
+            triggers: Vec::new(),
+            script: None,
+            par_time: None,
+            fog: false,
+            ambient: 1.,
+            difficulty: None,
+        }
+    }
+    /// Removes an enemy and the objectives and patrols referring to it, keeping others pointing at the right enemy
+    pub fn remove_enemy(&mut self, i: usize) -> Enemy {
+        self.objectives.retain(|o| !matches!(o.goal, Goal::KillTarget(t) if t == i));
+        for objective in &mut self.objectives {
+            if let Goal::KillTarget(ref mut t) = objective.goal {
+                if *t > i {
+                    *t -= 1;
+                }
+            }
+        }
+        for trigger in &mut self.triggers {
+            trigger.actions.retain(|a| !matches!(a, Action::Patrol{enemy, ..} if *enemy == i));
+            for action in &mut trigger.actions {
+                if let Action::Patrol{ref mut enemy, ..} = action {
+                    if *enemy > i {
+                        *enemy -= 1;
+                    }
+                }
+            }
+        }
+        self.enemies.remove(i)
+    }
+    /// Removes an NPC and the objectives protecting it, keeping other objectives pointing at the right NPC
+    pub fn remove_npc(&mut self, i: usize) -> Player {
+        self.objectives.retain(|o| !matches!(o.goal, Goal::Protect(t) if t == i));
+        for objective in &mut self.objectives {
+            if let Goal::Protect(ref mut t) = objective.goal {
+                if *t > i {
+                    *t -= 1;
+                }
+            }
         }
+        self.npcs.remove(i)
     }
     pub fn load<P: AsRef<Path>>(path: P) -> GameResult<Self> {
         info!("Loading level {}", path.as_ref().display());
 
-        let mut reader = BufReader::new(File::open(path)?);
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+    /// Reads a level in the level file format
+    pub fn read_from<R: BufRead>(mut reader: R) -> GameResult<Self> {
         let mut ret = Level::new(Palette::default(), 0, 0);
 
         // For support of older level files
@@ -209,12 +415,25 @@ impl Level {
                 }
                 "ENEMIES2" => ret.enemies = bincode::deserialize_from(&mut reader)
                     .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
+                "NPCS" => ret.npcs = bincode::deserialize_from(&mut reader)
+                    .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
                 "POINT GOAL" => ret.exit = Some(bincode::deserialize_from(&mut reader)
                     .map(|(x, y)| point!(x, y))
                     .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?),
                 "INTELS" => ret.intels = bincode::deserialize_from(&mut reader)
                     .map(|l: Vec<(f32, f32)>| l.into_iter().map(|(x, y)| point!(x, y)).collect())
                     .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
+                "CHECKPOINTS" => ret.checkpoints = bincode::deserialize_from(&mut reader)
+                    .map(|l: Vec<(f32, f32)>| l.into_iter().map(|(x, y)| point!(x, y)).collect())
+                    .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
+                "SPAWNS" => ret.spawns = bincode::deserialize_from(&mut reader)
+                    .map(|l: Vec<(f32, f32)>| l.into_iter().map(|(x, y)| point!(x, y)).collect())
+                    .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
+                "ALARMS" => ret.alarms = bincode::deserialize_from(&mut reader)
+                    .map(|l: Vec<(f32, f32)>| l.into_iter().map(|(x, y)| point!(x, y)).collect())
+                    .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
+                "CAMERAS" => ret.cameras = bincode::deserialize_from(&mut reader)
+                    .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
                 "DECORATIONS" => ret.decals = bincode::deserialize_from(&mut reader)
                     .map(|old_decs: Vec<OldDecoration>| old_decs.into_iter().map(|od| od.renew()).collect())
                     .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
@@ -229,6 +448,20 @@ impl Level {
                 "WEAPONS" => ret.weapons = bincode::deserialize_from(&mut reader)
                     .map(|l: Vec<((f32, f32), u8)>| l.into_iter().map(|((x, y), i)| WEAPONS[WEAPONS_OLD[i as usize]].make_drop(point!(x, y))).collect())
                     .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
+                "OBJECTIVES" => ret.objectives = bincode::deserialize_from(&mut reader)
+                    .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
+                "TRIGGERS" => ret.triggers = bincode::deserialize_from(&mut reader)
+                    .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
+                "SCRIPT" => ret.script = Some(bincode::deserialize_from(&mut reader)
+                    .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?),
+                "PAR" => ret.par_time = Some(bincode::deserialize_from(&mut reader)
+                    .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?),
+                "FOG" => ret.fog = bincode::deserialize_from(&mut reader)
+                    .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
+                "AMBIENT" => ret.ambient = bincode::deserialize_from(&mut reader)
+                    .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?,
+                "DIFFICULTY" => ret.difficulty = Some(bincode::deserialize_from(&mut reader)
+                    .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?),
                 "END" => break, 
                 _ => return Err(GameError::ResourceLoadError("Bad section".to_string()))
             }
@@ -239,9 +472,18 @@ impl Level {
     pub fn save<P: AsRef<Path>>(&self, path: P) -> GameResult<()> {
         info!("Saving {}", path.as_ref().display());
 
-        let mut file = File::create(path)?;
+        self.write_to(File::create(path)?)?;
+
+        info!("Level saved");
 
-        writeln!(file, "GRD")?;
+        Ok(())
+    }
+    /// Writes the level in the level file format
+    pub fn write_to<W: Write>(&self, mut file: W) -> GameResult<()> {
+        writeln!(file, "PALETTE")?;
+        bincode::serialize_into(&mut file, self.palette.materials())
+            .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        writeln!(file, "\nGRD")?;
         bincode::serialize_into(&mut file, &self.grid)
             .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
         if let Some(start) = self.start_point {
@@ -254,6 +496,11 @@ impl Level {
             bincode::serialize_into(&mut file, &self.enemies)
             .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
         }
+        if !self.npcs.is_empty() {
+            writeln!(file, "\nNPCS")?;
+            bincode::serialize_into(&mut file, &self.npcs)
+            .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        }
         if let Some(p) = self.exit {
             writeln!(file, "\nPOINT GOAL")?;
             bincode::serialize_into(&mut file, &(p.x, p.y))
@@ -265,6 +512,29 @@ impl Level {
             bincode::serialize_into(&mut file, &intels)
                 .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
         }
+        if !self.checkpoints.is_empty() {
+            writeln!(file, "\nCHECKPOINTS")?;
+            let checkpoints: Vec<_> = self.checkpoints.iter().map(|p| (p.x, p.y)).collect();
+            bincode::serialize_into(&mut file, &checkpoints)
+                .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        }
+        if !self.spawns.is_empty() {
+            writeln!(file, "\nSPAWNS")?;
+            let spawns: Vec<_> = self.spawns.iter().map(|p| (p.x, p.y)).collect();
+            bincode::serialize_into(&mut file, &spawns)
+                .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        }
+        if !self.alarms.is_empty() {
+            writeln!(file, "\nALARMS")?;
+            let alarms: Vec<_> = self.alarms.iter().map(|p| (p.x, p.y)).collect();
+            bincode::serialize_into(&mut file, &alarms)
+                .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        }
+        if !self.cameras.is_empty() {
+            writeln!(file, "\nCAMERAS")?;
+            bincode::serialize_into(&mut file, &self.cameras)
+                .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        }
         if !self.decals.is_empty() {
             writeln!(file, "\nDECS")?;
             bincode::serialize_into(&mut file, &self.decals)
@@ -282,11 +552,44 @@ impl Level {
             bincode::serialize_into(&mut file, &pickups)
                 .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
         }
+        if !self.objectives.is_empty() {
+            writeln!(file, "\nOBJECTIVES")?;
+            bincode::serialize_into(&mut file, &self.objectives)
+                .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        }
+        if !self.triggers.is_empty() {
+            writeln!(file, "\nTRIGGERS")?;
+            bincode::serialize_into(&mut file, &self.triggers)
+                .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        }
+        if let Some(script) = &self.script {
+            writeln!(file, "\nSCRIPT")?;
+            bincode::serialize_into(&mut file, script)
+                .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        }
+        if let Some(par_time) = self.par_time {
+            writeln!(file, "\nPAR")?;
+            bincode::serialize_into(&mut file, &par_time)
+                .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        }
+        if self.fog {
+            writeln!(file, "\nFOG")?;
+            bincode::serialize_into(&mut file, &self.fog)
+                .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        }
+        if self.ambient < 1. {
+            writeln!(file, "\nAMBIENT")?;
+            bincode::serialize_into(&mut file, &self.ambient)
+                .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        }
+        if let Some(difficulty) = self.difficulty {
+            writeln!(file, "\nDIFFICULTY")?;
+            bincode::serialize_into(&mut file, &difficulty)
+                .map_err(|e| GameError::ResourceLoadError(format!("{:?}", e)))?;
+        }
 
         writeln!(file, "\nEND")?;
 
-        info!("Level saved");
-
         Ok(())
     }
 }
@@ -482,6 +785,16 @@ impl Grid {
             }
         }
     }
+    /// Where rays fanning out from a point stop, in order of angle
+    ///
+    /// `rays + 1` rays are spread evenly from `start` to `start + sweep` radians and reach at most `range`
+    pub fn visibility_polygon(&self, pal: &Palette, from: Point2, range: f32, start: f32, sweep: f32, rays: u32) -> Vec<Point2> {
+        let step = sweep / rays as f32;
+
+        (0..=rays).map(|i| {
+            self.ray_cast(pal, from, range * angle_to_vec(start + i as f32 * step), true).into_point()
+        }).collect()
+    }
     /// Closest point on a line segment to a circle
     pub fn closest_point_of_line_to_circle(line_start: Point2, line_dist: Vector2, circle_center: Point2) -> Point2 {
         let c = circle_center - line_start;
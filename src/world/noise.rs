@@ -0,0 +1,31 @@
+//! Noises the player makes that enemies come to check out
+
+use crate::util::Point2;
+
+use super::World;
+
+/// How far away a gunshot can be heard
+pub const GUNSHOT_NOISE: f32 = 640.;
+/// How far away someone getting stabbed can be heard
+pub const MELEE_NOISE: f32 = 160.;
+/// How far away the player's footsteps can be heard
+pub const FOOTSTEP_NOISE: f32 = 140.;
+/// How far away the player's footsteps can be heard when sneaking
+pub const SNEAK_FOOTSTEP_NOISE: f32 = 36.;
+/// How far the player walks with each step
+pub const STRIDE: f32 = 48.;
+/// How close to a noise an enemy already heading there has to be going to not bother changing course
+const NOISE_SPREAD: f32 = 64.;
+
+impl World {
+    /// Makes enemies that can hear a noise and have nothing better to do come and check it out
+    pub fn noise(&mut self, pos: Point2, radius: f32) {
+        for enemy in self.enemies.iter_mut() {
+            let enemy_pos = enemy.pl.obj.pos;
+            let heading_there = enemy.behaviour.cur_target.map(|t| (t - pos).norm() <= NOISE_SPREAD).unwrap_or(false);
+            if (enemy_pos - pos).norm() <= radius && !enemy.busy() && enemy.spotted().is_none() && !heading_there {
+                enemy.behaviour.go_to_then_go_back(enemy_pos, pos);
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{
+    util::Point2,
+    DELTA,
+};
+
+use std::fmt::{self, Display};
+
+use super::{World, Level};
+
+/// Seconds the player has to stand next to a bomb to defuse it
+pub const DEFUSE_TIME: f32 = 3.;
+/// How close the player has to be to a bomb to defuse it
+pub const DEFUSE_RANGE: f32 = 32.;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Goal {
+    /// Kill every enemy in the level
+    KillAll,
+    /// Kill the enemy with the given index in the level
+    KillTarget(usize),
+    /// Collect a number of intels
+    CollectIntels(u16),
+    /// Get within `radius` of a point
+    Reach {
+        #[serde(with = "crate::io::save::Point2Def")]
+        pos: Point2,
+        radius: f32,
+    },
+    /// Stay alive for some seconds
+    Survive(f32),
+    /// Keep the NPC with the given index alive
+    Protect(usize),
+    /// Stand by the bomb long enough to defuse it before the time limit runs out
+    Defuse {
+        #[serde(with = "crate::io::save::Point2Def")]
+        pos: Point2,
+        time_limit: f32,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Objective {
+    pub goal: Goal,
+    /// The level can be won without completing this
+    pub optional: bool,
+    /// Only active once every required objective before it is done
+    pub ordered: bool,
+}
+
+impl Objective {
+    #[inline]
+    pub fn new(goal: Goal) -> Self {
+        Objective {
+            goal,
+            optional: false,
+            ordered: false,
+        }
+    }
+    /// The objectives of levels that don't define any
+    ///
+    /// Collect every intel and then reach the exit if there is one, otherwise kill everyone.
+    pub fn defaults(level: &Level) -> Vec<Self> {
+        match level.exit {
+            Some(pos) => {
+                let mut objectives = Vec::with_capacity(2);
+                if !level.intels.is_empty() {
+                    objectives.push(Objective::new(Goal::CollectIntels(level.intels.len() as u16)));
+                }
+                objectives.push(Objective {
+                    ordered: true,
+                    .. Objective::new(Goal::Reach{pos, radius: 32.})
+                });
+                objectives
+            }
+            None => vec![Objective::new(Goal::KillAll)],
+        }
+    }
+}
+
+impl Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.goal {
+            Goal::KillAll => write!(f, "Kill all enemies")?,
+            Goal::KillTarget(i) => write!(f, "Kill enemy #{}", i)?,
+            Goal::CollectIntels(n) => write!(f, "Collect {} intel", n)?,
+            Goal::Reach{pos, radius} => write!(f, "Reach {:.0} (radius {:.0})", pos, radius)?,
+            Goal::Survive(secs) => write!(f, "Survive {:.0}s", secs)?,
+            Goal::Protect(i) => write!(f, "Protect NPC #{}", i)?,
+            Goal::Defuse{pos, time_limit} => write!(f, "Defuse bomb at {:.0} within {:.0}s", pos, time_limit)?,
+        }
+        if self.optional {
+            write!(f, " (optional)")?;
+        }
+        if self.ordered {
+            write!(f, " (ordered)")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Status {
+    /// Waiting for an earlier objective to be done
+    Locked,
+    Active,
+    Complete,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// An objective being played
+pub struct ObjectiveState {
+    pub objective: Objective,
+    pub status: Status,
+    /// How much has been done, e.g. intels collected or seconds survived
+    pub done: f32,
+    /// How much has to be done
+    pub total: f32,
+    /// Time the objective became active
+    started: Option<f32>,
+}
+
+impl ObjectiveState {
+    pub fn new(objective: Objective, world: &World) -> Self {
+        let total = match objective.goal {
+            Goal::KillAll => world.enemies.len() as f32,
+            Goal::CollectIntels(n) => f32::from(n),
+            Goal::Survive(secs) => secs,
+            Goal::Defuse{..} => DEFUSE_TIME,
+            Goal::KillTarget(_) | Goal::Reach{..} | Goal::Protect(_) => 1.,
+        };
+
+        ObjectiveState {
+            objective,
+            status: Status::Locked,
+            done: 0.,
+            total,
+            started: None,
+        }
+    }
+    /// Whether this counts as done for winning the level and unlocking ordered objectives
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        match (self.status, &self.objective.goal) {
+            (Status::Complete, _) => true,
+            // An NPC that's still alive has been protected
+            (Status::Active, Goal::Protect(_)) => true,
+            _ => false,
+        }
+    }
+    /// Updates the status of the objective
+    ///
+    /// `time` is the time in seconds since the level began
+    pub fn update(&mut self, world: &World, time: f32, locked: bool) {
+        match self.status {
+            Status::Complete | Status::Failed => return,
+            Status::Locked | Status::Active if locked => {
+                self.status = Status::Locked;
+                return
+            }
+            Status::Locked | Status::Active => self.status = Status::Active,
+        }
+        let started = *self.started.get_or_insert(time);
+        let player = &world.player;
+
+        let complete = match self.objective.goal {
+            Goal::KillAll => {
+                // Enemies can be spawned during the level
+                let left = world.enemies.len() as f32;
+                self.total = self.total.max(self.done + left);
+                self.done = self.total - left;
+                world.enemies.is_empty()
+            }
+            Goal::KillTarget(id) => !world.enemies.iter().any(|e| e.id == id),
+            Goal::CollectIntels(_) => {
+                self.done = f32::from(world.intels_collected).min(self.total);
+                self.done >= self.total
+            }
+            Goal::Reach{pos, radius} => (pos - player.obj.pos).norm() < radius,
+            Goal::Survive(secs) => {
+                self.done = (time - started).min(secs);
+                self.done >= secs
+            }
+            Goal::Protect(i) => {
+                if world.npcs.get(i).map(|npc| npc.health.is_dead()).unwrap_or(true) {
+                    self.status = Status::Failed;
+                }
+                false
+            }
+            Goal::Defuse{pos, time_limit} => {
+                if (pos - player.obj.pos).norm() <= DEFUSE_RANGE {
+                    self.done += DELTA;
+                }
+                if self.done >= DEFUSE_TIME {
+                    true
+                } else {
+                    if time - started >= time_limit {
+                        self.status = Status::Failed;
+                    }
+                    false
+                }
+            }
+        };
+
+        if complete {
+            self.status = Status::Complete;
+        }
+    }
+    /// Seconds left before a timed objective fails
+    pub fn time_left(&self, time: f32) -> Option<f32> {
+        match (self.objective.goal, self.started) {
+            (Goal::Defuse{time_limit, ..}, Some(started)) => Some((time_limit - (time - started)).max(0.)),
+            _ => None,
+        }
+    }
+    /// Text describing the progress of the objective for the HUD
+    pub fn progress_text(&self, time: f32) -> String {
+        let desc = match self.objective.goal {
+            Goal::KillAll => format!("Kill all enemies {:.0}/{:.0}", self.done, self.total),
+            Goal::KillTarget(_) => "Eliminate the target".to_owned(),
+            Goal::CollectIntels(_) => format!("Collect intel {:.0}/{:.0}", self.done, self.total),
+            Goal::Reach{..} => "Reach the exit".to_owned(),
+            Goal::Survive(_) => format!("Survive {:.0}/{:.0}s", self.done, self.total),
+            Goal::Protect(_) => "Protect the NPC".to_owned(),
+            Goal::Defuse{..} => match self.time_left(time) {
+                Some(left) => format!("Defuse the bomb {:.0}% ({:.1}s left)", 100. * self.done / self.total, left),
+                None => "Defuse the bomb".to_owned(),
+            },
+        };
+        let mark = match self.status {
+            Status::Locked => "[-]",
+            Status::Active => "[ ]",
+            Status::Complete => "[x]",
+            Status::Failed => "[!]",
+        };
+
+        if self.objective.optional {
+            format!("{} {} (optional)\n", mark, desc)
+        } else {
+            format!("{} {}\n", mark, desc)
+        }
+    }
+}
+
+impl World {
+    /// Updates all objectives in order
+    pub fn update_objectives(&mut self, time: f32) {
+        let mut objectives = std::mem::take(&mut self.objectives);
+
+        let mut blocked = false;
+        for objective in &mut objectives {
+            objective.update(self, time, blocked && objective.objective.ordered);
+            if !objective.objective.optional && !objective.is_done() {
+                blocked = true;
+            }
+        }
+
+        self.objectives = objectives;
+    }
+    /// Every required objective is done
+    pub fn objectives_won(&self) -> bool {
+        self.objectives.iter().all(|o| o.objective.optional || o.is_done())
+    }
+    /// A required objective has failed
+    pub fn objectives_failed(&self) -> bool {
+        self.objectives.iter().any(|o| !o.objective.optional && o.status == Status::Failed)
+    }
+}
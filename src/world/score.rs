@@ -0,0 +1,207 @@
+//! Keeping score of how a level was played
+
+use crate::obj::health::Health;
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+/// Name kills with grenades are recorded under
+pub const GRENADE: &str = "grenade";
+
+const KILL_POINTS: i32 = 100;
+const BACKSTAB_POINTS: i32 = 50;
+/// Points for hitting every shot
+const ACCURACY_POINTS: f32 = 500.;
+const STEALTH_POINTS: i32 = 1000;
+/// Points for finishing on par time, going down to nothing at twice the par time
+const SPEED_POINTS: f32 = 1000.;
+const DAMAGE_PENALTY: f32 = 2.;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/// What the player has done during a level
+pub struct Score {
+    /// Bullets fired by the player
+    pub shots_fired: u32,
+    /// Bullets fired by the player that hit an enemy
+    pub shots_hit: u32,
+    /// Kills by weapon id
+    pub kills: BTreeMap<String, u32>,
+    pub backstabs: u32,
+    /// Health and armour lost
+    pub damage_taken: f32,
+    /// Whether any enemy has seen the player
+    pub detected: bool,
+}
+
+impl Score {
+    pub fn kill(&mut self, weapon: &str) {
+        *self.kills.entry(weapon.to_owned()).or_insert(0) += 1;
+    }
+    #[inline]
+    pub fn total_kills(&self) -> u32 {
+        self.kills.values().sum()
+    }
+    /// Fraction of shots that hit, if any were fired
+    pub fn accuracy(&self) -> Option<f32> {
+        if self.shots_fired > 0 {
+            Some(self.shots_hit as f32 / self.shots_fired as f32)
+        } else {
+            None
+        }
+    }
+    /// Records damage taken between two points in time, ignoring healing
+    pub fn record_damage(&mut self, before: Health, after: Health) {
+        let lost = (before.hp + before.armour) - (after.hp + after.armour);
+        if lost > 0. {
+            self.damage_taken += lost;
+        }
+    }
+    /// Works out the points for each part of the score and the grade
+    ///
+    /// `time` and `par_time` are in seconds, `enemies` is the number of enemies in the level
+    pub fn breakdown(&self, time: f32, par_time: Option<f32>, enemies: usize) -> Breakdown {
+        let mut parts = Vec::with_capacity(6);
+        let mut max = 0;
+
+        parts.push(("Kills", self.total_kills() as i32 * KILL_POINTS));
+        max += enemies as i32 * KILL_POINTS;
+        if self.backstabs > 0 {
+            parts.push(("Backstabs", self.backstabs as i32 * BACKSTAB_POINTS));
+        }
+        if let Some(accuracy) = self.accuracy() {
+            parts.push(("Accuracy", (accuracy * ACCURACY_POINTS) as i32));
+            max += ACCURACY_POINTS as i32;
+        }
+        if !self.detected {
+            parts.push(("Stealth", STEALTH_POINTS));
+        }
+        max += STEALTH_POINTS;
+        if let Some(par) = par_time {
+            let speed = (2. - time / par).clamp(0., 1.);
+            parts.push(("Speed", (speed * SPEED_POINTS) as i32));
+            max += SPEED_POINTS as i32;
+        }
+        if self.damage_taken > 0. {
+            parts.push(("Damage taken", -(self.damage_taken * DAMAGE_PENALTY) as i32));
+        }
+
+        let total = parts.iter().map(|&(_, p)| p).sum();
+        Breakdown {
+            grade: Grade::from_fraction(total as f32 / max.max(1) as f32),
+            parts,
+            total,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakdown {
+    /// The points given for each part of the score
+    pub parts: Vec<(&'static str, i32)>,
+    pub total: i32,
+    pub grade: Grade,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Grade {
+    S,
+    A,
+    B,
+    C,
+    D,
+}
+
+impl Grade {
+    /// The grade for a score that's the given fraction of the best ordinary score
+    pub fn from_fraction(f: f32) -> Self {
+        if f >= 0.9 {
+            Grade::S
+        } else if f >= 0.75 {
+            Grade::A
+        } else if f >= 0.55 {
+            Grade::B
+        } else if f >= 0.35 {
+            Grade::C
+        } else {
+            Grade::D
+        }
+    }
+}
+
+impl Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Grade::S => "S",
+            Grade::A => "A",
+            Grade::B => "B",
+            Grade::C => "C",
+            Grade::D => "D",
+        }.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(breakdown: &Breakdown, part: &str) -> Option<i32> {
+        breakdown.parts.iter().find(|&&(p, _)| p == part).map(|&(_, points)| points)
+    }
+
+    #[test]
+    fn grades_go_down_with_the_fraction() {
+        assert_eq!(Grade::from_fraction(1.2), Grade::S);
+        assert_eq!(Grade::from_fraction(0.9), Grade::S);
+        assert_eq!(Grade::from_fraction(0.8), Grade::A);
+        assert_eq!(Grade::from_fraction(0.6), Grade::B);
+        assert_eq!(Grade::from_fraction(0.4), Grade::C);
+        assert_eq!(Grade::from_fraction(0.), Grade::D);
+        assert_eq!(Grade::from_fraction(-1.), Grade::D);
+    }
+
+    #[test]
+    fn flawless_run_gets_full_marks() {
+        let mut score = Score{shots_fired: 4, shots_hit: 4, ..Score::default()};
+        score.kill("glock");
+        score.kill(GRENADE);
+
+        let breakdown = score.breakdown(30., Some(60.), 2);
+        assert_eq!(points(&breakdown, "Kills"), Some(2 * KILL_POINTS));
+        assert_eq!(points(&breakdown, "Accuracy"), Some(ACCURACY_POINTS as i32));
+        assert_eq!(points(&breakdown, "Stealth"), Some(STEALTH_POINTS));
+        assert_eq!(points(&breakdown, "Speed"), Some(SPEED_POINTS as i32));
+        assert_eq!(points(&breakdown, "Damage taken"), None);
+        assert_eq!(breakdown.total, 2 * KILL_POINTS + ACCURACY_POINTS as i32 + STEALTH_POINTS + SPEED_POINTS as i32);
+        assert_eq!(breakdown.grade, Grade::S);
+    }
+
+    #[test]
+    fn sloppy_run_loses_points() {
+        let score = Score {
+            shots_fired: 10,
+            shots_hit: 1,
+            damage_taken: 150.,
+            detected: true,
+            ..Score::default()
+        };
+
+        let breakdown = score.breakdown(150., Some(60.), 5);
+        assert_eq!(points(&breakdown, "Kills"), Some(0));
+        assert_eq!(points(&breakdown, "Accuracy"), Some(ACCURACY_POINTS as i32 / 10));
+        assert_eq!(points(&breakdown, "Stealth"), None);
+        // More than twice the par time
+        assert_eq!(points(&breakdown, "Speed"), Some(0));
+        assert_eq!(points(&breakdown, "Damage taken"), Some(-300));
+        assert_eq!(breakdown.grade, Grade::D);
+    }
+
+    #[test]
+    fn parts_without_data_are_left_out() {
+        let breakdown = Score::default().breakdown(10., None, 0);
+        assert_eq!(points(&breakdown, "Accuracy"), None);
+        assert_eq!(points(&breakdown, "Speed"), None);
+        // Only stealth counts towards the grade
+        assert_eq!(breakdown.total, STEALTH_POINTS);
+        assert_eq!(breakdown.grade, Grade::S);
+    }
+}
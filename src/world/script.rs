@@ -0,0 +1,297 @@
+//! Level scripts written in [Rhai](https://rhai.rs)
+//!
+//! A script can define the callbacks `on_start()`, `on_tick(time)`,
+//! `on_enemy_killed(id, x, y)` and `on_pickup(what)`.
+//! Every callback has a map bound to `this` that can be used to keep state between calls.
+//!
+//! Scripts never touch the world directly. Queries read a copy of the world made before each
+//! callback, and changes are queued up and carried out once the callback returns.
+
+use crate::{
+    util::Point2,
+    obj::{Object, pickup::{Pickup, PICKUPS}, weapon::WEAPONS},
+};
+use ggez::{GameResult, error::GameError};
+use rhai::{Engine, AST, Scope, Dynamic, Map, CallFnOptions, FuncArgs, FLOAT, INT, format_map_as_json};
+use serde::{Serialize, Serializer, Deserialize, Deserializer, de::Error};
+
+use std::{cell::RefCell, rc::Rc, fs::read_to_string, path::Path};
+
+use super::{World, Action, Objective, ObjectiveState, Goal, Status};
+
+/// Operations a script may use per callback before it is stopped
+const MAX_OPERATIONS: u64 = 100_000;
+
+#[derive(Debug, Clone)]
+/// A change to the world requested by a script
+pub enum ScriptCommand {
+    Action(Action),
+    SpawnWeapon {
+        id: String,
+        pos: Point2,
+    },
+    SpawnPickup {
+        pickup: u8,
+        pos: Point2,
+    },
+    AddObjective(Objective),
+    SetObjective {
+        index: usize,
+        status: Status,
+    },
+}
+
+#[derive(Debug, Clone)]
+/// Something that happened in the world that scripts get told about
+pub enum ScriptEvent {
+    EnemyKilled {
+        id: usize,
+        pos: Point2,
+    },
+    /// The player picked up an intel, a pickup or a weapon
+    Pickup(String),
+}
+
+#[derive(Debug, Default)]
+/// The parts of the world scripts can query
+struct Snapshot {
+    time: f32,
+    player_pos: Point2,
+    player_hp: f32,
+    player_armour: f32,
+    enemies: Vec<(usize, Point2, f32)>,
+    intels_collected: u16,
+    intels_left: usize,
+    objectives: Vec<Status>,
+}
+
+impl Snapshot {
+    fn update(&mut self, world: &World, time: f32) {
+        self.time = time;
+        self.player_pos = world.player.obj.pos;
+        self.player_hp = world.player.health.hp;
+        self.player_armour = world.player.health.armour;
+        self.enemies.clear();
+        self.enemies.extend(world.enemies.iter().map(|e| (e.id, e.pl.obj.pos, e.pl.health.hp)));
+        self.intels_collected = world.intels_collected;
+        self.intels_left = world.intels.len();
+        self.objectives.clear();
+        self.objectives.extend(world.objectives.iter().map(|o| o.status));
+    }
+}
+
+#[derive(Debug, Clone)]
+/// The state of a script saved with a checkpoint
+pub struct ScriptState(Dynamic);
+
+// The state is kept as JSON in save files since it can be any map
+impl Serialize for ScriptState {
+    fn serialize<S: Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        match self.0.read_lock::<Map>() {
+            Some(map) => format_map_as_json(&map).serialize(ser),
+            None => "{}".serialize(ser),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ScriptState {
+    fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, D::Error> {
+        let json = String::deserialize(des)?;
+        Engine::new_raw().parse_json(json, true)
+            .map(|map| ScriptState(Dynamic::from_map(map)))
+            .map_err(|e| D::Error::custom(e.to_string()))
+    }
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    state: Dynamic,
+    started: bool,
+    snapshot: Rc<RefCell<Snapshot>>,
+    commands: Rc<RefCell<Vec<ScriptCommand>>>,
+}
+
+impl Script {
+    pub fn load<P: AsRef<Path>>(path: P) -> GameResult<Self> {
+        info!("Loading script {}", path.as_ref().display());
+
+        let source = read_to_string(path)?;
+        let snapshot = Rc::new(RefCell::new(Snapshot::default()));
+        let commands = Rc::new(RefCell::new(Vec::new()));
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|s| info!("{}", s));
+        register_queries(&mut engine, &snapshot);
+        register_commands(&mut engine, &commands);
+
+        let ast = engine.compile(source)
+            .map_err(|e| GameError::ResourceLoadError(format!("{}", e)))?;
+
+        Ok(Script {
+            engine,
+            ast,
+            state: Dynamic::from_map(Map::new()),
+            started: false,
+            snapshot,
+            commands,
+        })
+    }
+    fn call(&mut self, name: &str, args: impl FuncArgs) {
+        if !self.ast.iter_functions().any(|f| f.name == name) {
+            return
+        }
+
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.state);
+        if let Err(e) = self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, name, args) {
+            error!("Script error in {}: {}", name, e);
+        }
+    }
+    /// Runs the callbacks for this tick and the events that happened during it
+    ///
+    /// `time` is the time in seconds since the level began
+    pub fn update(&mut self, world: &World, time: f32, events: impl IntoIterator<Item=ScriptEvent>) {
+        self.snapshot.borrow_mut().update(world, time);
+
+        if !self.started {
+            self.started = true;
+            self.call("on_start", ());
+        }
+        for event in events {
+            match event {
+                ScriptEvent::EnemyKilled{id, pos} => self.call("on_enemy_killed", (id as INT, pos.x, pos.y)),
+                ScriptEvent::Pickup(what) => self.call("on_pickup", (what,)),
+            }
+        }
+        self.call("on_tick", (time,));
+    }
+    pub fn save_state(&self) -> ScriptState {
+        ScriptState(self.state.clone())
+    }
+    /// Continues from a saved state without running `on_start` again
+    pub fn restore_state(&mut self, ScriptState(state): ScriptState) {
+        self.state = state;
+        self.started = true;
+        self.commands.borrow_mut().clear();
+    }
+    /// Takes the commands queued up by the script since last time
+    pub fn take_commands(&mut self) -> Vec<ScriptCommand> {
+        std::mem::take(&mut *self.commands.borrow_mut())
+    }
+}
+
+fn register_queries(engine: &mut Engine, snapshot: &Rc<RefCell<Snapshot>>) {
+    let s = snapshot.clone();
+    engine.register_fn("time", move || s.borrow().time);
+    let s = snapshot.clone();
+    engine.register_fn("player_x", move || s.borrow().player_pos.x);
+    let s = snapshot.clone();
+    engine.register_fn("player_y", move || s.borrow().player_pos.y);
+    let s = snapshot.clone();
+    engine.register_fn("player_hp", move || s.borrow().player_hp);
+    let s = snapshot.clone();
+    engine.register_fn("player_armour", move || s.borrow().player_armour);
+    let s = snapshot.clone();
+    engine.register_fn("enemy_count", move || s.borrow().enemies.len() as INT);
+    let s = snapshot.clone();
+    engine.register_fn("enemy_id", move |i: INT| s.borrow().enemies.get(i as usize).map(|e| e.0 as INT).unwrap_or(-1));
+    let s = snapshot.clone();
+    engine.register_fn("enemy_x", move |i: INT| s.borrow().enemies.get(i as usize).map(|e| e.1.x).unwrap_or(FLOAT::NAN));
+    let s = snapshot.clone();
+    engine.register_fn("enemy_y", move |i: INT| s.borrow().enemies.get(i as usize).map(|e| e.1.y).unwrap_or(FLOAT::NAN));
+    let s = snapshot.clone();
+    engine.register_fn("enemy_hp", move |i: INT| s.borrow().enemies.get(i as usize).map(|e| e.2).unwrap_or(0.));
+    let s = snapshot.clone();
+    engine.register_fn("intels_collected", move || INT::from(s.borrow().intels_collected));
+    let s = snapshot.clone();
+    engine.register_fn("intels_left", move || s.borrow().intels_left as INT);
+    let s = snapshot.clone();
+    engine.register_fn("objective_count", move || s.borrow().objectives.len() as INT);
+    let s = snapshot.clone();
+    engine.register_fn("objective_status", move |i: INT| match s.borrow().objectives.get(i as usize) {
+        Some(Status::Locked) => "locked",
+        Some(Status::Active) => "active",
+        Some(Status::Complete) => "complete",
+        Some(Status::Failed) => "failed",
+        None => "",
+    });
+}
+
+fn register_commands(engine: &mut Engine, commands: &Rc<RefCell<Vec<ScriptCommand>>>) {
+    let c = commands.clone();
+    engine.register_fn("message", move |msg: &str| c.borrow_mut().push(ScriptCommand::Action(Action::Message(msg.to_owned()))));
+    let c = commands.clone();
+    engine.register_fn("play_sound", move |snd: &str| c.borrow_mut().push(ScriptCommand::Action(Action::PlaySound(snd.to_owned()))));
+    let c = commands.clone();
+    engine.register_fn("win", move || c.borrow_mut().push(ScriptCommand::Action(Action::EndLevel{won: true})));
+    let c = commands.clone();
+    engine.register_fn("lose", move || c.borrow_mut().push(ScriptCommand::Action(Action::EndLevel{won: false})));
+    let c = commands.clone();
+    engine.register_fn("exit", move |exit: INT| c.borrow_mut().push(ScriptCommand::Action(Action::Exit(exit as usize))));
+    let c = commands.clone();
+    engine.register_fn("open_door", move |x: INT, y: INT| c.borrow_mut().push(ScriptCommand::Action(Action::OpenDoor{x: x as u16, y: y as u16})));
+    let c = commands.clone();
+    engine.register_fn("spawn_enemy", move |x: FLOAT, y: FLOAT| {
+        c.borrow_mut().push(ScriptCommand::Action(Action::SpawnEnemy{obj: Object::new(point!(x, y)), weapon: None}))
+    });
+    let c = commands.clone();
+    engine.register_fn("spawn_enemy", move |x: FLOAT, y: FLOAT, wep: &str| {
+        c.borrow_mut().push(ScriptCommand::Action(Action::SpawnEnemy{obj: Object::new(point!(x, y)), weapon: Some(wep.to_owned())}))
+    });
+    let c = commands.clone();
+    engine.register_fn("spawn_weapon", move |id: &str, x: FLOAT, y: FLOAT| {
+        c.borrow_mut().push(ScriptCommand::SpawnWeapon{id: id.to_owned(), pos: point!(x, y)})
+    });
+    let c = commands.clone();
+    engine.register_fn("spawn_pickup", move |name: &str, x: FLOAT, y: FLOAT| {
+        match PICKUPS.iter().position(|p| p.spr.trim_start_matches("pickups/") == name) {
+            Some(i) => c.borrow_mut().push(ScriptCommand::SpawnPickup{pickup: i as u8, pos: point!(x, y)}),
+            None => error!("No pickup called {}", name),
+        }
+    });
+    let c = commands.clone();
+    engine.register_fn("objective_kill_all", move || c.borrow_mut().push(ScriptCommand::AddObjective(Objective::new(Goal::KillAll))));
+    let c = commands.clone();
+    engine.register_fn("objective_kill", move |id: INT| c.borrow_mut().push(ScriptCommand::AddObjective(Objective::new(Goal::KillTarget(id as usize)))));
+    let c = commands.clone();
+    engine.register_fn("objective_collect", move |n: INT| c.borrow_mut().push(ScriptCommand::AddObjective(Objective::new(Goal::CollectIntels(n as u16)))));
+    let c = commands.clone();
+    engine.register_fn("objective_reach", move |x: FLOAT, y: FLOAT, radius: FLOAT| {
+        c.borrow_mut().push(ScriptCommand::AddObjective(Objective::new(Goal::Reach{pos: point!(x, y), radius})))
+    });
+    let c = commands.clone();
+    engine.register_fn("objective_survive", move |secs: FLOAT| c.borrow_mut().push(ScriptCommand::AddObjective(Objective::new(Goal::Survive(secs)))));
+    let c = commands.clone();
+    engine.register_fn("complete_objective", move |i: INT| c.borrow_mut().push(ScriptCommand::SetObjective{index: i as usize, status: Status::Complete}));
+    let c = commands.clone();
+    engine.register_fn("fail_objective", move |i: INT| c.borrow_mut().push(ScriptCommand::SetObjective{index: i as usize, status: Status::Failed}));
+}
+
+impl World {
+    /// Carries out a command from a script
+    ///
+    /// ### Returns
+    /// The action to be handled by the game if the command is one
+    pub fn apply_script_command(&mut self, cmd: ScriptCommand) -> Option<Action> {
+        match cmd {
+            ScriptCommand::Action(action) => return Some(action),
+            ScriptCommand::SpawnWeapon{id, pos} => match WEAPONS.get(&*id) {
+                Some(weapon) => self.weapons.push(weapon.make_drop(pos)),
+                None => error!("No weapon called {}", id),
+            },
+            ScriptCommand::SpawnPickup{pickup, pos} => self.pickups.push(Pickup::new(pos, pickup)),
+            ScriptCommand::AddObjective(objective) => {
+                let objective = ObjectiveState::new(objective, self);
+                self.objectives.push(objective);
+            }
+            ScriptCommand::SetObjective{index, status} => match self.objectives.get_mut(index) {
+                Some(objective) => objective.status = status,
+                None => error!("No objective #{}", index),
+            },
+        }
+        None
+    }
+}
@@ -0,0 +1,151 @@
+//! Survival, where waves of enemies keep coming from the spawn points of a level
+
+use crate::{
+    DELTA,
+    util::Point2,
+    obj::{
+        Object,
+        enemy::Enemy,
+        health::Health,
+        pickup::Pickup,
+        weapon::{WeaponSlot, WEAPONS},
+    },
+};
+
+use rand::{thread_rng, Rng, seq::SliceRandom};
+
+use super::{World, Level, Grid, GRENADE};
+
+/// Seconds before the first wave
+const FIRST_BREAK: f32 = 3.;
+/// Seconds between a wave being cleared and the next one
+const WAVE_BREAK: f32 = 8.;
+/// How far from a spawn point enemies can appear
+const SPAWN_SPREAD: f32 = 24.;
+/// Tries at finding an open spot near a spawn point before using the spawn point itself
+const SPAWN_TRIES: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+/// A kind of enemy that can turn up in a wave
+struct Archetype {
+    /// The first wave it turns up in
+    min_wave: u32,
+    health: Health,
+    /// The kind of weapon it gets, picked from all the weapons in that slot
+    slot: WeaponSlot,
+}
+
+const ARCHETYPES: [Archetype; 4] = [
+    // Grunt
+    Archetype{min_wave: 1, health: Health{hp: 100., armour: 0.}, slot: WeaponSlot::Holster},
+    // Guard
+    Archetype{min_wave: 2, health: Health{hp: 100., armour: 50.}, slot: WeaponSlot::Holster},
+    // Soldier
+    Archetype{min_wave: 3, health: Health{hp: 100., armour: 50.}, slot: WeaponSlot::Sling},
+    // Heavy
+    Archetype{min_wave: 5, health: Health{hp: 150., armour: 100.}, slot: WeaponSlot::Sling},
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaveUpdate {
+    None,
+    Started(u32),
+    Cleared(u32),
+}
+
+#[derive(Debug, Clone)]
+pub struct Survival {
+    /// The current wave, 0 before the first one
+    pub wave: u32,
+    /// Waves whose enemies have all been killed
+    pub cleared: u32,
+    /// Seconds until the next wave, if the current one has been cleared
+    pub countdown: Option<f32>,
+    /// Where the waves come from
+    spawns: Vec<Point2>,
+}
+
+impl Survival {
+    /// Sets up survival on a level
+    ///
+    /// ### Returns
+    /// `None` if the level has neither spawn points nor enemies to take the place of them
+    pub fn new(level: &Level) -> Option<Self> {
+        // Levels without spawn points use where the enemies were placed
+        let spawns: Vec<Point2> = if level.spawns.is_empty() {
+            level.enemies.iter().map(|e| e.pl.obj.pos).collect()
+        } else {
+            level.spawns.clone()
+        };
+        if spawns.is_empty() {
+            return None
+        }
+
+        Some(Survival {
+            wave: 0,
+            cleared: 0,
+            countdown: Some(FIRST_BREAK),
+            spawns,
+        })
+    }
+    /// Number of enemies in a wave
+    #[inline]
+    fn wave_size(wave: u32) -> u32 {
+        2 + 2 * wave
+    }
+    /// Starts the next wave when it's time and checks if the current one has been cleared
+    pub fn update(&mut self, world: &mut World, level: &Level) -> WaveUpdate {
+        match &mut self.countdown {
+            Some(countdown) => {
+                *countdown -= DELTA;
+                if *countdown <= 0. {
+                    self.countdown = None;
+                    self.wave += 1;
+                    self.spawn_wave(world);
+                    WaveUpdate::Started(self.wave)
+                } else {
+                    WaveUpdate::None
+                }
+            }
+            None if world.enemies.is_empty() => {
+                self.cleared = self.wave;
+                self.countdown = Some(WAVE_BREAK);
+                // Resupply before the next wave
+                world.pickups = level.pickups.iter().map(|&(p, i)| Pickup::new(p, i)).collect();
+                WaveUpdate::Cleared(self.wave)
+            }
+            None => WaveUpdate::None,
+        }
+    }
+    fn spawn_wave(&mut self, world: &mut World) {
+        let mut rng = thread_rng();
+        let archetypes: Vec<_> = ARCHETYPES.iter().filter(|a| a.min_wave <= self.wave).collect();
+
+        for _ in 0..Self::wave_size(self.wave) {
+            let archetype = archetypes.choose(&mut rng).unwrap();
+            let spawn = *self.spawns.choose(&mut rng).unwrap();
+            // Spread the enemies out, as long as that doesn't put them in a wall
+            let pos = (0..SPAWN_TRIES)
+                .map(|_| spawn + vector!(rng.gen_range(-SPAWN_SPREAD..=SPAWN_SPREAD), rng.gen_range(-SPAWN_SPREAD..=SPAWN_SPREAD)))
+                .find(|&p| !world.grid.is_solid_tuple(&world.palette, Grid::snap(p)))
+                .unwrap_or(spawn);
+
+            let mut enemy = Enemy::new(Object::new(pos));
+            enemy.pl.health = archetype.health;
+            enemy.pl.wep.utilities.set(GRENADE, world.difficulty.enemy_grenades());
+            let weapons: Vec<_> = WEAPONS.values()
+                .filter(|w| matches!((w.slot, archetype.slot), (WeaponSlot::Holster, WeaponSlot::Holster) | (WeaponSlot::Sling, WeaponSlot::Sling)))
+                .collect();
+            if let Some(weapon) = weapons.choose(&mut rng) {
+                *enemy.pl.wep.insert(weapon) = Some(weapon.make_instance());
+                enemy.pl.wep.init_active();
+            }
+            // Go looking for the player
+            enemy.behaviour.chase_then_wander(world.player.obj.pos);
+            enemy.id = world.next_enemy_id;
+            world.next_enemy_id += 1;
+
+            world.enemies.push(enemy);
+        }
+    }
+}
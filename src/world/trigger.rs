@@ -0,0 +1,215 @@
+use crate::{
+    util::Point2,
+    obj::{Object, enemy::Enemy, decal::Decal, weapon::WEAPONS},
+};
+
+use std::fmt::{self, Display};
+
+use super::{World, Palette, Status, GRENADE, get_spr};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// A rectangular area in the world
+pub struct Area {
+    #[serde(with = "crate::io::save::Point2Def")]
+    pub min: Point2,
+    #[serde(with = "crate::io::save::Point2Def")]
+    pub max: Point2,
+}
+
+impl Area {
+    /// Makes the area spanned by two opposite corners
+    pub fn from_corners(a: Point2, b: Point2) -> Self {
+        Area {
+            min: point!(a.x.min(b.x), a.y.min(b.y)),
+            max: point!(a.x.max(b.x), a.y.max(b.y)),
+        }
+    }
+    #[inline]
+    pub fn contains(&self, p: Point2) -> bool {
+        p.x >= self.min.x && p.x < self.max.x && p.y >= self.min.y && p.y < self.max.y
+    }
+    #[inline]
+    pub fn width(&self) -> f32 {
+        self.max.x - self.min.x
+    }
+    #[inline]
+    pub fn height(&self) -> f32 {
+        self.max.y - self.min.y
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Condition {
+    /// The player walks into the area
+    Enter,
+    /// The objective with the given index is completed
+    ObjectiveComplete(usize),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Action {
+    /// Spawns an enemy holding the weapon with the given id
+    SpawnEnemy {
+        obj: Object,
+        weapon: Option<String>,
+    },
+    /// Opens the door on the tile
+    OpenDoor {
+        x: u16,
+        y: u16,
+    },
+    /// Shows a message on the screen
+    Message(String),
+    PlaySound(String),
+    /// Changes the path of the enemy with the given index in the level
+    Patrol {
+        enemy: usize,
+        #[serde(with = "crate::io::save::Point2DefVec")]
+        path: Vec<Point2>,
+        cyclical: bool,
+    },
+    /// Ends the level, either won or lost
+    EndLevel {
+        won: bool,
+    },
+    /// Wins the level through the exit with the given number, letting campaigns branch
+    Exit(usize),
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::SpawnEnemy{obj, weapon: Some(wep)} => write!(f, "spawn enemy with {} at {:.0}", wep, obj.pos),
+            Action::SpawnEnemy{obj, weapon: None} => write!(f, "spawn unarmed enemy at {:.0}", obj.pos),
+            Action::OpenDoor{x, y} => write!(f, "open door at ({}, {})", x, y),
+            Action::Message(msg) => write!(f, "show {:?}", msg),
+            Action::PlaySound(snd) => write!(f, "play {}", snd),
+            Action::Patrol{enemy, path, cyclical} => write!(f, "enemy #{} patrols {} points{}", enemy, path.len(), if *cyclical { " cyclically" } else { "" }),
+            Action::EndLevel{won: true} => write!(f, "win the level"),
+            Action::EndLevel{won: false} => write!(f, "lose the level"),
+            Action::Exit(exit) => write!(f, "win through exit {}", exit),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trigger {
+    pub area: Area,
+    pub condition: Condition,
+    pub actions: Vec<Action>,
+    /// Only fire the first time the condition is met
+    pub once: bool,
+    #[serde(skip)]
+    pub(crate) fired: bool,
+    #[serde(skip)]
+    pub(crate) player_inside: bool,
+}
+
+impl Trigger {
+    pub fn new(area: Area) -> Self {
+        Trigger {
+            area,
+            condition: Condition::Enter,
+            actions: Vec::new(),
+            once: true,
+            fired: false,
+            player_inside: false,
+        }
+    }
+}
+
+impl Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.condition {
+            Condition::Enter => write!(f, "on enter")?,
+            Condition::ObjectiveComplete(i) => write!(f, "on objective {} complete", i)?,
+        }
+        if self.once {
+            write!(f, " once")?;
+        }
+        write!(f, ":")?;
+        for action in &self.actions {
+            write!(f, " {};", action)?;
+        }
+        Ok(())
+    }
+}
+
+impl World {
+    /// Checks the conditions of all triggers
+    ///
+    /// ### Returns
+    /// The actions of the triggers that fired
+    pub fn fire_triggers(&mut self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        let player_pos = self.player.obj.pos;
+
+        for trigger in &mut self.triggers {
+            let fire = match trigger.condition {
+                Condition::Enter => {
+                    let was_inside = trigger.player_inside;
+                    trigger.player_inside = trigger.area.contains(player_pos);
+                    trigger.player_inside && !was_inside
+                }
+                Condition::ObjectiveComplete(i) => !trigger.fired && self.objectives.get(i).map(|o| o.status == Status::Complete).unwrap_or(false),
+            };
+
+            if fire && !(trigger.once && trigger.fired) {
+                trigger.fired = true;
+                actions.extend(trigger.actions.iter().cloned());
+            }
+        }
+
+        actions
+    }
+    /// Carries out the actions that only affect the world
+    pub fn apply_action(&mut self, action: &Action) {
+        match action {
+            Action::SpawnEnemy{obj, weapon} => {
+                let mut enemy = Enemy::new(obj.clone());
+                // Continuing after the level's ids keeps spawned enemies apart from the ones objectives were set on
+                enemy.id = self.next_enemy_id;
+                self.next_enemy_id += 1;
+                enemy.pl.wep.utilities.set(GRENADE, self.difficulty.enemy_grenades());
+                if let Some(wep) = weapon {
+                    if let Some(weapon) = WEAPONS.get(&**wep) {
+                        let _ = enemy.pl.wep.add_weapon(weapon.make_instance());
+                        enemy.pl.wep.init_active();
+                    } else {
+                        warn!("no weapon called {}", wep);
+                    }
+                }
+                self.enemies.push(enemy);
+            }
+            &Action::OpenDoor{x, y} => self.open_door(x, y),
+            Action::Patrol{enemy, path, cyclical} => {
+                if let Some(enemy) = self.enemies.iter_mut().find(|e| e.id == *enemy) {
+                    if *cyclical {
+                        enemy.behaviour.patrol_path(path.clone());
+                    } else {
+                        enemy.behaviour.path_then_wander(path.clone());
+                    }
+                }
+            }
+            Action::Message(_) | Action::PlaySound(_) | Action::EndLevel{..} | Action::Exit(_) => (),
+        }
+    }
+    /// Turns a door tile into an open door
+    pub fn open_door(&mut self, x: u16, y: u16) {
+        if self.grid.get(x, y).and_then(|m| self.palette.get(m)) != Some("door") {
+            return warn!("no door at ({}, {})", x, y);
+        }
+        let open = match self.palette.find("door_open") {
+            Some(i) => i,
+            None => {
+                self.palette = self.palette.clone().and(&Palette::new(vec!["door_open"]));
+                self.palette.find("door_open").unwrap()
+            }
+        };
+        self.grid.insert(x, y, open);
+
+        // Paint the open door over the closed one on the world canvas
+        let centre = point!(f32::from(x) * 32. + 16., f32::from(y) * 32. + 16.);
+        self.decal_queue.push(Decal::new(Object::new(centre), get_spr("door_open")));
+    }
+}